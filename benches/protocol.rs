@@ -0,0 +1,75 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use kvs::{
+    codec::Connection,
+    protocol::{parse, Value},
+};
+
+fn values() -> Vec<(&'static str, Value<'static>)> {
+    let nested = Value::Array(
+        (0..10)
+            .map(|_| Value::Array((0..10).map(Value::Positive).collect()))
+            .collect(),
+    );
+    let map = (0..1000u64)
+        .map(|i| {
+            (
+                Value::Bytes(Cow::Owned(format!("key:{i}").into_bytes())).encode(),
+                Value::Positive(i),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    vec![
+        ("small int", Value::Positive(7)),
+        // The longest string whose length fits into the first byte.
+        ("long string", Value::String(Cow::Owned("x".repeat(31)))),
+        ("nested array", nested),
+        ("large map", Value::Map(map)),
+    ]
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for (name, value) in values() {
+        group.throughput(Throughput::Bytes(value.clone().encode().len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &value, |b, value| {
+            b.iter(|| black_box(value.clone()).encode())
+        });
+    }
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, value) in values() {
+        let encoded = value.encode();
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &encoded, |b, encoded| {
+            b.iter(|| parse(black_box(encoded)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn round_trip(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("round trip");
+    for (name, value) in values() {
+        group.throughput(Throughput::Bytes(value.clone().encode().len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &value, |b, value| {
+            b.to_async(&runtime).iter(|| async {
+                let (client, server) = tokio::io::duplex(64 * 1024);
+                let mut writer = Connection::new(tokio::io::empty(), client);
+                let mut reader = Connection::new(server, tokio::io::sink());
+                writer.write_frame(value.clone()).await.unwrap();
+                writer.flush_writer().await.unwrap();
+                reader.read_frame_owned().await.unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode, round_trip);
+criterion_main!(benches);
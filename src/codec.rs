@@ -1,5 +1,7 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use crate::error::{self, ProtocolError};
-use bytes::BytesMut;
+use bytes::{Buf, BytesMut};
 use tokio::{
     io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter},
     net::{
@@ -8,16 +10,93 @@ use tokio::{
     },
 };
 
-use crate::protocol::{parse, Value};
+use crate::protocol::{parse, parse_with, peek_major, Major, ParseOptions, Value};
+
+/// Protocol version connections start with: CBOR-like framing.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Protocol versions a connection can switch to with `HELLO`.
+pub const SUPPORTED_PROTOCOLS: &[u8] = &[PROTOCOL_VERSION];
+
+/// Id of the next connection, see [`Connection::id`].
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// How [`Connection::read_frame_owned`] recovers from a malformed frame.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Resync {
+    /// Fail with the parse error, which usually ends the connection.
+    #[default]
+    Off,
+    /// Discard bytes one at a time until the rest of the buffer starts with a command: an array
+    /// whose first element is a string, or the start of one if more bytes have yet to arrive.
+    /// Other values are skipped too, since any single byte below `0x18` is a whole integer.
+    NextHeader,
+    /// Discard everything up to and including the next occurrence of the marker. Peers send the
+    /// marker before every frame, so it can not be confused with the middle of one. The marker
+    /// must not be empty.
+    Marker(Vec<u8>),
+}
+
+/// Reads one frame from a blocking reader, for tools working on dumps and logs without a
+/// runtime. Returns `Ok(None)` at the end of the input.
+///
+/// Only the bytes of the frame are consumed from `reader`, whatever follows is left for the next
+/// call. If the input ends in the middle of a frame, returns
+/// [`crate::error::ProtocolError::ZeroRead`].
+pub fn read_frame_sync<R: std::io::BufRead>(
+    reader: &mut R,
+) -> error::Result<Option<Value<'static>>> {
+    let mut pending = Vec::new();
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            if pending.is_empty() {
+                return Ok(None);
+            }
+            return Err(ProtocolError::ZeroRead);
+        }
+        let buffered = pending.len();
+        pending.extend_from_slice(buf);
+        match parse(&pending) {
+            Ok((rest, value)) => {
+                let value = value.to_owned();
+                reader.consume(pending.len() - rest.len() - buffered);
+                return Ok(Some(value));
+            }
+            Err(nom::Err::Incomplete(_)) => {
+                let read = pending.len() - buffered;
+                reader.consume(read);
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
 
 /// Wrappers around [`tokio::io::AsyncRead`] and [`tokio::io::AsyncWrite`] to work with
 /// [`crate::protocol::Value`]. It uses buffered write.
 ///
-/// After you write some value to the stream, you need to flush it manyally.
+/// After you write some value to the stream, you need to flush it manyally, or end the
+/// connection with [`Connection::close`].
 pub struct Connection<R, W> {
     pub read_half: R,
     pub write_half: BufWriter<W>,
+    /// Options used to parse every frame read from the stream.
+    pub parse_options: ParseOptions,
+    /// Protocol version negotiated with `HELLO`, one of [`SUPPORTED_PROTOCOLS`].
+    pub protocol: u8,
+    /// Unique id of the connection, assigned when it is created.
+    pub id: u64,
+    /// Name the client set with `CLIENT SETNAME`.
+    pub name: Option<String>,
+    /// How malformed frames are skipped, see [`Resync`].
+    pub resync: Resync,
+    /// Bytes discarded by resyncing so far.
+    pub skipped: u64,
     buf: BytesMut,
+    /// Length of the last frame parsed from the start of `buf`, see [`Connection::remaining`].
+    consumed: usize,
+    /// Frames are encoded here before being written, so the buffer is reused across writes.
+    scratch: BytesMut,
 }
 
 impl<'s> Connection<ReadHalf<'s>, WriteHalf<'s>> {
@@ -37,29 +116,158 @@ where
         Self {
             read_half,
             write_half: BufWriter::new(write_half),
+            parse_options: ParseOptions::default(),
+            protocol: PROTOCOL_VERSION,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name: None,
+            resync: Resync::Off,
+            skipped: 0,
             buf: BytesMut::new(),
+            consumed: 0,
+            scratch: BytesMut::new(),
         }
     }
 
     /// Reads some amount of bytes from the stream and parses it into [`crate::protocol::Value`].
     ///
-    /// If the number of bytes is 0, returns [`crate::error::ProtocolError`].
+    /// If the peer closed the stream, returns [`crate::error::ProtocolError::ConnectionClosed`].
     pub async fn read_frame(&mut self) -> error::Result<Value<'_>> {
         self.buf.clear();
+        self.consumed = 0;
         let read = self.read_half.read_buf(&mut self.buf).await?;
         if read == 0 {
-            return Err(ProtocolError::ZeroRead);
+            return Err(ProtocolError::ConnectionClosed);
+        }
+        let (rest, value) = parse_with(&self.buf[..read], &self.parse_options)?;
+        self.consumed = read - rest.len();
+        Ok(value)
+    }
+
+    /// Bytes read along with the last frame but not parsed yet, e.g. pipelined frames which
+    /// followed it in the same read, or everything read if parsing failed.
+    ///
+    /// The bytes are dropped by the next read.
+    pub fn remaining(&self) -> &[u8] {
+        &self.buf[self.consumed..]
+    }
+
+    /// Reads from the stream until a whole frame is buffered and returns it as an owned
+    /// [`crate::protocol::Value`].
+    ///
+    /// Unlike [`Connection::read_frame`], the frame may span any number of reads. If the peer
+    /// closed the stream before sending anything, returns
+    /// [`crate::error::ProtocolError::ConnectionClosed`], and if it did so in the middle of a frame,
+    /// returns [`crate::error::ProtocolError::ZeroRead`].
+    ///
+    /// Malformed bytes are skipped as configured by [`Connection::resync`] instead of failing.
+    pub async fn read_frame_owned(&mut self) -> error::Result<Value<'static>> {
+        self.buf.clear();
+        self.consumed = 0;
+        loop {
+            let read = self.read_half.read_buf(&mut self.buf).await?;
+            if read == 0 && self.buf.is_empty() {
+                return Err(ProtocolError::ConnectionClosed);
+            }
+            if read == 0 {
+                return Err(ProtocolError::ZeroRead);
+            }
+            loop {
+                match parse_with(&self.buf[..], &self.parse_options) {
+                    Ok((rest, value)) => {
+                        self.consumed = self.buf.len() - rest.len();
+                        return Ok(value.to_owned());
+                    }
+                    Err(nom::Err::Incomplete(_)) => break,
+                    Err(err) if self.resync == Resync::Off => return Err(err.into()),
+                    Err(_) => self.skip_malformed(),
+                }
+            }
         }
-        Ok(parse(&self.buf[..read])?.1)
+    }
+
+    /// Discards the malformed bytes at the start of the buffer, see [`Resync`].
+    fn skip_malformed(&mut self) {
+        let skip = match &self.resync {
+            Resync::Off => 0,
+            Resync::NextHeader => (1..self.buf.len())
+                .find(|&start| self.starts_command(&self.buf[start..]))
+                .unwrap_or(self.buf.len()),
+            Resync::Marker(marker) => self
+                .buf
+                .windows(marker.len())
+                .position(|window| window == marker)
+                .map_or(self.buf.len(), |position| position + marker.len()),
+        };
+        self.skipped += skip as u64;
+        self.buf.advance(skip);
+    }
+
+    /// Whether `input` starts with a command, or with what may still become one.
+    fn starts_command(&self, input: &[u8]) -> bool {
+        match parse_with(input, &self.parse_options) {
+            Ok((_, Value::Array(items))) => matches!(items.first(), Some(Value::String(_))),
+            Ok(_) => false,
+            // Array lengths live in the header, so the name follows right after it.
+            Err(nom::Err::Incomplete(_)) => {
+                let name = &input[1..];
+                peek_major(input).is_ok_and(|major| major == Major::Array)
+                    && (name.is_empty() || peek_major(name).is_ok_and(|m| m == Major::String))
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Like [`Connection::read_frame_owned`], but buffers the frame in `buf` and returns a value
+    /// borrowing it, so the caller decides how long the buffer lives and when it is reused.
+    ///
+    /// `buf` is cleared first.
+    pub async fn read_frame_into<'b>(&mut self, buf: &'b mut BytesMut) -> error::Result<Value<'b>> {
+        buf.clear();
+        loop {
+            let read = self.read_half.read_buf(buf).await?;
+            if read == 0 && buf.is_empty() {
+                return Err(ProtocolError::ConnectionClosed);
+            }
+            if read == 0 {
+                return Err(ProtocolError::ZeroRead);
+            }
+            // The value can not be returned from inside the loop while `buf` is still borrowed
+            // mutably by the next read, so the complete frame is parsed once more below.
+            match parse_with(&buf[..], &self.parse_options) {
+                Ok(_) => break,
+                Err(nom::Err::Incomplete(_)) => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(parse_with(&buf[..], &self.parse_options)?.1)
     }
 
     pub async fn write_frame(&mut self, data: Value<'_>) -> error::Result<()> {
-        Ok(self.write_half.write_all(&data.encode()[..]).await?)
+        self.scratch.clear();
+        data.encode_into(&mut self.scratch);
+        Ok(self.write_half.write_all(&self.scratch).await?)
+    }
+
+    /// Writes `data` as a length-delimited frame, see [`Value::encode_delimited_into`].
+    pub async fn write_frame_delimited(&mut self, data: Value<'_>) -> error::Result<()> {
+        self.scratch.clear();
+        data.encode_delimited_into(&mut self.scratch);
+        Ok(self.write_half.write_all(&self.scratch).await?)
     }
 
     pub async fn flush_writer(&mut self) -> std::io::Result<()> {
         self.write_half.flush().await
     }
+
+    /// Flushes buffered frames and shuts down the write half.
+    ///
+    /// Dropping a connection loses whatever is still buffered: `Drop` can not be async, so it has
+    /// no way to flush. End connections with `close` instead of dropping them.
+    pub async fn close(mut self) -> error::Result<()> {
+        self.write_half.flush().await?;
+        self.write_half.shutdown().await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -68,10 +276,10 @@ mod tests {
 
     use bytes::BytesMut;
     use nom::AsBytes;
-    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio::io::AsyncRead;
 
     use crate::{
-        codec::Connection,
+        codec::{read_frame_sync, Connection, Resync},
         command::{
             entry::CommandEntry,
             get::{Get, EMPTY},
@@ -79,7 +287,9 @@ mod tests {
             ping::Ping,
             set::{GetSet, Set},
         },
-        protocol::{parse, Value},
+        db::Db,
+        protocol::Value,
+        testing::TestWriter,
     };
 
     struct TestStream {
@@ -97,54 +307,26 @@ mod tests {
                     buf.put_slice(command.encode().encode().as_bytes());
                     std::task::Poll::Ready(Ok(()))
                 }
-                None => std::task::Poll::Ready(Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "the stream is empty",
-                ))),
+                None => std::task::Poll::Ready(Err(std::io::Error::other("the stream is empty"))),
             }
         }
     }
 
-    struct TestWriter {
-        values: Vec<Value<'static>>,
-    }
-
-    impl TestWriter {
-        pub fn new() -> Self {
-            Self { values: vec![] }
-        }
+    /// Yields the given chunks of raw bytes one read at a time.
+    struct ChunkedStream {
+        chunks: Vec<Vec<u8>>,
     }
 
-    impl AsyncWrite for TestWriter {
-        fn poll_write(
+    impl AsyncRead for ChunkedStream {
+        fn poll_read(
             mut self: std::pin::Pin<&mut Self>,
             _: &mut std::task::Context<'_>,
-            buf: &[u8],
-        ) -> std::task::Poll<Result<usize, std::io::Error>> {
-            let len = buf.len();
-            match parse(buf) {
-                Ok(value) => {
-                    self.values.push(value.1.to_owned());
-                    std::task::Poll::Ready(Ok(len))
-                }
-                Err(_) => std::task::Poll::Ready(Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "parse error",
-                ))),
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            if !self.chunks.is_empty() {
+                let chunk = self.chunks.remove(0);
+                buf.put_slice(&chunk);
             }
-        }
-
-        fn poll_flush(
-            self: std::pin::Pin<&mut Self>,
-            _: &mut std::task::Context<'_>,
-        ) -> std::task::Poll<Result<(), std::io::Error>> {
-            std::task::Poll::Ready(Ok(()))
-        }
-
-        fn poll_shutdown(
-            self: std::pin::Pin<&mut Self>,
-            _: &mut std::task::Context<'_>,
-        ) -> std::task::Poll<Result<(), std::io::Error>> {
             std::task::Poll::Ready(Ok(()))
         }
     }
@@ -158,7 +340,7 @@ mod tests {
         let writer = TestWriter::new();
 
         let mut connection = Connection::new(reader, writer);
-        let db = Arc::new(sharded::Map::new());
+        let db = Arc::new(Db::default());
 
         let payload = connection.read_frame().await;
         assert!(payload.is_ok());
@@ -190,11 +372,11 @@ mod tests {
         let writer = TestWriter { values: vec![] };
 
         let mut connection = Connection::new(reader, writer);
-        let db = Arc::new(sharded::Map::new());
+        let db = Arc::new(Db::default());
 
         {
             let db = db.clone();
-            let (key, mut shard) = db.write(BytesMut::from(&b"test2"[..]));
+            let (key, mut shard) = db.map.write(BytesMut::from(&b"test2"[..]));
             shard.insert(key, Value::<'static>::Positive(42));
         }
 
@@ -246,7 +428,7 @@ mod tests {
         };
         let writer = TestWriter::new();
         let mut connection = Connection::new(reader, writer);
-        let db = Arc::new(sharded::Map::new());
+        let db = Arc::new(Db::default());
         let payload = connection.read_frame().await;
         assert!(payload.is_ok());
         let payload = payload.unwrap();
@@ -294,7 +476,7 @@ mod tests {
         };
         let writer = TestWriter::new();
         let mut connection = Connection::new(reader, writer);
-        let db = Arc::new(sharded::Map::new());
+        let db = Arc::new(Db::default());
         let payload = connection.read_frame().await;
         assert!(payload.is_ok());
         let payload = payload.unwrap();
@@ -348,7 +530,7 @@ mod tests {
         let writer = TestWriter::new();
 
         let mut connection = Connection::new(reader, writer);
-        let db = Arc::new(sharded::Map::new());
+        let db = Arc::new(Db::default());
         for _ in 0..3 {
             let payload = connection.read_frame().await;
             assert!(payload.is_ok());
@@ -362,4 +544,202 @@ mod tests {
             vec![Value::Positive(0), Value::Positive(1), Value::Positive(101)]
         );
     }
+
+    #[tokio::test]
+    async fn read_frame_owned_reassembles_segments() {
+        let command = CommandEntry::Set(Set {
+            key: BytesMut::from(&b"test"[..]),
+            value: Value::String(Cow::Borrowed("some value")),
+        });
+        let encoded = command.clone().encode().encode();
+        let reader = ChunkedStream {
+            chunks: encoded.chunks(3).map(|c| c.to_vec()).collect(),
+        };
+        let mut connection = Connection::new(reader, TestWriter::new());
+
+        let payload = connection.read_frame_owned().await;
+        assert!(payload.is_ok());
+        let command_read = CommandEntry::parse(payload.unwrap());
+        assert!(command_read.is_ok());
+        assert_eq!(command_read.unwrap(), command);
+    }
+
+    #[tokio::test]
+    async fn remaining_after_pipelined_frame() {
+        let first = CommandEntry::Ping(Ping).encode().encode();
+        let second = CommandEntry::Get(Get {
+            key: BytesMut::from("key"),
+        })
+        .encode()
+        .encode();
+        let reader = ChunkedStream {
+            chunks: vec![[&first[..], &second[..]].concat()],
+        };
+        let mut connection = Connection::new(reader, TestWriter::new());
+
+        let frame = connection.read_frame().await.unwrap();
+        assert_eq!(
+            CommandEntry::parse(frame).unwrap(),
+            CommandEntry::Ping(Ping)
+        );
+        assert_eq!(connection.remaining(), &second[..]);
+    }
+
+    #[test_case::test_case(Resync::NextHeader, b""; "next header")]
+    #[test_case::test_case(Resync::Marker(b"SYNC".to_vec()), b"SYNC"; "marker")]
+    #[tokio::test]
+    async fn resync_after_garbage(resync: Resync, marker: &[u8]) {
+        let first = CommandEntry::Ping(Ping);
+        let second = CommandEntry::Get(Get {
+            key: BytesMut::from(&b"key"[..]),
+        });
+        // A float header, small integers, an array of an integer and a string with invalid
+        // UTF-8, none of which is a command.
+        let garbage = [0xE0, 0x05, 0x17, 0x00, 0x81, 0x00, 0xF3, 0x61, 0xFF];
+        let mut chunk = garbage.to_vec();
+        chunk.extend_from_slice(marker);
+        chunk.extend_from_slice(&second.clone().encode().encode());
+        let chunks = vec![first.clone().encode().encode().to_vec(), chunk];
+
+        let mut connection = Connection::new(ChunkedStream { chunks }, TestWriter::new());
+        connection.resync = resync;
+        let value = connection.read_frame_owned().await.unwrap();
+        assert_eq!(CommandEntry::parse(value).unwrap(), first);
+        let value = connection.read_frame_owned().await.unwrap();
+        assert_eq!(CommandEntry::parse(value).unwrap(), second);
+        assert_eq!(connection.skipped, (garbage.len() + marker.len()) as u64);
+    }
+
+    #[tokio::test]
+    async fn garbage_without_resync() {
+        let chunks = vec![vec![0xE0, 0xF3]];
+        let mut connection = Connection::new(ChunkedStream { chunks }, TestWriter::new());
+        assert!(matches!(
+            connection.read_frame_owned().await,
+            Err(crate::error::ProtocolError::Parse(_))
+        ));
+        assert_eq!(connection.skipped, 0);
+    }
+
+    #[test]
+    fn read_frames_from_blocking_reader() {
+        let values = vec![
+            Value::Positive(500),
+            Value::Array(vec![
+                Value::String(Cow::Borrowed("GET")),
+                Value::Bytes(Cow::Borrowed(b"key")),
+            ]),
+            Value::Error(Cow::Borrowed("oops")),
+        ];
+        let mut payload = vec![];
+        for value in values.clone() {
+            payload.extend_from_slice(&value.encode());
+        }
+        // A tiny buffer, so frames span several reads and reads span several frames.
+        let mut reader = std::io::BufReader::with_capacity(3, std::io::Cursor::new(&payload));
+        for value in values {
+            assert_eq!(read_frame_sync(&mut reader).unwrap(), Some(value));
+        }
+        assert_eq!(read_frame_sync(&mut reader).unwrap(), None);
+
+        assert!(matches!(
+            read_frame_sync(&mut std::io::Cursor::new(&payload[..2])),
+            Err(crate::error::ProtocolError::ZeroRead)
+        ));
+    }
+
+    #[tokio::test]
+    async fn read_frame_into_caller_buffer() {
+        let first = CommandEntry::Get(Get {
+            key: BytesMut::from(&b"first"[..]),
+        });
+        let second = CommandEntry::Ping(Ping);
+        let encoded = first.clone().encode().encode();
+        let mut chunks: Vec<Vec<u8>> = encoded.chunks(3).map(|c| c.to_vec()).collect();
+        chunks.push(second.clone().encode().encode().to_vec());
+        let mut connection = Connection::new(ChunkedStream { chunks }, TestWriter::new());
+
+        let mut buf = BytesMut::new();
+        let value = connection.read_frame_into(&mut buf).await.unwrap();
+        assert_eq!(CommandEntry::parse(value).unwrap(), first);
+        let value = connection.read_frame_into(&mut buf).await.unwrap();
+        assert_eq!(CommandEntry::parse(value).unwrap(), second);
+        assert!(matches!(
+            connection.read_frame_into(&mut buf).await,
+            Err(crate::error::ProtocolError::ConnectionClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn read_frame_owned_truncated() {
+        let encoded = CommandEntry::Ping(Ping).encode().encode();
+        let reader = ChunkedStream {
+            chunks: vec![encoded[..encoded.len() - 1].to_vec()],
+        };
+        let mut connection = Connection::new(reader, TestWriter::new());
+
+        assert!(matches!(
+            connection.read_frame_owned().await,
+            Err(crate::error::ProtocolError::ZeroRead)
+        ));
+    }
+
+    #[tokio::test]
+    async fn clean_close() {
+        let reader = ChunkedStream { chunks: vec![] };
+        let mut connection = Connection::new(reader, TestWriter::new());
+
+        assert!(matches!(
+            connection.read_frame().await,
+            Err(crate::error::ProtocolError::ConnectionClosed)
+        ));
+        assert!(matches!(
+            connection.read_frame_owned().await,
+            Err(crate::error::ProtocolError::ConnectionClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn close_flushes() {
+        let mut written = vec![];
+        let mut connection = Connection::new(tokio::io::empty(), &mut written);
+        connection
+            .write_frame(Value::String(Cow::Borrowed("PONG")))
+            .await
+            .unwrap();
+        connection.close().await.unwrap();
+        assert_eq!(
+            crate::protocol::parse(&written).unwrap().1,
+            Value::String(Cow::Borrowed("PONG"))
+        );
+    }
+
+    #[tokio::test]
+    async fn scratch_buffer_reused() {
+        let mut connection = Connection::new(tokio::io::empty(), TestWriter::new());
+        let frame = Value::Array(vec![Value::String(Cow::Borrowed("frame")); 10]);
+        connection.write_frame(frame.clone()).await.unwrap();
+        let capacity = connection.scratch.capacity();
+        let address = connection.scratch.as_ptr();
+        for _ in 0..1000 {
+            connection.write_frame(frame.clone()).await.unwrap();
+        }
+        assert_eq!(connection.scratch.capacity(), capacity);
+        assert_eq!(connection.scratch.as_ptr(), address);
+        connection.flush_writer().await.unwrap();
+        assert_eq!(connection.write_half.get_ref().values.len(), 1001);
+    }
+
+    #[tokio::test]
+    async fn lossy_strings() {
+        let reader = ChunkedStream {
+            chunks: vec![vec![0b011_00011, b'h', 0xFF, b'i']],
+        };
+        let mut connection = Connection::new(reader, TestWriter::new());
+        connection.parse_options.lossy_strings = true;
+
+        let payload = connection.read_frame().await;
+        assert!(payload.is_ok());
+        assert_eq!(payload.unwrap(), Value::String(Cow::Borrowed("h\u{FFFD}i")));
+    }
 }
@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    error::{CommandError, ProtocolError},
+    protocol::{Value, EMPTY},
+};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Aggregate {
+    Min,
+    Max,
+    Sum,
+}
+
+/// Replies with the minimum, maximum or sum of the numbers at `keys`, see
+/// [`Value::coerce_number`].
+///
+/// Missing keys are skipped and nil is replied if none of the keys exist. Values which are not a
+/// number are rejected with [`CommandError::NotANumber`], a sum which does not fit into an integer
+/// with [`CommandError::OutOfRange`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct NumAgg {
+    pub op: Aggregate,
+    pub keys: Vec<BytesMut>,
+}
+
+impl NumAgg {
+    fn aggregate(&self, db: &crate::db::Db) -> Result<Option<i128>, CommandError> {
+        let mut result = None;
+        for key in &self.keys {
+            let Some(value) = db.map.get_owned(key) else {
+                continue;
+            };
+            let n = value.coerce_number().ok_or(CommandError::NotANumber)?;
+            result = Some(match (self.op, result) {
+                (_, None) => n,
+                (Aggregate::Min, Some(acc)) => n.min(acc),
+                (Aggregate::Max, Some(acc)) => n.max(acc),
+                (Aggregate::Sum, Some(acc)) => n + acc,
+            });
+        }
+        Ok(result)
+    }
+}
+
+impl Command for NumAgg {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match self.aggregate(&db) {
+            Ok(Some(n)) => {
+                Value::from_number(n).unwrap_or_else(|| db.error_reply(CommandError::OutOfRange))
+            }
+            Ok(None) => Value::Error(Cow::Borrowed(EMPTY)),
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let [Value::String(op), keys @ ..] = req.as_ref() else {
+            return Err(ProtocolError::Command);
+        };
+        let op = match op.as_ref() {
+            "MIN" => Aggregate::Min,
+            "MAX" => Aggregate::Max,
+            "SUM" => Aggregate::Sum,
+            _ => return Err(ProtocolError::Command),
+        };
+        if keys.is_empty() {
+            return Err(ProtocolError::Command);
+        }
+        let keys = keys
+            .iter()
+            .map(|key| match key {
+                Value::Bytes(key) => Ok(BytesMut::from(key.as_bytes())),
+                _ => Err(ProtocolError::Command),
+            })
+            .collect::<crate::error::Result<_>>()?;
+        Ok(Self { op, keys })
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let op = match self.op {
+            Aggregate::Min => "MIN",
+            Aggregate::Max => "MAX",
+            Aggregate::Sum => "SUM",
+        };
+        let mut array = vec![
+            Value::String(Cow::Borrowed("NUMAGG")),
+            Value::String(Cow::Borrowed(op)),
+        ];
+        array.extend(
+            self.keys
+                .iter()
+                .map(|key| Value::Bytes(Cow::Borrowed(key.as_bytes()))),
+        );
+        Value::Array(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+    use test_case::test_case;
+
+    use crate::{
+        command::{
+            aggregate::{Aggregate, NumAgg},
+            entry::CommandEntry,
+        },
+        db::Db,
+        error::CommandError,
+        protocol::{Value, EMPTY},
+        testing::execute,
+    };
+
+    fn db() -> Arc<Db> {
+        let db = Arc::new(Db::default());
+        db.map.insert(BytesMut::from("a"), Value::Positive(7));
+        db.map.insert(BytesMut::from("b"), Value::Negative(-1000));
+        db.map
+            .insert(BytesMut::from("c"), Value::String(Cow::Borrowed("2000")));
+        db.map
+            .insert(BytesMut::from("text"), Value::String(Cow::Borrowed("abc")));
+        db
+    }
+
+    fn num_agg(op: Aggregate, keys: &[&str]) -> CommandEntry {
+        CommandEntry::NumAgg(NumAgg {
+            op,
+            keys: keys.iter().map(|key| BytesMut::from(*key)).collect(),
+        })
+    }
+
+    #[test_case(Aggregate::Min, &["a", "b", "c", "missing"], Value::Negative(-1000))]
+    #[test_case(Aggregate::Max, &["a", "b", "c", "missing"], Value::Positive(2000))]
+    #[test_case(Aggregate::Sum, &["a", "b", "c", "missing"], Value::Positive(1007))]
+    #[test_case(Aggregate::Sum, &["missing"], Value::Error(Cow::Borrowed(EMPTY)))]
+    #[test_case(Aggregate::Max, &["a", "text"], Value::from(CommandError::NotANumber))]
+    #[tokio::test]
+    async fn aggregate(op: Aggregate, keys: &[&str], expected: Value<'static>) {
+        assert_eq!(execute(&db(), num_agg(op, keys)).await, vec![expected]);
+    }
+}
@@ -0,0 +1,272 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::{decode_integer, hash::field_key, Command},
+    db::Db,
+    error::{CommandError, ProtocolError},
+    protocol::Value,
+};
+
+/// Operation [`Apply`] performs on a stored value, sent as an array starting with its name.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Op {
+    /// `["append", value]`: appends bytes or a string to a byte string or string, or pushes
+    /// `value` to the end of a list.
+    Append(Value<'static>),
+    /// `["incr", n]`: adds `n` to a number, see [`crate::command::incr::Incr`].
+    Incr(i64),
+    /// `["setfield", field, value]`: sets `field` of a hash, creating the hash if needed.
+    SetField(BytesMut, Value<'static>),
+}
+
+impl Op {
+    /// Applies the operation to `value`, `None` if the key does not exist yet, and returns the
+    /// new value.
+    fn apply(
+        &self,
+        value: Option<&Value<'static>>,
+        db: &Db,
+    ) -> Result<Value<'static>, CommandError> {
+        let value = value.map(Value::decompressed);
+        match (self, value.as_deref()) {
+            (Op::Append(tail), None) => Ok(tail.clone()),
+            (Op::Append(Value::Bytes(tail)), Some(Value::Bytes(bytes))) => {
+                Ok(Value::Bytes(Cow::Owned([bytes.as_ref(), tail].concat())))
+            }
+            (Op::Append(Value::String(tail)), Some(Value::Bytes(bytes))) => Ok(Value::Bytes(
+                Cow::Owned([bytes.as_ref(), tail.as_bytes()].concat()),
+            )),
+            (Op::Append(tail), Some(Value::String(string))) => {
+                let tail = match tail {
+                    Value::String(tail) => tail.as_ref(),
+                    Value::Bytes(tail) => {
+                        std::str::from_utf8(tail).map_err(|_| CommandError::WrongType)?
+                    }
+                    _ => return Err(CommandError::WrongType),
+                };
+                Ok(Value::String(Cow::Owned([string.as_ref(), tail].concat())))
+            }
+            (Op::Append(element), Some(Value::Array(items))) => {
+                let mut items = items.clone();
+                items.push(element.clone());
+                Ok(Value::Array(items))
+            }
+            (Op::Append(_), Some(_)) => Err(CommandError::WrongType),
+            (Op::Incr(by), value) => {
+                let n = value.map_or(Some(0), Value::coerce_number);
+                let n = n.ok_or(CommandError::NotANumber)?;
+                db.limits.overflow_mode().apply(n + *by as i128)
+            }
+            (Op::SetField(field, value), hash) => {
+                let mut hash = match hash {
+                    Some(Value::Map(hash)) => hash.clone(),
+                    Some(_) => return Err(CommandError::WrongType),
+                    None => HashMap::new(),
+                };
+                hash.insert(field_key(field), value.clone());
+                Ok(Value::Map(hash))
+            }
+        }
+    }
+
+    fn decode(op: &Value<'_>) -> crate::error::Result<Self> {
+        let Value::Array(op) = op else {
+            return Err(ProtocolError::Command);
+        };
+        match &op[..] {
+            [Value::String(name), value] if name == "append" => match value {
+                Value::Error(_) | Value::Comment(_) => Err(ProtocolError::Command),
+                value => Ok(Op::Append(value.clone().to_owned())),
+            },
+            [Value::String(name), by] if name == "incr" => Ok(Op::Incr(decode_integer(by)?)),
+            [Value::String(name), Value::Bytes(field), value] if name == "setfield" => Ok(
+                Op::SetField(BytesMut::from(field.as_bytes()), value.clone().to_owned()),
+            ),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        match self {
+            Op::Append(value) => {
+                Value::Array(vec![Value::String(Cow::Borrowed("append")), value.clone()])
+            }
+            Op::Incr(by) => Value::Array(vec![
+                Value::String(Cow::Borrowed("incr")),
+                Value::from_number(*by as i128).expect("an i64 fits into a value"),
+            ]),
+            Op::SetField(field, value) => Value::Array(vec![
+                Value::String(Cow::Borrowed("setfield")),
+                Value::Bytes(Cow::Borrowed(field.as_bytes())),
+                value.clone(),
+            ]),
+        }
+    }
+}
+
+/// Applies `op` to the value at `key` under the lock of its shard and replies with the new
+/// value, so small updates need no `GET` and `SET` round trip which could race other clients.
+///
+/// Values the operation does not apply to are rejected with [`CommandError::WrongType`] and left
+/// unchanged.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Apply {
+    pub key: BytesMut,
+    pub op: Op,
+}
+
+impl Command for Apply {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let result = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            self.op.apply(shard.get(&key), &db).inspect(|value| {
+                shard.insert(key, value.clone());
+            })
+        };
+        let reply = match result {
+            Ok(value) => {
+                db.modified(&self.key);
+                value
+            }
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), op] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                op: Op::decode(op)?,
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("APPLY")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            self.op.encode(),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+    use test_case::test_case;
+
+    use crate::{
+        command::{
+            apply::{Apply, Op},
+            entry::CommandEntry,
+            hash::field_key,
+        },
+        db::Db,
+        error::CommandError,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn apply(op: Op) -> CommandEntry {
+        CommandEntry::Apply(Apply {
+            key: BytesMut::from("key"),
+            op,
+        })
+    }
+
+    #[test_case(None, Value::Bytes(Cow::Borrowed(b"tail")); "missing")]
+    #[test_case(
+        Some(Value::Bytes(Cow::Borrowed(b"head "))),
+        Value::Bytes(Cow::Borrowed(b"head tail"));
+        "bytes"
+    )]
+    #[test_case(
+        Some(Value::String(Cow::Borrowed("head "))),
+        Value::String(Cow::Borrowed("head tail"));
+        "string"
+    )]
+    #[test_case(
+        Some(Value::Array(vec![Value::Positive(1)])),
+        Value::Array(vec![Value::Positive(1), Value::Bytes(Cow::Borrowed(b"tail"))]);
+        "list"
+    )]
+    #[test_case(Some(Value::Positive(1)), CommandError::WrongType.into(); "number")]
+    #[tokio::test]
+    async fn append(stored: Option<Value<'static>>, expected: Value<'static>) {
+        let db = Arc::new(Db::default());
+        if let Some(stored) = stored.clone() {
+            db.map.insert(BytesMut::from("key"), stored);
+        }
+        let op = Op::Append(Value::Bytes(Cow::Borrowed(b"tail")));
+        let reply = execute(&db, apply(op)).await;
+        assert_eq!(reply, vec![expected.clone()]);
+        let stored = if expected.error_code().is_some() {
+            stored
+        } else {
+            Some(expected)
+        };
+        assert_eq!(db.map.get_owned(b"key".as_slice()), stored);
+    }
+
+    #[tokio::test]
+    async fn incr() {
+        let db = Arc::new(Db::default());
+        assert_eq!(
+            execute(&db, apply(Op::Incr(5))).await,
+            vec![Value::Positive(5)]
+        );
+        assert_eq!(
+            execute(&db, apply(Op::Incr(-1000))).await,
+            vec![Value::Negative(-995)]
+        );
+        assert_eq!(
+            db.map.get_owned(b"key".as_slice()),
+            Some(Value::Negative(-995))
+        );
+
+        assert_eq!(
+            execute(&db, apply(Op::Incr(i64::MIN + 995))).await,
+            vec![Value::Negative(i64::MIN)]
+        );
+
+        db.map
+            .insert(BytesMut::from("key"), Value::String(Cow::Borrowed("abc")));
+        assert_eq!(
+            execute(&db, apply(Op::Incr(1))).await,
+            vec![Value::from(CommandError::NotANumber)]
+        );
+    }
+
+    #[tokio::test]
+    async fn set_field() {
+        let db = Arc::new(Db::default());
+        let op = Op::SetField(BytesMut::from("field"), Value::Positive(1));
+        execute(&db, apply(op)).await;
+        let Some(Value::Map(hash)) = db.map.get_owned(b"key".as_slice()) else {
+            panic!("no hash stored");
+        };
+        assert_eq!(hash.get(&field_key(b"field")), Some(&Value::Positive(1)));
+    }
+}
@@ -0,0 +1,137 @@
+use std::borrow::Cow;
+
+use crate::{
+    command::Command,
+    error::ProtocolError,
+    protocol::{Value, EMPTY},
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ClientSubcommand {
+    /// Names the connection, replying with `OK`.
+    SetName(String),
+    /// Replies with the name of the connection, or nil if it has none.
+    GetName,
+    /// Replies with the unique id of the connection.
+    Id,
+}
+
+/// Inspects and names the connection the command is sent on, see [`Connection::id`] and
+/// [`Connection::name`].
+///
+/// [`Connection::id`]: crate::codec::Connection::id
+/// [`Connection::name`]: crate::codec::Connection::name
+#[derive(Debug, PartialEq, Clone)]
+pub struct Client {
+    pub subcommand: ClientSubcommand,
+}
+
+impl Command for Client {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        _: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match &self.subcommand {
+            ClientSubcommand::SetName(name) => {
+                connection.name = Some(name.clone());
+                Value::String(Cow::Borrowed("OK"))
+            }
+            ClientSubcommand::GetName => match &connection.name {
+                Some(name) => Value::String(Cow::Owned(name.clone())),
+                None => Value::Error(Cow::Borrowed(EMPTY)),
+            },
+            ClientSubcommand::Id => Value::Positive(connection.id),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let subcommand = match req.as_ref() {
+            [Value::String(subcommand), Value::String(name)] if subcommand == "SETNAME" => {
+                ClientSubcommand::SetName(name.to_string())
+            }
+            [Value::String(subcommand)] if subcommand == "GETNAME" => ClientSubcommand::GetName,
+            [Value::String(subcommand)] if subcommand == "ID" => ClientSubcommand::Id,
+            _ => return Err(ProtocolError::Command),
+        };
+        Ok(Self { subcommand })
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let mut array = vec![Value::String(Cow::Borrowed("CLIENT"))];
+        match &self.subcommand {
+            ClientSubcommand::SetName(name) => {
+                array.push(Value::String(Cow::Borrowed("SETNAME")));
+                array.push(Value::String(Cow::Borrowed(name)));
+            }
+            ClientSubcommand::GetName => array.push(Value::String(Cow::Borrowed("GETNAME"))),
+            ClientSubcommand::Id => array.push(Value::String(Cow::Borrowed("ID"))),
+        }
+        Value::Array(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use crate::{
+        codec::Connection,
+        command::{
+            client::{Client, ClientSubcommand},
+            entry::CommandEntry,
+        },
+        db::Db,
+        protocol::{Value, EMPTY},
+        testing::TestWriter,
+    };
+
+    fn client(subcommand: ClientSubcommand) -> CommandEntry {
+        CommandEntry::Client(Client { subcommand })
+    }
+
+    #[tokio::test]
+    async fn name_kept_per_connection() {
+        let db = Arc::new(Db::default());
+        let mut connection = Connection::new(tokio::io::empty(), TestWriter::new());
+        let mut other = Connection::new(tokio::io::empty(), TestWriter::new());
+        for command in [
+            client(ClientSubcommand::GetName),
+            client(ClientSubcommand::SetName("worker".to_string())),
+            client(ClientSubcommand::GetName),
+            client(ClientSubcommand::Id),
+        ] {
+            command.execute(&mut connection, db.clone()).await;
+        }
+        client(ClientSubcommand::GetName)
+            .execute(&mut other, db.clone())
+            .await;
+
+        assert_eq!(
+            connection.write_half.get_ref().values,
+            vec![
+                Value::Error(Cow::Borrowed(EMPTY)),
+                Value::String(Cow::Borrowed("OK")),
+                Value::String(Cow::Borrowed("worker")),
+                Value::Positive(connection.id),
+            ]
+        );
+        assert_eq!(
+            other.write_half.get_ref().values,
+            vec![Value::Error(Cow::Borrowed(EMPTY))]
+        );
+        assert_ne!(connection.id, other.id);
+    }
+}
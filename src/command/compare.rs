@@ -0,0 +1,152 @@
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{command::Command, error::ProtocolError, protocol::Value};
+
+/// Deletes `key` only if its value equals `expected`.
+///
+/// Meant for releasing locks: a client only removes the lock while it still holds its own
+/// token, never one another client acquired in the meantime. Replies with 1 if the key was
+/// deleted, 0 if it is missing or holds another value.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompareAndDelete {
+    pub key: BytesMut,
+    pub expected: Value<'static>,
+}
+
+impl Command for CompareAndDelete {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let deleted = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            let matches = shard.get(&key) == Some(&self.expected);
+            if matches {
+                shard.remove(&key);
+            }
+            matches
+        };
+        if deleted {
+            db.modified(&self.key);
+        }
+        let _ = connection
+            .write_frame(Value::Positive(deleted as u64))
+            .await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), expected] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                expected: expected.clone().to_owned(),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("CAD")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            self.expected.clone(),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{compare::CompareAndDelete, entry::CommandEntry},
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn cad(token: &'static str) -> CommandEntry {
+        CommandEntry::CompareAndDelete(CompareAndDelete {
+            key: BytesMut::from("lock"),
+            expected: Value::String(Cow::Borrowed(token)),
+        })
+    }
+
+    fn lock(db: &Db, token: &'static str) {
+        db.map
+            .insert(BytesMut::from("lock"), Value::String(Cow::Borrowed(token)));
+    }
+
+    #[tokio::test]
+    async fn matching() {
+        let db = Arc::new(Db::default());
+        lock(&db, "mine");
+        assert_eq!(execute(&db, cad("mine")).await, vec![Value::Positive(1)]);
+        assert!(!db.map.contains(&BytesMut::from("lock")));
+    }
+
+    #[tokio::test]
+    async fn mismatched() {
+        let db = Arc::new(Db::default());
+        lock(&db, "theirs");
+        assert_eq!(execute(&db, cad("mine")).await, vec![Value::Positive(0)]);
+        assert_eq!(
+            db.map.get_owned(&BytesMut::from("lock")),
+            Some(Value::String(Cow::Borrowed("theirs")))
+        );
+    }
+
+    #[tokio::test]
+    async fn missing() {
+        let db = Arc::new(Db::default());
+        assert_eq!(execute(&db, cad("mine")).await, vec![Value::Positive(0)]);
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn compressed() {
+        use crate::{command::set::Set, db::config::Parameter};
+
+        let db = Arc::new(Db::default());
+        db.config_set(Parameter::CompressThreshold, 16).unwrap();
+        let token = "token-".repeat(20);
+        let set = CommandEntry::Set(Set {
+            key: BytesMut::from("lock"),
+            value: Value::String(Cow::Owned(token.clone())),
+        });
+        execute(&db, set).await;
+        assert!(matches!(
+            db.map.get_owned(&BytesMut::from("lock")),
+            Some(Value::Compressed(_))
+        ));
+
+        let cad = |token: String| {
+            CommandEntry::CompareAndDelete(CompareAndDelete {
+                key: BytesMut::from("lock"),
+                expected: Value::String(Cow::Owned(token)),
+            })
+        };
+        assert_eq!(
+            execute(&db, cad("other-".repeat(20))).await,
+            vec![Value::Positive(0)]
+        );
+        assert_eq!(execute(&db, cad(token)).await, vec![Value::Positive(1)]);
+        assert!(!db.map.contains(&BytesMut::from("lock")));
+    }
+}
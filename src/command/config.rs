@@ -0,0 +1,141 @@
+use std::borrow::Cow;
+
+use crate::{command::Command, db::config::Parameter, error::ProtocolError, protocol::Value};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConfigSubcommand {
+    /// Replies with `[name, value]` of the parameter.
+    Get(Parameter),
+    /// Changes the parameter and replies with `OK`.
+    Set(Parameter, u64),
+}
+
+/// Reads and changes server parameters at runtime, see [`crate::db::config::Parameter`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Config {
+    pub subcommand: ConfigSubcommand,
+}
+
+impl Command for Config {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match self.subcommand {
+            ConfigSubcommand::Get(parameter) => Value::Array(vec![
+                Value::String(Cow::Borrowed(parameter.name())),
+                Value::Positive(db.config_get(parameter)),
+            ]),
+            ConfigSubcommand::Set(parameter, value) => match db.config_set(parameter, value) {
+                Ok(()) => Value::String(Cow::Borrowed("OK")),
+                Err(error) => db.error_reply(error),
+            },
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let parameter = |name: &str| Parameter::from_name(name).ok_or(ProtocolError::Command);
+        let subcommand = match req.as_ref() {
+            [Value::String(subcommand), Value::String(name)] if subcommand == "GET" => {
+                ConfigSubcommand::Get(parameter(name)?)
+            }
+            [Value::String(subcommand), Value::String(name), Value::Positive(value)]
+                if subcommand == "SET" =>
+            {
+                ConfigSubcommand::Set(parameter(name)?, *value)
+            }
+            _ => return Err(ProtocolError::Command),
+        };
+        Ok(Self { subcommand })
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let mut array = vec![Value::String(Cow::Borrowed("CONFIG"))];
+        match self.subcommand {
+            ConfigSubcommand::Get(parameter) => {
+                array.push(Value::String(Cow::Borrowed("GET")));
+                array.push(Value::String(Cow::Borrowed(parameter.name())));
+            }
+            ConfigSubcommand::Set(parameter, value) => {
+                array.push(Value::String(Cow::Borrowed("SET")));
+                array.push(Value::String(Cow::Borrowed(parameter.name())));
+                array.push(Value::Positive(value));
+            }
+        }
+        Value::Array(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{
+        command::{
+            config::{Config, ConfigSubcommand},
+            entry::CommandEntry,
+        },
+        db::{config::Parameter, slowlog::SlowLogBuffer, Db},
+        protocol::Value,
+        testing::execute,
+    };
+
+    #[tokio::test]
+    async fn get() {
+        let db = Arc::new(Db {
+            slowlog: SlowLogBuffer::new(Duration::from_millis(5), 16),
+            ..Default::default()
+        });
+        let reply = execute(
+            &db,
+            CommandEntry::Config(Config {
+                subcommand: ConfigSubcommand::Get(Parameter::SlowlogLogSlowerThan),
+            }),
+        )
+        .await;
+        assert_eq!(
+            reply,
+            vec![Value::Array(vec![
+                Value::String("slowlog-log-slower-than".into()),
+                Value::Positive(5000),
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn set() {
+        let db = Arc::new(Db::default());
+        execute(
+            &db,
+            CommandEntry::Config(Config {
+                subcommand: ConfigSubcommand::Set(Parameter::SlowlogMaxLen, 4),
+            }),
+        )
+        .await;
+        assert_eq!(db.slowlog.max_len(), 4);
+        assert_eq!(db.config_get(Parameter::SlowlogMaxLen), 4);
+    }
+
+    #[test]
+    fn unknown_parameter() {
+        let value = Value::Array(vec![
+            Value::String("CONFIG".into()),
+            Value::String("GET".into()),
+            Value::String("maxmemory".into()),
+        ]);
+        assert!(CommandEntry::parse(value).is_err());
+    }
+}
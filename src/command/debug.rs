@@ -0,0 +1,207 @@
+use std::{borrow::Cow, time::Duration};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    error::ProtocolError,
+    protocol::{Value, EMPTY},
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DebugSubcommand {
+    /// Blocks the connection for the given duration, sent as milliseconds.
+    Sleep(Duration),
+    /// Describes how the value at the key is represented, see [`describe`].
+    Object(BytesMut),
+}
+
+/// Description of `value` as a map: its `type`, encoded length `len`, number of elements `n` for
+/// containers, and the first byte of its encoding `hdr`, e.g.
+/// `{"type": "array", "len": 4, "n": 3, "hdr": 0x83}`.
+pub fn describe(value: &Value<'_>) -> Value<'static> {
+    let kind = match value {
+        #[cfg(feature = "compression")]
+        Value::Compressed(_) => "compressed",
+        value => value.type_name(),
+    };
+    let encoded = value.clone().encode();
+    let mut fields = vec![
+        ("type", Value::String(Cow::Borrowed(kind))),
+        ("len", Value::Positive(encoded.len() as u64)),
+        ("hdr", Value::Positive(encoded[0] as u64)),
+    ];
+    if let Some(count) = value.container_len() {
+        fields.push(("n", Value::Positive(count as u64)));
+    }
+    Value::Map(
+        fields
+            .into_iter()
+            .map(|(field, value)| (Value::String(Cow::Borrowed(field)).encode(), value))
+            .collect(),
+    )
+}
+
+/// Diagnostic commands which are not meant for regular clients.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Debug {
+    pub subcommand: DebugSubcommand,
+}
+
+impl Command for Debug {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        match &self.subcommand {
+            DebugSubcommand::Sleep(duration) => {
+                tokio::time::sleep(*duration).await;
+                let _ = connection
+                    .write_frame(Value::String(Cow::Borrowed("OK")))
+                    .await;
+            }
+            DebugSubcommand::Object(key) => {
+                let reply = match db.map.get_owned(key) {
+                    Some(value) => describe(&value),
+                    None => Value::Error(Cow::Borrowed(EMPTY)),
+                };
+                let _ = connection.write_frame(reply).await;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::String(subcommand), Value::Positive(millis)] if subcommand == "SLEEP" => {
+                Ok(Self {
+                    subcommand: DebugSubcommand::Sleep(Duration::from_millis(*millis)),
+                })
+            }
+            [Value::String(subcommand), Value::Bytes(key)] if subcommand == "OBJECT" => Ok(Self {
+                subcommand: DebugSubcommand::Object(BytesMut::from(key.as_bytes())),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        match &self.subcommand {
+            DebugSubcommand::Sleep(duration) => Value::Array(vec![
+                Value::String(Cow::Borrowed("DEBUG")),
+                Value::String(Cow::Borrowed("SLEEP")),
+                Value::Positive(duration.as_millis() as u64),
+            ]),
+            DebugSubcommand::Object(key) => Value::Array(vec![
+                Value::String(Cow::Borrowed("DEBUG")),
+                Value::String(Cow::Borrowed("OBJECT")),
+                Value::Bytes(Cow::Borrowed(key.as_bytes())),
+            ]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{
+            debug::{Debug, DebugSubcommand},
+            entry::CommandEntry,
+        },
+        db::Db,
+        protocol::{Value, EMPTY},
+        testing::execute,
+    };
+
+    fn fields(fields: &[(&'static str, Value<'static>)]) -> Value<'static> {
+        Value::Map(
+            fields
+                .iter()
+                .map(|(field, value)| {
+                    (Value::String(Cow::Borrowed(*field)).encode(), value.clone())
+                })
+                .collect(),
+        )
+    }
+
+    fn debug_object(key: &str) -> CommandEntry {
+        CommandEntry::Debug(Debug {
+            subcommand: DebugSubcommand::Object(BytesMut::from(key)),
+        })
+    }
+
+    #[tokio::test]
+    async fn object() {
+        let db = Arc::new(Db::default());
+        db.map.insert(
+            BytesMut::from("list"),
+            Value::Array(vec![Value::Positive(1), Value::Positive(500)]),
+        );
+        db.map.insert(BytesMut::from("number"), Value::Positive(7));
+
+        assert_eq!(
+            execute(&db, debug_object("list")).await,
+            vec![fields(&[
+                ("type", Value::String(Cow::Borrowed("array"))),
+                ("len", Value::Positive(5)),
+                ("n", Value::Positive(2)),
+                ("hdr", Value::Positive(0x82)),
+            ])]
+        );
+        assert_eq!(
+            execute(&db, debug_object("number")).await,
+            vec![fields(&[
+                ("type", Value::String(Cow::Borrowed("integer"))),
+                ("len", Value::Positive(1)),
+                ("hdr", Value::Positive(0x07)),
+            ])]
+        );
+        assert_eq!(
+            execute(&db, debug_object("missing")).await,
+            vec![Value::Error(Cow::Borrowed(EMPTY))]
+        );
+    }
+
+    #[tokio::test]
+    async fn object_of_long_value() {
+        let db = Arc::new(Db::default());
+        db.map.insert(
+            BytesMut::from("list"),
+            Value::Array(vec![Value::Positive(1); 12345]),
+        );
+        assert_eq!(
+            execute(&db, debug_object("list")).await,
+            vec![fields(&[
+                ("type", Value::String(Cow::Borrowed("array"))),
+                ("len", Value::Positive(12347)),
+                ("n", Value::Positive(12345)),
+                ("hdr", Value::Positive(0x9F)),
+            ])]
+        );
+    }
+
+    #[test]
+    fn object_round_trip() {
+        let command = debug_object("key");
+        assert_eq!(
+            CommandEntry::parse(command.clone().encode()).unwrap(),
+            command
+        );
+    }
+}
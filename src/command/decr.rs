@@ -3,7 +3,11 @@ use std::borrow::Cow;
 use bytes::BytesMut;
 use nom::AsBytes;
 
-use crate::{command::Command, protocol::Value};
+use crate::{
+    command::{incr::increment, Command},
+    error::CommandError,
+    protocol::Value,
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Decr {
@@ -16,33 +20,13 @@ impl Command for Decr {
     async fn execute<W, R>(
         &self,
         connection: &mut crate::codec::Connection<R, W>,
-        db: std::sync::Arc<sharded::Map<bytes::BytesMut, crate::protocol::Value<'static>>>,
+        db: std::sync::Arc<crate::db::Db>,
     ) -> Self::ExecutionResult
     where
         R: tokio::io::AsyncRead + Unpin,
         W: Unpin + tokio::io::AsyncWrite,
     {
-        let (key, mut shard) = db.write(self.key.clone());
-        if let Some(value) = shard.get_mut(key.clone()) {
-            match value.clone() {
-                Value::Positive(p) => {
-                    *value = Value::Positive(p - 1);
-                    let _ = connection.write_frame(Value::Positive(p - 1)).await;
-                }
-                Value::Negative(n) => {
-                    *value = Value::Negative(n - 1);
-                    let _ = connection.write_frame(Value::Negative(n - 1)).await;
-                }
-                _ => {
-                    let _ = connection
-                        .write_frame(Value::Error(Cow::from("Not a number")))
-                        .await;
-                }
-            };
-        } else {
-            shard.insert(key, Value::Positive(0));
-            let _ = connection.write_frame(Value::Positive(0)).await;
-        }
+        increment(&self.key, -1, connection, &db).await;
         Ok(())
     }
 
@@ -51,8 +35,8 @@ impl Command for Decr {
         Self: Sized,
         V: AsRef<[crate::protocol::Value<'c>]>,
     {
-        match req.as_ref()[0] {
-            Value::Bytes(ref b) => Ok(Self {
+        match req.as_ref().first() {
+            Some(Value::Bytes(b)) => Ok(Self {
                 key: BytesMut::from(b.as_bytes()),
             }),
             _ => Err(crate::error::ProtocolError::Command),
@@ -79,35 +63,13 @@ impl Command for DecrBy {
     async fn execute<W, R>(
         &self,
         connection: &mut crate::codec::Connection<R, W>,
-        db: std::sync::Arc<sharded::Map<BytesMut, Value<'static>>>,
+        db: std::sync::Arc<crate::db::Db>,
     ) -> Self::ExecutionResult
     where
         R: tokio::io::AsyncRead + Unpin,
         W: Unpin + tokio::io::AsyncWrite,
     {
-        let (key, mut shard) = db.write(self.key.clone());
-        if let Some(value) = shard.get_mut(key.clone()) {
-            match value.clone() {
-                Value::Positive(p) => {
-                    *value = Value::Positive((p as i64 - self.by) as u64);
-                    let _ = connection
-                        .write_frame(Value::Positive((p as i64 - self.by) as u64))
-                        .await;
-                }
-                Value::Negative(n) => {
-                    *value = Value::Negative(n - self.by);
-                    let _ = connection.write_frame(Value::Negative(n - self.by)).await;
-                }
-                _ => {
-                    let _ = connection
-                        .write_frame(Value::Error(Cow::from("Not a number")))
-                        .await;
-                }
-            };
-        } else {
-            shard.insert(key, Value::Positive(0));
-            let _ = connection.write_frame(Value::Positive(0)).await;
-        }
+        increment(&self.key, -(self.by as i128), connection, &db).await;
         Ok(())
     }
 
@@ -133,3 +95,111 @@ impl Command for DecrBy {
         ])
     }
 }
+
+/// Decrements `key` like [`Decr`] and deletes it once it reaches 0, replying with the new value,
+/// so reference counts can be released without a separate, racy `DEL`.
+///
+/// A missing key replies 0 and is not created, as if it had just been released.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DecrDel {
+    pub key: BytesMut,
+}
+
+impl Command for DecrDel {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let result = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            match shard.get(&key) {
+                Some(value) => value
+                    .coerce_number()
+                    .ok_or(CommandError::NotANumber)
+                    .and_then(|n| db.limits.overflow_mode().apply(n - 1))
+                    .map(|result| {
+                        if result == Value::Positive(0) {
+                            shard.remove(&key);
+                        } else {
+                            shard.insert(key, result.clone());
+                        }
+                        Some(result)
+                    }),
+                None => Ok(None),
+            }
+        };
+        let reply = match result {
+            Ok(Some(reply)) => {
+                db.modified(&self.key);
+                reply
+            }
+            Ok(None) => Value::Positive(0),
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+            }),
+            _ => Err(crate::error::ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("DECRDEL")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{decr::DecrDel, entry::CommandEntry},
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    #[tokio::test]
+    async fn deleted_at_zero() {
+        let db = Arc::new(Db::default());
+        let decr_del = || {
+            CommandEntry::DecrDel(DecrDel {
+                key: BytesMut::from("refs"),
+            })
+        };
+        db.map.insert(BytesMut::from("refs"), Value::Positive(2));
+
+        assert_eq!(execute(&db, decr_del()).await, vec![Value::Positive(1)]);
+        assert_eq!(
+            db.map.get_owned(b"refs".as_slice()),
+            Some(Value::Positive(1))
+        );
+        assert_eq!(execute(&db, decr_del()).await, vec![Value::Positive(0)]);
+        assert!(!db.map.contains(b"refs".as_slice()));
+
+        assert_eq!(execute(&db, decr_del()).await, vec![Value::Positive(0)]);
+        assert!(!db.map.contains(b"refs".as_slice()));
+    }
+}
@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    error::ProtocolError,
+    protocol::{Value, EMPTY},
+};
+
+/// Removes the keys and replies with how many of them existed.
+///
+/// `DEL key RETURN` removes a single key and replies with its value instead, or nil if it did not
+/// exist, so reading and deleting a value takes one round trip.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Del {
+    pub keys: Vec<BytesMut>,
+    /// Reply with the removed value rather than the count, only valid with a single key.
+    pub return_value: bool,
+}
+
+impl Command for Del {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let removed = self
+            .keys
+            .iter()
+            .filter_map(|key| {
+                let value = db.map.remove(key)?;
+                db.modified(key);
+                Some(value)
+            })
+            .collect::<Vec<_>>();
+        let reply = if self.return_value {
+            removed
+                .into_iter()
+                .next()
+                .unwrap_or(Value::Error(Cow::Borrowed(EMPTY)))
+        } else {
+            Value::Positive(removed.len() as u64)
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let (return_value, keys) = match req.as_ref() {
+            [key, Value::String(flag)] if flag == "RETURN" => (true, std::slice::from_ref(key)),
+            keys => (false, keys),
+        };
+        if keys.is_empty() {
+            return Err(ProtocolError::Command);
+        }
+        let keys = keys
+            .iter()
+            .map(|key| match key {
+                Value::Bytes(key) => Ok(BytesMut::from(key.as_bytes())),
+                _ => Err(ProtocolError::Command),
+            })
+            .collect::<crate::error::Result<_>>()?;
+        Ok(Self { keys, return_value })
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let mut array = vec![Value::String(Cow::Borrowed("DEL"))];
+        array.extend(
+            self.keys
+                .iter()
+                .map(|key| Value::Bytes(Cow::Borrowed(key.as_bytes()))),
+        );
+        if self.return_value {
+            array.push(Value::String(Cow::Borrowed("RETURN")));
+        }
+        Value::Array(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+    use test_case::test_case;
+
+    use crate::{
+        command::{del::Del, entry::CommandEntry},
+        db::Db,
+        protocol::{Value, EMPTY},
+        testing::execute,
+    };
+
+    fn del(keys: &[&str], return_value: bool) -> CommandEntry {
+        CommandEntry::Del(Del {
+            keys: keys.iter().map(|key| BytesMut::from(*key)).collect(),
+            return_value,
+        })
+    }
+
+    #[test_case(&["a"], false, Value::Positive(1); "count")]
+    #[test_case(&["a", "b", "missing"], false, Value::Positive(2); "count several")]
+    #[test_case(&["a"], true, Value::String(Cow::Borrowed("first")); "return value")]
+    #[test_case(&["missing"], true, Value::Error(Cow::Borrowed(EMPTY)); "return missing")]
+    #[tokio::test]
+    async fn del_replies(keys: &[&str], return_value: bool, expected: Value<'static>) {
+        let db = Arc::new(Db::default());
+        db.map
+            .insert(BytesMut::from("a"), Value::String(Cow::Borrowed("first")));
+        db.map.insert(BytesMut::from("b"), Value::Positive(2));
+        assert_eq!(execute(&db, del(keys, return_value)).await, vec![expected]);
+        for key in keys {
+            assert!(!db.map.contains(key.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn return_needs_a_single_key() {
+        let frame = |args: Vec<Value<'static>>| {
+            let mut array = vec![Value::String(Cow::Borrowed("DEL"))];
+            array.extend(args);
+            CommandEntry::parse(Value::Array(array))
+        };
+        let key = || Value::Bytes(Cow::Borrowed(b"key"));
+        let flag = || Value::String(Cow::Borrowed("RETURN"));
+        assert_eq!(frame(vec![key(), flag()]).unwrap(), del(&["key"], true));
+        assert!(frame(vec![key(), key(), flag()]).is_err());
+        assert!(frame(vec![flag()]).is_err());
+        assert!(frame(vec![]).is_err());
+    }
+}
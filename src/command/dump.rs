@@ -0,0 +1,117 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    error::{CommandError, ProtocolError},
+    protocol::Value,
+};
+
+/// Replies with the whole keyspace as a single map of key to value.
+///
+/// Meant for debugging small databases: once the keyspace holds more keys than
+/// [`crate::db::config::Parameter::DumpMaxKeys`], it is refused with
+/// [`CommandError::TooLarge`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct DumpAll;
+
+impl Command for DumpAll {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let max_keys = db.limits.dump_max_keys() as usize;
+        let reply = if db.map.len() > max_keys {
+            db.error_reply(CommandError::TooLarge)
+        } else {
+            let mut entries = HashMap::new();
+            db.map.for_each(|key, value| {
+                entries.insert(
+                    Value::Bytes(Cow::Borrowed(key.as_bytes())).encode(),
+                    value.clone(),
+                );
+            });
+            // Keys written since the length check could push the dump past the limit.
+            if entries.len() > max_keys {
+                db.error_reply(CommandError::TooLarge)
+            } else {
+                Value::Map(entries)
+            }
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        if req.as_ref().is_empty() {
+            Ok(Self)
+        } else {
+            Err(ProtocolError::Command)
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![Value::String(Cow::Borrowed("DUMPALL"))])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{dump::DumpAll, entry::CommandEntry},
+        db::{config::Parameter, Db},
+        error::CommandError,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn populate(db: &Db, count: u64) {
+        db.bulk_load((0..count).map(|i| {
+            (
+                BytesMut::from(format!("key:{i}").as_str()),
+                Value::Positive(i),
+            )
+        }));
+    }
+
+    #[tokio::test]
+    async fn dump_all() {
+        let db = Arc::new(Db::default());
+        populate(&db, 5);
+        let reply = execute(&db, CommandEntry::DumpAll(DumpAll)).await;
+        let [Value::Map(entries)] = &reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        assert_eq!(entries.len(), 5);
+        for i in 0..5 {
+            let key = format!("key:{i}");
+            let key = Value::Bytes(Cow::Borrowed(key.as_bytes())).encode();
+            assert_eq!(entries.get(&key), Some(&Value::Positive(i)));
+        }
+    }
+
+    #[tokio::test]
+    async fn too_many_keys() {
+        let db = Arc::new(Db::default());
+        db.config_set(Parameter::DumpMaxKeys, 3).unwrap();
+        populate(&db, 4);
+        let reply = execute(&db, CommandEntry::DumpAll(DumpAll)).await;
+        assert_eq!(reply, vec![Value::from(CommandError::TooLarge)]);
+    }
+}
@@ -1,90 +1,635 @@
+#[cfg(feature = "signing")]
+use crate::command::sign::{Sign, Verify};
 use crate::{
     command::{
-        decr::{Decr, DecrBy},
-        incr::{Incr, IncrBy},
-        set::GetSet,
+        aggregate::NumAgg,
+        apply::Apply,
+        client::Client,
+        compare::CompareAndDelete,
+        config::Config,
+        debug::Debug,
+        decr::{Decr, DecrBy, DecrDel},
+        del::Del,
+        dump::DumpAll,
+        errors::Errors,
+        fingerprint::Fingerprint,
+        hash::{HMerge, HMove, HSetGet},
+        hello::Hello,
+        incr::{GetReset, Incr, IncrBy, IncrIfBelow},
+        keys::KeysStream,
+        latency::Latency,
+        len::Len,
+        list::{BlPop, LDiff, LIndex, LPush, LTrim, Sort},
+        memory::{Fragmentation, MemUsage, ShardStats, SizeHistogram},
+        mset::MSetNx,
+        ratelimit::RateLimit,
+        recent::RecentChanges,
+        save::{DumpPattern, SaveKey},
+        scan::Scan,
+        set::{GetOrSet, GetSet, SetIdempotent},
+        slowlog::SlowLog,
+        stream::{XAdd, XRange},
+        swap::SwapKeys,
+        time::Time,
+        upload::{Commit, Reserve, WriteChunk},
+        validate::Validate,
+        wait::WaitKey,
         Command,
     },
-    error::ProtocolError,
+    error::{CommandError, ProtocolError},
 };
-use std::borrow::Cow;
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
-use bytes::BytesMut;
-use sharded::Map;
 use tokio::io::{AsyncRead, AsyncWrite};
 
 use crate::{
     codec::Connection,
     command::{get::Get, ping::Ping, set::Set},
+    db::Db,
     protocol::Value,
 };
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum CommandEntry {
-    Ping(Ping),
-    Get(Get),
-    Set(Set),
-    GetSet(GetSet),
-    Incr(Incr),
-    IncrBy(IncrBy),
-    Decr(Decr),
-    DecrBy(DecrBy),
+/// Declares every command the server understands.
+///
+/// Each entry maps the wire name of a command to its [`CommandEntry`] variant and the type
+/// implementing [`Command`] for it. Decoding, execution, and encoding are all generated from this
+/// single list, so registering a new command only touches the invocation below. Entries may be
+/// preceded by attributes, e.g. `#[cfg(feature = "...")]` for commands behind a feature.
+macro_rules! commands {
+    ($($(#[$attr:meta])* $name:literal => $variant:ident($command:ty)),* $(,)?) => {
+        #[derive(Debug, PartialEq, Clone)]
+        pub enum CommandEntry {
+            $($(#[$attr])* $variant($command),)*
+        }
+
+        impl CommandEntry {
+            /// Wire names of all registered commands.
+            pub const NAMES: &'static [&'static str] = &[$($(#[$attr])* $name),*];
+
+            /// Decodes arguments of the command registered as `name`.
+            fn decode(name: &str, args: &[Value<'_>]) -> crate::error::Result<Self> {
+                match name {
+                    $($(#[$attr])* $name => Ok(Self::$variant(<$command>::decode(args)?)),)*
+                    _ => Err(ProtocolError::Command),
+                }
+            }
+
+            /// Wire name the command is registered under.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($(#[$attr])* Self::$variant(_) => $name,)*
+                }
+            }
+
+            pub async fn execute<R, W>(
+                &self,
+                connection: &mut Connection<R, W>,
+                db: Arc<Db>,
+            ) where
+                W: AsyncWrite + Unpin,
+                R: AsyncRead + Unpin,
+            {
+                let start = Instant::now();
+                let _ = match self {
+                    $($(#[$attr])* Self::$variant(c) => c.execute(connection, db.clone()).await,)*
+                };
+                Self::finish(self.name(), start, connection, &db).await;
+            }
+
+            pub fn encode(self) -> Value<'static> {
+                match self {
+                    $($(#[$attr])* Self::$variant(c) => c.encode().to_owned(),)*
+                }
+            }
+        }
+    };
+}
+
+commands! {
+    "PING" => Ping(Ping),
+    "GET" => Get(Get),
+    "SET" => Set(Set),
+    "GETSET" => GetSet(GetSet),
+    "INCR" => Incr(Incr),
+    "INCRBY" => IncrBy(IncrBy),
+    "DECR" => Decr(Decr),
+    "DECRBY" => DecrBy(DecrBy),
+    "SLOWLOG" => SlowLog(SlowLog),
+    "DEBUG" => Debug(Debug),
+    "CONFIG" => Config(Config),
+    "RECENTCHANGES" => RecentChanges(RecentChanges),
+    "SCAN" => Scan(Scan),
+    "LPUSH" => LPush(LPush),
+    "BLPOP" => BlPop(BlPop),
+    "HELLO" => Hello(Hello),
+    "MEMUSAGE" => MemUsage(MemUsage),
+    "ERRORS" => Errors(Errors),
+    "DUMPALL" => DumpAll(DumpAll),
+    "HMOVE" => HMove(HMove),
+    "LEN" => Len(Len),
+    "CAD" => CompareAndDelete(CompareAndDelete),
+    "TIME" => Time(Time),
+    "MSETNX" => MSetNx(MSetNx),
+    "LINDEX" => LIndex(LIndex),
+    "GETRESET" => GetReset(GetReset),
+    "LDIFF" => LDiff(LDiff),
+    "SAVEKEY" => SaveKey(SaveKey),
+    "SHARDSTATS" => ShardStats(ShardStats),
+    "FINGERPRINT" => Fingerprint(Fingerprint),
+    "DEL" => Del(Del),
+    "RESERVE" => Reserve(Reserve),
+    "WRITECHUNK" => WriteChunk(WriteChunk),
+    "COMMIT" => Commit(Commit),
+    "NUMAGG" => NumAgg(NumAgg),
+    "SWAPKEYS" => SwapKeys(SwapKeys),
+    "SETIDEMPOTENT" => SetIdempotent(SetIdempotent),
+    "LTRIM" => LTrim(LTrim),
+    "CLIENT" => Client(Client),
+    "INCRIFBELOW" => IncrIfBelow(IncrIfBelow),
+    "SORT" => Sort(Sort),
+    "WAITKEY" => WaitKey(WaitKey),
+    #[cfg(feature = "signing")]
+    "SIGN" => Sign(Sign),
+    #[cfg(feature = "signing")]
+    "VERIFY" => Verify(Verify),
+    "APPLY" => Apply(Apply),
+    "SIZEHISTOGRAM" => SizeHistogram(SizeHistogram),
+    "DUMPPATTERN" => DumpPattern(DumpPattern),
+    "HSETGET" => HSetGet(HSetGet),
+    "LATENCY" => Latency(Latency),
+    "RATELIMIT" => RateLimit(RateLimit),
+    "GETORSET" => GetOrSet(GetOrSet),
+    "KEYSSTREAM" => KeysStream(KeysStream),
+    "DECRDEL" => DecrDel(DecrDel),
+    "VALIDATE" => Validate(Validate),
+    "HMERGE" => HMerge(HMerge),
+    "FRAGMENTATION" => Fragmentation(Fragmentation),
+    "XADD" => XAdd(XAdd),
+    "XRANGE" => XRange(XRange),
 }
 
 impl CommandEntry {
     pub fn parse(input: Value<'_>) -> crate::error::Result<Self> {
+        Self::parse_limited(input, usize::MAX)
+    }
+
+    /// Like [`CommandEntry::parse`], but rejects commands with more than `max_args` arguments
+    /// with [`ProtocolError::TooManyArguments`] before decoding them.
+    pub fn parse_limited(input: Value<'_>, max_args: usize) -> crate::error::Result<Self> {
         let Value::Array(array) = input else {
             return Err(ProtocolError::Command);
         };
-        let Value::String(Cow::Borrowed(first)) = array.first().ok_or(ProtocolError::Command)?
-        else {
+        let Value::String(first) = array.first().ok_or(ProtocolError::Command)? else {
             return Err(ProtocolError::Command);
         };
-        match *first {
-            "PING" => Ok(Self::Ping(Ping::decode(&array[1..])?)),
-            "GET" => Ok(Self::Get(Get::decode(&array[1..])?)),
-            "SET" => Ok(Self::Set(Set::decode(&array[1..])?)),
-            "GETSET" => Ok(Self::GetSet(GetSet::decode(&array[1..])?)),
-            "INCR" => Ok(Self::Incr(Incr::decode(&array[1..])?)),
-            "INCRBY" => Ok(Self::IncrBy(IncrBy::decode(&array[1..])?)),
-            "DECR" => Ok(Self::Decr(Decr::decode(&array[1..])?)),
-            "DECRBY" => Ok(Self::DecrBy(DecrBy::decode(&array[1..])?)),
-            _ => todo!(),
+        if array.len() - 1 > max_args {
+            return Err(ProtocolError::TooManyArguments);
         }
+        Self::decode(first.as_ref(), &array[1..])
     }
 
-    pub async fn execute<R, W>(
-        &self,
+    /// Records how long the command `name` started at `start` took and flushes its reply.
+    async fn finish<R, W>(
+        name: &'static str,
+        start: Instant,
         connection: &mut Connection<R, W>,
-        db: Arc<Map<BytesMut, Value<'static>>>,
+        db: &Db,
     ) where
         W: AsyncWrite + Unpin,
         R: AsyncRead + Unpin,
     {
-        let _ = match self {
-            CommandEntry::Ping(p) => p.execute(connection, db).await,
-            CommandEntry::Get(g) => g.execute(connection, db).await,
-            CommandEntry::Set(s) => s.execute(connection, db).await,
-            CommandEntry::GetSet(s) => s.execute(connection, db).await,
-            CommandEntry::Incr(i) => i.execute(connection, db).await,
-            CommandEntry::IncrBy(i) => i.execute(connection, db).await,
-            CommandEntry::Decr(d) => d.execute(connection, db).await,
-            CommandEntry::DecrBy(d) => d.execute(connection, db).await,
-        };
+        let elapsed = start.elapsed();
+        db.slowlog.record(name, elapsed);
+        db.latencies.record(name, elapsed);
         let _ = connection.flush_writer().await;
     }
 
-    pub fn encode(self) -> Value<'static> {
-        match self {
-            CommandEntry::Ping(p) => p.encode().to_owned(),
-            CommandEntry::Get(g) => g.encode().to_owned(),
-            CommandEntry::Set(s) => s.encode().to_owned(),
-            CommandEntry::GetSet(s) => s.encode().to_owned(),
-            CommandEntry::Incr(i) => i.encode().to_owned(),
-            CommandEntry::IncrBy(i) => i.encode().to_owned(),
-            CommandEntry::Decr(d) => d.encode().to_owned(),
-            CommandEntry::DecrBy(d) => d.encode().to_owned(),
+    /// Parses `frame` as a command and executes it. Frames which are not a valid command are
+    /// replied to with [`CommandError::Parse`], or [`CommandError::TooManyArguments`] past the
+    /// configured limit. Comments are ignored without a reply.
+    ///
+    /// `GET` is executed with its key borrowed from the frame, see [`Get::execute_borrowed`].
+    pub async fn dispatch<R, W>(frame: Value<'_>, connection: &mut Connection<R, W>, db: Arc<Db>)
+    where
+        W: AsyncWrite + Unpin,
+        R: AsyncRead + Unpin,
+    {
+        if let Value::Comment(_) = frame {
+            return;
+        }
+        let max_args = usize::try_from(db.limits.max_args()).unwrap_or(usize::MAX);
+        if let Value::Array(array) = &frame {
+            if let [Value::String(name), Value::Bytes(key)] = &array[..] {
+                if name == "GET" && max_args >= 1 {
+                    let start = Instant::now();
+                    let _ = Get::execute_borrowed(key, connection, &db).await;
+                    Self::finish("GET", start, connection, &db).await;
+                    return;
+                }
+            }
         }
+        match Self::parse_limited(frame, max_args) {
+            Ok(command) => command.execute(connection, db).await,
+            Err(error) => {
+                let error = match error {
+                    ProtocolError::TooManyArguments => CommandError::TooManyArguments,
+                    _ => CommandError::Parse,
+                };
+                let _ = connection.write_frame(db.error_reply(error)).await;
+                let _ = connection.flush_writer().await;
+            }
+        }
+    }
+}
+
+impl TryFrom<Value<'_>> for CommandEntry {
+    type Error = ProtocolError;
+
+    fn try_from(value: Value<'_>) -> Result<Self, Self::Error> {
+        Self::parse(value)
+    }
+}
+
+impl From<CommandEntry> for Value<'static> {
+    fn from(command: CommandEntry) -> Self {
+        command.encode()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use bytes::BytesMut;
+    use test_case::test_case;
+
+    use crate::{
+        codec::Connection,
+        command::{
+            aggregate::{Aggregate, NumAgg},
+            apply::{Apply, Op},
+            client::{Client, ClientSubcommand},
+            compare::CompareAndDelete,
+            config::{Config, ConfigSubcommand},
+            debug::{Debug, DebugSubcommand},
+            decr::{Decr, DecrBy, DecrDel},
+            del::Del,
+            dump::DumpAll,
+            entry::CommandEntry,
+            errors::{Errors, ErrorsSubcommand},
+            fingerprint::Fingerprint,
+            get::{Get, EMPTY},
+            hash::{field_key, HMerge, HMove, HSetGet},
+            hello::Hello,
+            incr::{GetReset, Incr, IncrBy, IncrIfBelow},
+            keys::KeysStream,
+            latency::{Latency, LatencySubcommand},
+            len::Len,
+            list::{BlPop, LDiff, LIndex, LPush, LTrim, Sort, SortOrder},
+            memory::{Fragmentation, MemUsage, ShardStats, SizeHistogram},
+            mset::MSetNx,
+            ping::Ping,
+            ratelimit::RateLimit,
+            recent::RecentChanges,
+            save::{DumpPattern, SaveKey},
+            scan::Scan,
+            set::{GetOrSet, GetSet, Set, SetIdempotent},
+            slowlog::{SlowLog, SlowLogSubcommand},
+            stream::{XAdd, XRange},
+            swap::SwapKeys,
+            time::Time,
+            upload::{Commit, Reserve, WriteChunk},
+            validate::Validate,
+            wait::WaitKey,
+        },
+        db::{config::Parameter, Db},
+        error::CommandError,
+        protocol::Value,
+        testing::TestWriter,
+    };
+
+    #[test_case(CommandEntry::Ping(Ping))]
+    #[test_case(CommandEntry::Get(Get { key: BytesMut::from(&b"key"[..]) }))]
+    #[test_case(CommandEntry::Set(Set { key: BytesMut::from(&b"key"[..]), value: Value::Positive(42) }))]
+    #[test_case(CommandEntry::GetSet(GetSet { key: BytesMut::from(&b"key"[..]), value: Value::Negative(-42) }))]
+    #[test_case(CommandEntry::Incr(Incr { key: BytesMut::from(&b"key"[..]) }))]
+    #[test_case(CommandEntry::IncrBy(IncrBy { key: BytesMut::from(&b"key"[..]), by: 100 }))]
+    #[test_case(CommandEntry::Decr(Decr { key: BytesMut::from(&b"key"[..]) }))]
+    #[test_case(CommandEntry::DecrBy(DecrBy { key: BytesMut::from(&b"key"[..]), by: 100 }))]
+    fn value_round_trip(command: CommandEntry) {
+        let value = Value::from(command.clone());
+        let parsed = CommandEntry::try_from(value);
+        assert!(parsed.is_ok());
+        assert_eq!(parsed.unwrap(), command);
+    }
+
+    fn every_command() -> Vec<CommandEntry> {
+        let key = BytesMut::from(&b"key"[..]);
+        vec![
+            CommandEntry::Ping(Ping),
+            CommandEntry::Get(Get { key: key.clone() }),
+            CommandEntry::Set(Set {
+                key: key.clone(),
+                value: Value::Positive(42),
+            }),
+            CommandEntry::GetSet(GetSet {
+                key: key.clone(),
+                value: Value::Positive(42),
+            }),
+            CommandEntry::Incr(Incr { key: key.clone() }),
+            CommandEntry::IncrBy(IncrBy {
+                key: key.clone(),
+                by: 1,
+            }),
+            CommandEntry::Decr(Decr { key: key.clone() }),
+            CommandEntry::DecrBy(DecrBy {
+                key: key.clone(),
+                by: 1,
+            }),
+            CommandEntry::SlowLog(SlowLog {
+                subcommand: SlowLogSubcommand::Get,
+            }),
+            CommandEntry::Debug(Debug {
+                subcommand: DebugSubcommand::Sleep(Duration::from_millis(1)),
+            }),
+            CommandEntry::Config(Config {
+                subcommand: ConfigSubcommand::Get(Parameter::SlowlogMaxLen),
+            }),
+            CommandEntry::RecentChanges(RecentChanges { count: 10 }),
+            CommandEntry::Scan(Scan {
+                cursor: 0,
+                count: 10,
+                kind: Some("integer".to_string()),
+            }),
+            CommandEntry::LPush(LPush {
+                key: key.clone(),
+                values: vec![Value::Positive(1), Value::Positive(2)],
+            }),
+            CommandEntry::BlPop(BlPop {
+                keys: vec![key],
+                timeout_ms: 100,
+            }),
+            CommandEntry::Hello(Hello { version: Some(1) }),
+            CommandEntry::MemUsage(MemUsage {
+                key: BytesMut::from(&b"key"[..]),
+            }),
+            CommandEntry::Errors(Errors {
+                subcommand: ErrorsSubcommand::Reset,
+            }),
+            CommandEntry::DumpAll(DumpAll),
+            CommandEntry::HMove(HMove {
+                src_hash: BytesMut::from(&b"hash"[..]),
+                field: BytesMut::from(&b"field"[..]),
+                dst_key: BytesMut::from(&b"key"[..]),
+            }),
+            CommandEntry::Len(Len {
+                key: BytesMut::from(&b"key"[..]),
+            }),
+            CommandEntry::CompareAndDelete(CompareAndDelete {
+                key: BytesMut::from(&b"key"[..]),
+                expected: Value::Positive(42),
+            }),
+            CommandEntry::Time(Time),
+            CommandEntry::MSetNx(MSetNx {
+                pairs: vec![(BytesMut::from(&b"key"[..]), Value::Positive(42))],
+            }),
+            CommandEntry::LIndex(LIndex {
+                key: BytesMut::from(&b"key"[..]),
+                index: -1,
+            }),
+            CommandEntry::GetReset(GetReset {
+                key: BytesMut::from(&b"key"[..]),
+            }),
+            CommandEntry::LDiff(LDiff {
+                key_a: BytesMut::from(&b"key"[..]),
+                key_b: BytesMut::from(&b"other"[..]),
+            }),
+            CommandEntry::SaveKey(SaveKey {
+                key: BytesMut::from(&b"key"[..]),
+                path: "key.kvs".into(),
+            }),
+            CommandEntry::ShardStats(ShardStats),
+            CommandEntry::Fingerprint(Fingerprint),
+            CommandEntry::Del(Del {
+                keys: vec![BytesMut::from("key")],
+                return_value: true,
+            }),
+            CommandEntry::Reserve(Reserve { size: 10 }),
+            CommandEntry::WriteChunk(WriteChunk {
+                handle: 1,
+                offset: 0,
+                bytes: b"chunk".to_vec(),
+            }),
+            CommandEntry::Commit(Commit {
+                handle: 1,
+                key: BytesMut::from("key"),
+            }),
+            CommandEntry::NumAgg(NumAgg {
+                op: Aggregate::Sum,
+                keys: vec![BytesMut::from("key")],
+            }),
+            CommandEntry::SwapKeys(SwapKeys {
+                a: BytesMut::from("a"),
+                b: BytesMut::from("b"),
+            }),
+            CommandEntry::SetIdempotent(SetIdempotent {
+                key: BytesMut::from("key"),
+                value: Value::Positive(1),
+                token: BytesMut::from("token"),
+            }),
+            CommandEntry::LTrim(LTrim {
+                key: BytesMut::from("list"),
+                start: 0,
+                stop: -1,
+            }),
+            CommandEntry::Client(Client {
+                subcommand: ClientSubcommand::SetName("name".to_string()),
+            }),
+            CommandEntry::IncrIfBelow(IncrIfBelow {
+                key: BytesMut::from("key"),
+                limit: 100,
+                by: 1,
+            }),
+            CommandEntry::Sort(Sort {
+                key: BytesMut::from("list"),
+                order: SortOrder::Desc,
+                limit: Some((1, 2)),
+            }),
+            CommandEntry::WaitKey(WaitKey {
+                key: BytesMut::from("key"),
+                timeout_ms: 10,
+            }),
+            #[cfg(feature = "signing")]
+            CommandEntry::Sign(crate::command::sign::Sign {
+                key: BytesMut::from("key"),
+                secret: b"secret".to_vec(),
+            }),
+            #[cfg(feature = "signing")]
+            CommandEntry::Verify(crate::command::sign::Verify {
+                key: BytesMut::from("key"),
+                secret: b"secret".to_vec(),
+                signature: vec![0; 16],
+            }),
+            CommandEntry::Apply(Apply {
+                key: BytesMut::from("key"),
+                op: Op::SetField(BytesMut::from("field"), Value::Positive(1)),
+            }),
+            CommandEntry::SizeHistogram(SizeHistogram),
+            CommandEntry::DumpPattern(DumpPattern {
+                pattern: BytesMut::from("user:*"),
+                path: "users.kvs".into(),
+            }),
+            CommandEntry::HSetGet(HSetGet {
+                key: BytesMut::from("hash"),
+                field: BytesMut::from("field"),
+                value: Value::Positive(1),
+            }),
+            CommandEntry::Latency(Latency {
+                subcommand: LatencySubcommand::Percentile {
+                    p: 99.9,
+                    command: Some("GET".to_string()),
+                },
+            }),
+            CommandEntry::RateLimit(RateLimit {
+                key: BytesMut::from("key"),
+                capacity: 10,
+                refill_per_sec: 1,
+                tokens: 1,
+            }),
+            CommandEntry::GetOrSet(GetOrSet {
+                key: BytesMut::from("key"),
+                default: Value::Positive(0),
+            }),
+            CommandEntry::KeysStream(KeysStream {
+                pattern: BytesMut::from("user:*"),
+            }),
+            CommandEntry::DecrDel(DecrDel {
+                key: BytesMut::from("key"),
+            }),
+            CommandEntry::Validate(Validate {
+                payload: BytesMut::from(&[0x01][..]),
+            }),
+            CommandEntry::HMerge(HMerge {
+                key: BytesMut::from("hash"),
+                patch: HashMap::from([(field_key(b"field"), Value::Positive(1))]),
+            }),
+            CommandEntry::Fragmentation(Fragmentation),
+            CommandEntry::XAdd(XAdd {
+                key: BytesMut::from("key"),
+                entry: Value::Positive(1),
+            }),
+            CommandEntry::XRange(XRange {
+                key: BytesMut::from("key"),
+                start: 1,
+                end: 10,
+            }),
+        ]
+    }
+
+    #[test]
+    fn every_name_dispatches() {
+        let commands = every_command();
+        for name in CommandEntry::NAMES {
+            let command = commands
+                .iter()
+                .find(|c| c.name() == *name)
+                .unwrap_or_else(|| panic!("{name} is not covered"));
+            let parsed = CommandEntry::parse(command.clone().encode());
+            assert!(parsed.is_ok(), "{name} does not dispatch");
+            assert_eq!(&parsed.unwrap(), command);
+        }
+    }
+
+    #[test]
+    fn every_variant_encodes() {
+        for command in every_command() {
+            let name = command.name();
+            let Value::Array(array) = command.encode() else {
+                panic!("{name} is not encoded as an array");
+            };
+            assert_eq!(array.first(), Some(&Value::String(name.into())));
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatch_unknown_command() {
+        let db = Arc::new(Db::default());
+        let mut connection = Connection::new(tokio::io::empty(), TestWriter::new());
+        let frame = Value::Array(vec![Value::String("UNKNOWN".into())]);
+        CommandEntry::dispatch(frame, &mut connection, db.clone()).await;
+        assert_eq!(
+            connection.write_half.get_ref().values,
+            vec![Value::from(CommandError::Parse)]
+        );
+        assert_eq!(db.errors.count(CommandError::Parse), 1);
+    }
+
+    #[tokio::test]
+    async fn dispatch_comment() {
+        let db = Arc::new(Db::default());
+        let mut connection = Connection::new(tokio::io::empty(), TestWriter::new());
+        let frame = Value::Comment("replayed from the log".into());
+        CommandEntry::dispatch(frame, &mut connection, db.clone()).await;
+        assert!(connection.write_half.get_ref().values.is_empty());
+        assert_eq!(db.errors.count(CommandError::Parse), 0);
+    }
+
+    #[tokio::test]
+    async fn dispatch_get_with_borrowed_key() {
+        let db = Arc::new(Db::default());
+        db.map.insert(BytesMut::from("key"), Value::Positive(42));
+        let mut connection = Connection::new(tokio::io::empty(), TestWriter::new());
+        for key in ["key", "missing"] {
+            let frame = Value::Array(vec![
+                Value::String("GET".into()),
+                Value::Bytes(key.as_bytes().into()),
+            ]);
+            CommandEntry::dispatch(frame, &mut connection, db.clone()).await;
+        }
+        assert_eq!(
+            connection.write_half.get_ref().values,
+            vec![Value::Positive(42), Value::Error(EMPTY.into())]
+        );
+        assert_eq!(db.latencies.histogram(Some("GET")).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn dispatch_too_many_arguments() {
+        let db = Arc::new(Db::default());
+        db.config_set(Parameter::MaxArgs, 4).unwrap();
+        let lpush = |count: u64| {
+            let mut frame = vec![
+                Value::String("LPUSH".into()),
+                Value::Bytes(b"key".as_slice().into()),
+            ];
+            frame.extend((1..count).map(Value::Positive));
+            Value::Array(frame)
+        };
+        let mut connection = Connection::new(tokio::io::empty(), TestWriter::new());
+        CommandEntry::dispatch(lpush(5), &mut connection, db.clone()).await;
+        assert_eq!(
+            connection.write_half.get_ref().values,
+            vec![Value::from(CommandError::TooManyArguments)]
+        );
+        assert!(!db.map.contains(b"key".as_slice()));
+
+        let mut connection = Connection::new(tokio::io::empty(), TestWriter::new());
+        CommandEntry::dispatch(lpush(4), &mut connection, db.clone()).await;
+        assert_eq!(
+            connection.write_half.get_ref().values,
+            vec![Value::Positive(3)]
+        );
+    }
+
+    #[test]
+    fn unknown_command() {
+        let value = Value::Array(vec![Value::String("UNKNOWN".into())]);
+        assert!(CommandEntry::parse(value).is_err());
+    }
+
+    #[test]
+    fn not_a_command() {
+        assert!(CommandEntry::try_from(Value::Positive(42)).is_err());
+        assert!(CommandEntry::try_from(Value::Array(vec![])).is_err());
     }
 }
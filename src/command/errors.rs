@@ -0,0 +1,163 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{command::Command, error::ProtocolError, protocol::Value};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ErrorsSubcommand {
+    /// Replies with a map of error name to the number of times it was replied with.
+    Get,
+    /// Resets every counter to zero.
+    Reset,
+}
+
+/// Exposes error reply statistics, see [`crate::db::errors`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Errors {
+    pub subcommand: ErrorsSubcommand,
+}
+
+impl Command for Errors {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match self.subcommand {
+            ErrorsSubcommand::Get => Value::Map(
+                db.errors
+                    .counts()
+                    .into_iter()
+                    .map(|(error, count)| {
+                        (
+                            Value::String(Cow::Borrowed(error.name())).encode(),
+                            Value::Positive(count),
+                        )
+                    })
+                    .collect::<HashMap<_, _>>(),
+            ),
+            ErrorsSubcommand::Reset => {
+                db.errors.reset();
+                Value::String(Cow::Borrowed("OK"))
+            }
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let [Value::String(subcommand)] = req.as_ref() else {
+            return Err(ProtocolError::Command);
+        };
+        let subcommand = match subcommand.as_ref() {
+            "GET" => ErrorsSubcommand::Get,
+            "RESET" => ErrorsSubcommand::Reset,
+            _ => return Err(ProtocolError::Command),
+        };
+        Ok(Self { subcommand })
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let subcommand = match self.subcommand {
+            ErrorsSubcommand::Get => "GET",
+            ErrorsSubcommand::Reset => "RESET",
+        };
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("ERRORS")),
+            Value::String(Cow::Borrowed(subcommand)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{
+            entry::CommandEntry,
+            errors::{Errors, ErrorsSubcommand},
+            list::LPush,
+            set::Set,
+        },
+        db::Db,
+        error::CommandError,
+        protocol::Value,
+        testing::execute,
+    };
+
+    async fn report(db: &Arc<Db>, error: CommandError) -> Option<Value<'static>> {
+        let reply = execute(
+            db,
+            CommandEntry::Errors(Errors {
+                subcommand: ErrorsSubcommand::Get,
+            }),
+        )
+        .await;
+        let [Value::Map(counts)] = &reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        counts
+            .get(&Value::String(Cow::Borrowed(error.name())).encode())
+            .cloned()
+    }
+
+    #[tokio::test]
+    async fn wrong_type_counted() {
+        let db = Arc::new(Db::default());
+        let key = BytesMut::from("key");
+        execute(
+            &db,
+            CommandEntry::Set(Set {
+                key: key.clone(),
+                value: Value::Positive(42),
+            }),
+        )
+        .await;
+        assert_eq!(
+            report(&db, CommandError::WrongType).await,
+            Some(Value::Positive(0))
+        );
+
+        let reply = execute(
+            &db,
+            CommandEntry::LPush(LPush {
+                key,
+                values: vec![Value::Positive(1)],
+            }),
+        )
+        .await;
+        assert_eq!(reply, vec![Value::from(CommandError::WrongType)]);
+        assert_eq!(
+            report(&db, CommandError::WrongType).await,
+            Some(Value::Positive(1))
+        );
+        assert_eq!(
+            report(&db, CommandError::Parse).await,
+            Some(Value::Positive(0))
+        );
+
+        execute(
+            &db,
+            CommandEntry::Errors(Errors {
+                subcommand: ErrorsSubcommand::Reset,
+            }),
+        )
+        .await;
+        assert_eq!(
+            report(&db, CommandError::WrongType).await,
+            Some(Value::Positive(0))
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::borrow::Cow;
+
+use crate::{
+    command::Command,
+    db::Db,
+    error::ProtocolError,
+    protocol::{EncodeOptions, Value},
+};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a, which unlike the std hashers is specified, so every build hashes alike.
+fn fnv1a(bytes: &[u8], seed: u64) -> u64 {
+    bytes.iter().fold(seed, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Hash of the value encoded canonically, so maps hash alike whatever order their entries are in.
+fn hash_value(value: &Value<'_>) -> u64 {
+    let canonical = EncodeOptions { canonical: true };
+    fnv1a(&value.clone().encode_with(&canonical), FNV_OFFSET)
+}
+
+/// Hash of the whole keyspace, equal for databases holding the same keys and values however
+/// they were written. The hashes of the entries are XORed, so the order they are visited in does
+/// not matter.
+pub fn fingerprint(db: &Db) -> u64 {
+    let mut fingerprint = 0;
+    db.map
+        .for_each(|key, value| fingerprint ^= fnv1a(key, hash_value(value)));
+    fingerprint
+}
+
+/// Replies with the [`fingerprint`] of the keyspace, for comparing the data of two servers.
+///
+/// Shards are hashed one after another, so keys written meanwhile may or may not be included.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fingerprint;
+
+impl Command for Fingerprint {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let _ = connection
+            .write_frame(Value::Positive(fingerprint(&db)))
+            .await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        if req.as_ref().is_empty() {
+            Ok(Self)
+        } else {
+            Err(ProtocolError::Command)
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![Value::String(Cow::Borrowed("FINGERPRINT"))])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{entry::CommandEntry, fingerprint::Fingerprint},
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn entries() -> Vec<(BytesMut, Value<'static>)> {
+        let hash = (0..10u64)
+            .map(|i| (Value::Positive(i).encode(), Value::Positive(i * 2)))
+            .collect::<HashMap<_, _>>();
+        (0..100u64)
+            .map(|i| {
+                (
+                    BytesMut::from(format!("key:{i}").as_str()),
+                    Value::Positive(i),
+                )
+            })
+            .chain([
+                (BytesMut::from("hash"), Value::Map(hash)),
+                (
+                    BytesMut::from("list"),
+                    Value::Array(vec![Value::String(Cow::Borrowed("a"))]),
+                ),
+            ])
+            .collect()
+    }
+
+    async fn fingerprint(db: &Arc<Db>) -> Vec<Value<'static>> {
+        execute(db, CommandEntry::Fingerprint(Fingerprint)).await
+    }
+
+    #[tokio::test]
+    async fn independent_of_insertion_order() {
+        let forward = Arc::new(Db::default());
+        for (key, value) in entries() {
+            forward.map.insert(key, value);
+        }
+        let backward = Arc::new(Db::default());
+        for (key, value) in entries().into_iter().rev() {
+            backward.map.insert(key, value);
+        }
+        assert_eq!(fingerprint(&forward).await, fingerprint(&backward).await);
+
+        backward
+            .map
+            .insert(BytesMut::from("key:0"), Value::Positive(1));
+        assert_ne!(fingerprint(&forward).await, fingerprint(&backward).await);
+    }
+}
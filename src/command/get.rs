@@ -3,14 +3,80 @@ use std::borrow::Cow;
 use bytes::BytesMut;
 use nom::AsBytes;
 
-use crate::{command::Command, protocol::Value};
+use crate::{
+    command::{retry::retry, Command, StorageCommand},
+    db::{storage::Storage, Db},
+    error::{CommandError, ProtocolError},
+    protocol::Value,
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Get {
     pub key: BytesMut,
 }
 
-pub const EMPTY: &str = "Can not find the key";
+pub use crate::protocol::EMPTY;
+
+impl Get {
+    /// Reply to `GET key`, looked up with the key borrowed straight from the frame.
+    pub fn lookup(storage: &impl Storage, key: &[u8]) -> crate::error::Result<Value<'static>> {
+        Ok(storage
+            .get(key)?
+            .unwrap_or(Value::Error(Cow::Borrowed(EMPTY))))
+    }
+
+    /// Executes `GET key` with the key borrowed from the frame, without decoding a [`Get`].
+    ///
+    /// Only read-only commands can do this: commands storing the key have to copy it anyway, as
+    /// the map owns its keys and the frame is dropped once the command returns.
+    pub async fn execute_borrowed<R, W>(
+        key: &[u8],
+        connection: &mut crate::codec::Connection<R, W>,
+        db: &Db,
+    ) -> crate::error::Result<()>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        Self::reply(key, connection, db, &db.map).await
+    }
+
+    async fn reply<S, R, W>(
+        key: &[u8],
+        connection: &mut crate::codec::Connection<R, W>,
+        db: &Db,
+        storage: &S,
+    ) -> crate::error::Result<()>
+    where
+        S: Storage,
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match retry(|| Self::lookup(storage, key)).await {
+            Ok(value) => value,
+            Err(ProtocolError::Retryable) => db.error_reply(CommandError::Unavailable),
+            Err(error) => return Err(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+}
+
+impl StorageCommand for Get {
+    async fn execute_on<S, R, W>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: &Db,
+        storage: &S,
+    ) -> crate::error::Result<()>
+    where
+        S: Storage,
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        Self::reply(&self.key, connection, db, storage).await
+    }
+}
 
 impl Command for Get {
     type ExecutionResult = crate::error::Result<()>;
@@ -18,36 +84,28 @@ impl Command for Get {
     async fn execute<W, R>(
         &self,
         connection: &mut crate::codec::Connection<R, W>,
-        db: std::sync::Arc<sharded::Map<bytes::BytesMut, crate::protocol::Value<'static>>>,
+        db: std::sync::Arc<crate::db::Db>,
     ) -> Self::ExecutionResult
     where
         R: tokio::io::AsyncRead + Unpin,
         W: Unpin + tokio::io::AsyncWrite,
     {
-        let shard = db.read(&self.key);
-        match shard.1.get(shard.0) {
-            Some(value) => {
-                let _ = connection.write_frame(value.clone()).await;
-            }
-            None => {
-                let _ = connection
-                    .write_frame(Value::Error(Cow::Borrowed(EMPTY)))
-                    .await;
-            }
-        }
-        Ok(())
+        self.execute_on(connection, &db, &db.map).await
     }
 
+    /// Decodes the key by copying it out of the frame, since [`Get`] outlives it. Callers that
+    /// still hold the frame can skip the copy and go through [`Get::execute_borrowed`] instead,
+    /// as [`CommandEntry::dispatch`](crate::command::entry::CommandEntry::dispatch) does.
     fn decode<'c, V>(req: V) -> crate::error::Result<Self>
     where
         Self: Sized,
         V: AsRef<[crate::protocol::Value<'c>]>,
     {
-        match req.as_ref()[0] {
-            Value::Bytes(ref b) => Ok(Self {
+        match req.as_ref().first() {
+            Some(Value::Bytes(b)) => Ok(Self {
                 key: BytesMut::from(b.as_bytes()),
             }),
-            _ => Err(crate::error::ProtocolError::Command),
+            _ => Err(ProtocolError::Command),
         }
     }
 
@@ -58,3 +116,41 @@ impl Command for Get {
         ])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use bytes::BytesMut;
+
+    use crate::{
+        db::Db,
+        protocol::{parse, Value},
+    };
+
+    use super::{Get, EMPTY};
+
+    #[test]
+    fn lookup_borrows_key_from_frame() {
+        let db = Db::default();
+        db.map
+            .insert(BytesMut::from(&b"key"[..]), Value::Positive(42));
+        let frame = Value::Array(vec![
+            Value::String(Cow::Borrowed("GET")),
+            Value::Bytes(Cow::Borrowed(b"key")),
+        ])
+        .encode();
+        let (_, request) = parse(&frame).unwrap();
+        let Value::Array(args) = request else {
+            panic!("expected an array");
+        };
+        let Value::Bytes(Cow::Borrowed(key)) = &args[1] else {
+            panic!("expected a key borrowed from the frame");
+        };
+        assert_eq!(Get::lookup(&db.map, key).unwrap(), Value::Positive(42));
+        assert_eq!(
+            Get::lookup(&db.map, b"missing").unwrap(),
+            Value::Error(Cow::Borrowed(EMPTY))
+        );
+    }
+}
@@ -0,0 +1,406 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    error::{CommandError, ProtocolError},
+    protocol::Value,
+};
+
+/// Key a hash field is stored under in a [`Value::Map`].
+///
+/// Hashes are maps whose keys are the encoded fields, so a field is looked up by its encoding.
+pub fn field_key(field: &[u8]) -> BytesMut {
+    Value::Bytes(Cow::Borrowed(field)).encode()
+}
+
+/// Moves `field` out of the hash at `src_hash` into the top-level key `dst_key`, overwriting it.
+///
+/// Both keys are locked together, so no client sees the value in both places or in neither.
+/// Replies with 1 if the field was moved, 0 if the hash or the field does not exist. A hash left
+/// empty is removed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HMove {
+    pub src_hash: BytesMut,
+    pub field: BytesMut,
+    pub dst_key: BytesMut,
+}
+
+impl Command for HMove {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let result = {
+            let mut shards = db.map.write_keys([&self.src_hash, &self.dst_key]);
+            let src = shards.shard(&self.src_hash);
+            let moved = match src.get_mut(&self.src_hash) {
+                Some(Value::Map(hash)) => {
+                    let value = hash.remove(&field_key(&self.field));
+                    if hash.is_empty() {
+                        src.remove(&self.src_hash);
+                    }
+                    Ok(value)
+                }
+                Some(_) => Err(CommandError::WrongType),
+                None => Ok(None),
+            };
+            moved.map(|value| match value {
+                Some(value) => {
+                    shards
+                        .shard(&self.dst_key)
+                        .insert(self.dst_key.clone(), value);
+                    true
+                }
+                None => false,
+            })
+        };
+        let reply = match result {
+            Ok(true) => {
+                db.modified(&self.src_hash);
+                db.modified(&self.dst_key);
+                Value::Positive(1)
+            }
+            Ok(false) => Value::Positive(0),
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(src_hash), Value::Bytes(field), Value::Bytes(dst_key)] => Ok(Self {
+                src_hash: BytesMut::from(src_hash.as_bytes()),
+                field: BytesMut::from(field.as_bytes()),
+                dst_key: BytesMut::from(dst_key.as_bytes()),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("HMOVE")),
+            Value::Bytes(Cow::Borrowed(self.src_hash.as_bytes())),
+            Value::Bytes(Cow::Borrowed(self.field.as_bytes())),
+            Value::Bytes(Cow::Borrowed(self.dst_key.as_bytes())),
+        ])
+    }
+}
+
+/// Sets `field` of the hash at `key` to `value`, creating the hash if needed, and replies with
+/// the whole hash as it is after the write.
+///
+/// The write and the read happen under one lock, so the reply is exactly the hash the write
+/// produced, without changes of other clients mixed in.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HSetGet {
+    pub key: BytesMut,
+    pub field: BytesMut,
+    pub value: Value<'static>,
+}
+
+impl Command for HSetGet {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let result = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            match shard
+                .entry(key)
+                .or_insert_with(|| Value::Map(HashMap::new()))
+            {
+                Value::Map(hash) => {
+                    hash.insert(field_key(&self.field), db.stored(self.value.clone()));
+                    Ok(Value::Map(hash.clone()))
+                }
+                _ => Err(CommandError::WrongType),
+            }
+        };
+        let reply = match result {
+            Ok(hash) => {
+                db.modified(&self.key);
+                hash
+            }
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), Value::Bytes(field), value] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                field: BytesMut::from(field.as_bytes()),
+                value: value.clone().to_owned(),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("HSETGET")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::Bytes(Cow::Borrowed(self.field.as_bytes())),
+            self.value.clone(),
+        ])
+    }
+}
+
+/// Merges `patch` into the hash at `key`, see [`Value::merge`], creating the hash if needed, and
+/// replies with the number of fields it has afterwards.
+///
+/// Fields set to nil in the patch are removed, so a single patch can add, change, and delete
+/// fields at once without other clients seeing it half applied. A hash left empty is removed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HMerge {
+    pub key: BytesMut,
+    pub patch: HashMap<BytesMut, Value<'static>>,
+}
+
+impl Command for HMerge {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let result = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            let merged = match shard.get_mut(&key) {
+                Some(hash) => hash
+                    .merge(self.patch.clone())
+                    .map(|changed| (hash.container_len().unwrap_or_default(), changed)),
+                None => {
+                    let mut hash = Value::Map(HashMap::new());
+                    let changed = hash.merge(self.patch.clone()) == Some(true);
+                    let fields = hash.container_len().unwrap_or_default();
+                    if changed {
+                        shard.insert(key.clone(), hash);
+                    }
+                    Some((fields, changed))
+                }
+            };
+            match merged {
+                Some((0, changed)) => Ok((0, shard.remove(&key).is_some() | changed)),
+                Some(merged) => Ok(merged),
+                None => Err(CommandError::WrongType),
+            }
+        };
+        let reply = match result {
+            Ok((fields, changed)) => {
+                if changed {
+                    db.modified(&self.key);
+                }
+                Value::Positive(fields as u64)
+            }
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), Value::Map(patch)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                patch: patch
+                    .iter()
+                    .map(|(field, value)| (field.clone(), value.clone().to_owned()))
+                    .collect(),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("HMERGE")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::Map(self.patch.clone()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{
+            entry::CommandEntry,
+            get::EMPTY,
+            hash::{field_key, HMerge, HMove, HSetGet},
+        },
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn hmove(field: &str) -> CommandEntry {
+        CommandEntry::HMove(HMove {
+            src_hash: BytesMut::from("user:1"),
+            field: BytesMut::from(field),
+            dst_key: BytesMut::from("name"),
+        })
+    }
+
+    #[tokio::test]
+    async fn move_field() {
+        let db = Arc::new(Db::default());
+        let hash = HashMap::from([
+            (field_key(b"name"), Value::String(Cow::Borrowed("alice"))),
+            (field_key(b"age"), Value::Positive(30)),
+        ]);
+        db.map.insert(BytesMut::from("user:1"), Value::Map(hash));
+
+        assert_eq!(execute(&db, hmove("name")).await, vec![Value::Positive(1)]);
+        assert_eq!(
+            db.map.get_owned(&BytesMut::from("name")),
+            Some(Value::String(Cow::Borrowed("alice")))
+        );
+        let Some(Value::Map(hash)) = db.map.get_owned(&BytesMut::from("user:1")) else {
+            panic!("the hash is gone");
+        };
+        assert!(!hash.contains_key(&field_key(b"name")));
+        assert_eq!(hash.get(&field_key(b"age")), Some(&Value::Positive(30)));
+
+        assert_eq!(execute(&db, hmove("name")).await, vec![Value::Positive(0)]);
+        assert_eq!(execute(&db, hmove("age")).await, vec![Value::Positive(1)]);
+        assert!(!db.map.contains(&BytesMut::from("user:1")));
+        assert_eq!(execute(&db, hmove("age")).await, vec![Value::Positive(0)]);
+    }
+
+    #[tokio::test]
+    async fn set_and_get_hash() {
+        let db = Arc::new(Db::default());
+        let mut hash = HashMap::from([
+            (field_key(b"name"), Value::String(Cow::Borrowed("alice"))),
+            (field_key(b"age"), Value::Positive(30)),
+        ]);
+        db.map
+            .insert(BytesMut::from("user:1"), Value::Map(hash.clone()));
+        let hsetget = CommandEntry::HSetGet(HSetGet {
+            key: BytesMut::from("user:1"),
+            field: BytesMut::from("city"),
+            value: Value::String(Cow::Borrowed("Kyiv")),
+        });
+
+        hash.insert(field_key(b"city"), Value::String(Cow::Borrowed("Kyiv")));
+        assert_eq!(execute(&db, hsetget).await, vec![Value::Map(hash.clone())]);
+        assert_eq!(
+            db.map.get_owned(&BytesMut::from("user:1")),
+            Some(Value::Map(hash))
+        );
+    }
+
+    #[tokio::test]
+    async fn merge_patch() {
+        let db = Arc::new(Db::default());
+        let hash = HashMap::from([
+            (field_key(b"name"), Value::String(Cow::Borrowed("alice"))),
+            (field_key(b"age"), Value::Positive(30)),
+            (field_key(b"city"), Value::String(Cow::Borrowed("Kyiv"))),
+        ]);
+        db.map.insert(BytesMut::from("user:1"), Value::Map(hash));
+        let hmerge = CommandEntry::HMerge(HMerge {
+            key: BytesMut::from("user:1"),
+            patch: HashMap::from([
+                (field_key(b"email"), Value::String(Cow::Borrowed("a@b.c"))),
+                (field_key(b"age"), Value::Positive(31)),
+                (field_key(b"city"), Value::Error(Cow::Borrowed(EMPTY))),
+            ]),
+        });
+
+        assert_eq!(execute(&db, hmerge).await, vec![Value::Positive(3)]);
+        assert_eq!(
+            db.map.get_owned(&BytesMut::from("user:1")),
+            Some(Value::Map(HashMap::from([
+                (field_key(b"name"), Value::String(Cow::Borrowed("alice"))),
+                (field_key(b"age"), Value::Positive(31)),
+                (field_key(b"email"), Value::String(Cow::Borrowed("a@b.c"))),
+            ])))
+        );
+    }
+
+    #[tokio::test]
+    async fn unchanged_by_patch() {
+        let db = Arc::new(Db::default());
+        let hmerge = |key: &str, patch| {
+            CommandEntry::HMerge(HMerge {
+                key: BytesMut::from(key),
+                patch,
+            })
+        };
+
+        assert_eq!(
+            execute(&db, hmerge("user:1", HashMap::new())).await,
+            vec![Value::Positive(0)]
+        );
+        assert!(!db.map.contains(&BytesMut::from("user:1")));
+
+        let hash = HashMap::from([(field_key(b"age"), Value::Positive(30))]);
+        db.map
+            .insert(BytesMut::from("user:1"), Value::Map(hash.clone()));
+        let patch = HashMap::from([
+            (field_key(b"age"), Value::Positive(30)),
+            (field_key(b"city"), Value::Error(Cow::Borrowed(EMPTY))),
+        ]);
+        assert_eq!(
+            execute(&db, hmerge("user:1", patch)).await,
+            vec![Value::Positive(1)]
+        );
+        assert_eq!(
+            db.map.get_owned(&BytesMut::from("user:1")),
+            Some(Value::Map(hash))
+        );
+        assert!(db.recent.latest(10).is_empty());
+
+        let patch = HashMap::from([(field_key(b"age"), Value::Error(Cow::Borrowed(EMPTY)))]);
+        assert_eq!(
+            execute(&db, hmerge("user:1", patch)).await,
+            vec![Value::Positive(0)]
+        );
+        assert!(!db.map.contains(&BytesMut::from("user:1")));
+        assert_eq!(db.recent.latest(10), vec![BytesMut::from("user:1")]);
+    }
+}
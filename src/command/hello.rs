@@ -0,0 +1,118 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{
+    codec::SUPPORTED_PROTOCOLS,
+    command::Command,
+    error::{CommandError, ProtocolError},
+    protocol::Value,
+};
+
+/// Replies with server metadata as a map of `version`, `protocol`, `role`, and `uptime` in
+/// seconds.
+///
+/// If `version` is given, the connection first switches to that protocol version, unsupported
+/// versions are rejected with an error and leave the connection as is.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Hello {
+    pub version: Option<u8>,
+}
+
+impl Command for Hello {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        if let Some(version) = self.version {
+            if !SUPPORTED_PROTOCOLS.contains(&version) {
+                let _ = connection
+                    .write_frame(db.error_reply(CommandError::UnsupportedProtocol))
+                    .await;
+                return Ok(());
+            }
+            connection.protocol = version;
+        }
+        let metadata = [
+            (
+                "version",
+                Value::String(Cow::Borrowed(env!("CARGO_PKG_VERSION"))),
+            ),
+            ("protocol", Value::Positive(connection.protocol as u64)),
+            ("role", Value::String(Cow::Borrowed("master"))),
+            ("uptime", Value::Positive(db.start.uptime().as_secs())),
+        ];
+        let metadata = metadata
+            .into_iter()
+            .map(|(key, value)| (Value::String(Cow::Borrowed(key)).encode(), value))
+            .collect::<HashMap<_, _>>();
+        let _ = connection.write_frame(Value::Map(metadata)).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [] => Ok(Self { version: None }),
+            [Value::Positive(version)] => Ok(Self {
+                version: Some(u8::try_from(*version).map_err(|_| ProtocolError::Command)?),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let mut array = vec![Value::String(Cow::Borrowed("HELLO"))];
+        array.extend(self.version.map(|version| Value::Positive(version as u64)));
+        Value::Array(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use crate::{
+        codec::PROTOCOL_VERSION,
+        command::{entry::CommandEntry, hello::Hello},
+        db::Db,
+        error::CommandError,
+        protocol::Value,
+        testing::execute,
+    };
+
+    #[tokio::test]
+    async fn metadata() {
+        let db = Arc::new(Db::default());
+        let reply = execute(&db, CommandEntry::Hello(Hello { version: None })).await;
+        let [Value::Map(metadata)] = &reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        let field = |name| metadata.get(&Value::String(Cow::Borrowed(name)).encode());
+        assert_eq!(
+            field("version"),
+            Some(&Value::String(Cow::Borrowed(env!("CARGO_PKG_VERSION"))))
+        );
+        assert_eq!(
+            field("protocol"),
+            Some(&Value::Positive(PROTOCOL_VERSION as u64))
+        );
+        assert_eq!(field("role"), Some(&Value::String(Cow::Borrowed("master"))));
+        assert_eq!(field("uptime"), Some(&Value::Positive(0)));
+    }
+
+    #[tokio::test]
+    async fn unsupported_version() {
+        let db = Arc::new(Db::default());
+        let reply = execute(&db, CommandEntry::Hello(Hello { version: Some(42) })).await;
+        assert_eq!(reply, vec![Value::from(CommandError::UnsupportedProtocol)]);
+    }
+}
@@ -3,7 +3,50 @@ use std::borrow::Cow;
 use bytes::BytesMut;
 use nom::AsBytes;
 
-use crate::{command::Command, protocol::Value};
+use crate::{
+    command::{decode_integer, Command},
+    db::Db,
+    error::CommandError,
+    protocol::Value,
+};
+
+/// Adds `by` to the number at `key` and replies with the result, see [`Value::coerce_number`].
+///
+/// Bytes and strings holding a decimal number are incremented too and stored as a number
+/// afterwards. A missing key is set to 0. Overflow is handled according to the configured
+/// [`crate::db::config::OverflowMode`].
+pub(crate) async fn increment<R, W>(
+    key: &BytesMut,
+    by: i128,
+    connection: &mut crate::codec::Connection<R, W>,
+    db: &Db,
+) where
+    R: tokio::io::AsyncRead + Unpin,
+    W: Unpin + tokio::io::AsyncWrite,
+{
+    let result = {
+        let (key, mut shard) = db.map.write(key.clone());
+        match shard.get_mut(&key) {
+            Some(value) => value
+                .coerce_number()
+                .ok_or(CommandError::NotANumber)
+                .and_then(|n| db.limits.overflow_mode().apply(n + by))
+                .inspect(|result| *value = result.clone()),
+            None => {
+                shard.insert(key, Value::Positive(0));
+                Ok(Value::Positive(0))
+            }
+        }
+    };
+    let reply = match result {
+        Ok(reply) => {
+            db.modified(key);
+            reply
+        }
+        Err(error) => db.error_reply(error),
+    };
+    let _ = connection.write_frame(reply).await;
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Incr {
@@ -16,33 +59,13 @@ impl Command for Incr {
     async fn execute<W, R>(
         &self,
         connection: &mut crate::codec::Connection<R, W>,
-        db: std::sync::Arc<sharded::Map<bytes::BytesMut, crate::protocol::Value<'static>>>,
+        db: std::sync::Arc<crate::db::Db>,
     ) -> Self::ExecutionResult
     where
         R: tokio::io::AsyncRead + Unpin,
         W: Unpin + tokio::io::AsyncWrite,
     {
-        let (key, mut shard) = db.write(self.key.clone());
-        if let Some(value) = shard.get_mut(key.clone()) {
-            match value.clone() {
-                Value::Positive(p) => {
-                    *value = Value::Positive(p + 1);
-                    let _ = connection.write_frame(Value::Positive(p + 1)).await;
-                }
-                Value::Negative(n) => {
-                    *value = Value::Negative(n + 1);
-                    let _ = connection.write_frame(Value::Negative(n + 1)).await;
-                }
-                _ => {
-                    let _ = connection
-                        .write_frame(Value::Error(Cow::from("Not a number")))
-                        .await;
-                }
-            };
-        } else {
-            shard.insert(key, Value::Positive(0));
-            let _ = connection.write_frame(Value::Positive(0)).await;
-        }
+        increment(&self.key, 1, connection, &db).await;
         Ok(())
     }
 
@@ -51,8 +74,8 @@ impl Command for Incr {
         Self: Sized,
         V: AsRef<[crate::protocol::Value<'c>]>,
     {
-        match req.as_ref()[0] {
-            Value::Bytes(ref b) => Ok(Self {
+        match req.as_ref().first() {
+            Some(Value::Bytes(b)) => Ok(Self {
                 key: BytesMut::from(b.as_bytes()),
             }),
             _ => Err(crate::error::ProtocolError::Command),
@@ -79,35 +102,13 @@ impl Command for IncrBy {
     async fn execute<W, R>(
         &self,
         connection: &mut crate::codec::Connection<R, W>,
-        db: std::sync::Arc<sharded::Map<BytesMut, Value<'static>>>,
+        db: std::sync::Arc<crate::db::Db>,
     ) -> Self::ExecutionResult
     where
         R: tokio::io::AsyncRead + Unpin,
         W: Unpin + tokio::io::AsyncWrite,
     {
-        let (key, mut shard) = db.write(self.key.clone());
-        if let Some(value) = shard.get_mut(key.clone()) {
-            match value.clone() {
-                Value::Positive(p) => {
-                    *value = Value::Positive((p as i64 + self.by) as u64);
-                    let _ = connection
-                        .write_frame(Value::Positive((p as i64 + self.by) as u64))
-                        .await;
-                }
-                Value::Negative(n) => {
-                    *value = Value::Negative(n + self.by);
-                    let _ = connection.write_frame(Value::Negative(n + self.by)).await;
-                }
-                _ => {
-                    let _ = connection
-                        .write_frame(Value::Error(Cow::from("Not a number")))
-                        .await;
-                }
-            };
-        } else {
-            shard.insert(key, Value::Positive(0));
-            let _ = connection.write_frame(Value::Positive(0)).await;
-        }
+        increment(&self.key, self.by as i128, connection, &db).await;
         Ok(())
     }
 
@@ -133,3 +134,351 @@ impl Command for IncrBy {
         ])
     }
 }
+
+/// Replies with the number at `key` and resets it to 0 in the same locked step, so no increment
+/// between the read and the reset is lost.
+///
+/// A missing key replies with 0 and is left missing.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GetReset {
+    pub key: BytesMut,
+}
+
+impl Command for GetReset {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let result = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            match shard.get_mut(&key) {
+                Some(value) => value
+                    .coerce_number()
+                    .and_then(Value::from_number)
+                    .ok_or(CommandError::NotANumber)
+                    .inspect(|_| *value = Value::Positive(0))
+                    .map(Some),
+                None => Ok(None),
+            }
+        };
+        let reply = match result {
+            Ok(Some(reply)) => {
+                db.modified(&self.key);
+                reply
+            }
+            Ok(None) => Value::Positive(0),
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+            }),
+            _ => Err(crate::error::ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::from("GETRESET")),
+            Value::Bytes(Cow::from(self.key.as_bytes())),
+        ])
+    }
+}
+
+/// Adds `by` to the number at `key` only if the result stays at or below `limit`, and replies
+/// with the result. Otherwise replies with [`CommandError::LimitExceeded`] and leaves the number
+/// as is.
+///
+/// The check and the increment happen under one lock, so concurrent clients can not overshoot
+/// the limit together. A missing key counts as 0.
+#[derive(Debug, PartialEq, Clone)]
+pub struct IncrIfBelow {
+    pub key: BytesMut,
+    pub limit: i64,
+    pub by: i64,
+}
+
+impl Command for IncrIfBelow {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let result = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            let current = match shard.get(&key) {
+                Some(value) => value.coerce_number().ok_or(CommandError::NotANumber),
+                None => Ok(0),
+            };
+            current
+                .map(|n| n + self.by as i128)
+                .and_then(|n| {
+                    if n > self.limit as i128 {
+                        return Err(CommandError::LimitExceeded);
+                    }
+                    Value::from_number(n).ok_or(CommandError::OutOfRange)
+                })
+                .inspect(|result| {
+                    shard.insert(key, result.clone());
+                })
+        };
+        let reply = match result {
+            Ok(reply) => {
+                db.modified(&self.key);
+                reply
+            }
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), limit, by] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                limit: decode_integer(limit)?,
+                by: decode_integer(by)?,
+            }),
+            _ => Err(crate::error::ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::from("INCRIFBELOW")),
+            Value::Bytes(Cow::from(self.key.as_bytes())),
+            Value::from_number(self.limit as i128).expect("every i64 fits into a value"),
+            Value::from_number(self.by as i128).expect("every i64 fits into a value"),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+    use test_case::test_case;
+
+    use crate::{
+        command::{
+            decr::DecrBy,
+            entry::CommandEntry,
+            get::Get,
+            incr::{GetReset, Incr, IncrBy, IncrIfBelow},
+            set::Set,
+        },
+        db::{
+            config::{OverflowMode, Parameter},
+            Db,
+        },
+        error::CommandError,
+        protocol::Value,
+        testing::execute,
+    };
+
+    async fn set(db: &Arc<Db>, value: Value<'static>) {
+        execute(
+            db,
+            CommandEntry::Set(Set {
+                key: BytesMut::from("key"),
+                value,
+            }),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn incr_numeric_string() {
+        let db = Arc::new(Db::default());
+        let incr = CommandEntry::Incr(Incr {
+            key: BytesMut::from("key"),
+        });
+        set(&db, Value::String(Cow::Borrowed("42"))).await;
+        assert_eq!(execute(&db, incr.clone()).await, vec![Value::Positive(43)]);
+        set(&db, Value::Bytes(Cow::Borrowed(b"42"))).await;
+        assert_eq!(execute(&db, incr.clone()).await, vec![Value::Positive(43)]);
+        set(&db, Value::String(Cow::Borrowed("forty two"))).await;
+        assert_eq!(
+            execute(&db, incr).await,
+            vec![Value::from(CommandError::NotANumber)]
+        );
+    }
+
+    #[test_case(OverflowMode::Error, Err(CommandError::OutOfRange))]
+    #[test_case(OverflowMode::Saturate, Ok(u64::MAX))]
+    #[test_case(OverflowMode::Wrap, Ok(0))]
+    #[tokio::test]
+    async fn incr_overflow(mode: OverflowMode, result: Result<u64, CommandError>) {
+        let db = Arc::new(Db::default());
+        db.config_set(Parameter::IncrOverflow, mode as u64).unwrap();
+        set(&db, Value::Positive(u64::MAX)).await;
+        let reply = execute(
+            &db,
+            CommandEntry::Incr(Incr {
+                key: BytesMut::from("key"),
+            }),
+        )
+        .await;
+        let stored = db.map.get_owned(&BytesMut::from("key"));
+        match result {
+            Ok(n) => {
+                assert_eq!(reply, vec![Value::Positive(n)]);
+                assert_eq!(stored, Some(Value::Positive(n)));
+            }
+            Err(error) => {
+                assert_eq!(reply, vec![Value::from(error)]);
+                assert_eq!(stored, Some(Value::Positive(u64::MAX)));
+            }
+        }
+    }
+
+    #[test_case(1, OverflowMode::Error, Ok(Value::Negative(i64::MIN)))]
+    #[test_case(2, OverflowMode::Error, Err(CommandError::OutOfRange))]
+    #[test_case(2, OverflowMode::Saturate, Ok(Value::Negative(i64::MIN)))]
+    #[test_case(2, OverflowMode::Wrap, Ok(Value::Positive(i64::MAX as u64)))]
+    #[tokio::test]
+    async fn decr_overflow(
+        by: i64,
+        mode: OverflowMode,
+        result: Result<Value<'static>, CommandError>,
+    ) {
+        let db = Arc::new(Db::default());
+        db.config_set(Parameter::IncrOverflow, mode as u64).unwrap();
+        set(&db, Value::Negative(i64::MIN + 1)).await;
+        let reply = execute(
+            &db,
+            CommandEntry::DecrBy(DecrBy {
+                key: BytesMut::from("key"),
+                by,
+            }),
+        )
+        .await;
+        let stored = db.map.get_owned(&BytesMut::from("key"));
+        match result {
+            Ok(n) => {
+                assert_eq!(reply, vec![n.clone()]);
+                assert_eq!(stored, Some(n));
+            }
+            Err(error) => {
+                assert_eq!(reply, vec![Value::from(error)]);
+                assert_eq!(stored, Some(Value::Negative(i64::MIN + 1)));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn decr_crosses_zero() {
+        let db = Arc::new(Db::default());
+        set(&db, Value::String(Cow::Borrowed("2"))).await;
+        execute(
+            &db,
+            CommandEntry::DecrBy(DecrBy {
+                key: BytesMut::from("key"),
+                by: 5,
+            }),
+        )
+        .await;
+        assert_eq!(
+            db.map.get_owned(&BytesMut::from("key")),
+            Some(Value::Negative(-3))
+        );
+    }
+
+    #[tokio::test]
+    async fn get_reset_drains_counter() {
+        let db = Arc::new(Db::default());
+        let key = BytesMut::from("key");
+        let get_reset = CommandEntry::GetReset(GetReset { key: key.clone() });
+        assert_eq!(
+            execute(&db, get_reset.clone()).await,
+            vec![Value::Positive(0)]
+        );
+        assert!(!db.map.contains(&key));
+
+        for _ in 0..3 {
+            execute(&db, CommandEntry::Incr(Incr { key: key.clone() })).await;
+        }
+        execute(
+            &db,
+            CommandEntry::IncrBy(IncrBy {
+                key: key.clone(),
+                by: 10,
+            }),
+        )
+        .await;
+        assert_eq!(
+            execute(&db, get_reset.clone()).await,
+            vec![Value::Positive(12)]
+        );
+        assert_eq!(
+            execute(&db, CommandEntry::Get(Get { key })).await,
+            vec![Value::Positive(0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn incr_if_below_limit() {
+        let db = Arc::new(Db::default());
+        let incr = |by| {
+            CommandEntry::IncrIfBelow(IncrIfBelow {
+                key: BytesMut::from("tokens"),
+                limit: 10,
+                by,
+            })
+        };
+        assert_eq!(execute(&db, incr(4)).await, vec![Value::Positive(4)]);
+        assert_eq!(execute(&db, incr(6)).await, vec![Value::Positive(10)]);
+        assert_eq!(
+            execute(&db, incr(1)).await,
+            vec![Value::from(CommandError::LimitExceeded)]
+        );
+        assert_eq!(
+            db.map.get_owned(b"tokens".as_slice()),
+            Some(Value::Positive(10))
+        );
+        assert_eq!(execute(&db, incr(-3)).await, vec![Value::Positive(7)]);
+    }
+
+    #[tokio::test]
+    async fn incr_if_below_missing_key_past_limit() {
+        let db = Arc::new(Db::default());
+        let incr = CommandEntry::IncrIfBelow(IncrIfBelow {
+            key: BytesMut::from("tokens"),
+            limit: 2,
+            by: 3,
+        });
+        assert_eq!(
+            execute(&db, incr).await,
+            vec![Value::from(CommandError::LimitExceeded)]
+        );
+        assert!(!db.map.contains(b"tokens".as_slice()));
+    }
+}
@@ -0,0 +1,125 @@
+use std::{borrow::Cow, sync::Arc};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::{
+        get::EMPTY,
+        streaming::{self, Replies, StreamingCommand},
+        Command,
+    },
+    db::{glob, Db},
+    error::ProtocolError,
+    protocol::Value,
+};
+
+/// Streams every key matching the glob `pattern`, see [`glob::matches`], each as its own frame,
+/// followed by a nil frame once all shards were walked.
+///
+/// Unlike collecting all matches into one reply, only the matches of a single shard are held at
+/// a time, so memory stays bounded however many keys match. Like [`Db::save_matching`], keys
+/// written meanwhile may or may not be sent.
+#[derive(Debug, PartialEq, Clone)]
+pub struct KeysStream {
+    pub pattern: BytesMut,
+}
+
+impl StreamingCommand for KeysStream {
+    async fn stream(&self, replies: Replies, db: Arc<Db>) -> crate::error::Result<()> {
+        for shard in 0..db.map.shard_count() {
+            let keys = db
+                .map
+                .shard_keys(shard, |key, _| glob::matches(&self.pattern, key));
+            for key in keys {
+                if !replies.send(Value::Bytes(Cow::Owned(key.to_vec()))).await {
+                    return Ok(());
+                }
+            }
+        }
+        replies.send(Value::Error(Cow::Borrowed(EMPTY))).await;
+        Ok(())
+    }
+}
+
+impl Command for KeysStream {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        streaming::execute(self, connection, db).await
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(pattern)] => Ok(Self {
+                pattern: BytesMut::from(pattern.as_bytes()),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("KEYSSTREAM")),
+            Value::Bytes(Cow::Borrowed(self.pattern.as_bytes())),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{entry::CommandEntry, get::EMPTY, keys::KeysStream},
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    #[tokio::test]
+    async fn frame_per_key() {
+        let db = Arc::new(Db::default());
+        for n in 0..100 {
+            db.map.insert(
+                BytesMut::from(format!("user:{n}").as_str()),
+                Value::Positive(n),
+            );
+            db.map.insert(
+                BytesMut::from(format!("order:{n}").as_str()),
+                Value::Positive(n),
+            );
+        }
+        let keys = CommandEntry::KeysStream(KeysStream {
+            pattern: BytesMut::from("user:*"),
+        });
+        let mut frames = execute(&db, keys).await;
+
+        assert_eq!(frames.pop(), Some(Value::Error(Cow::Borrowed(EMPTY))));
+        let mut keys: Vec<_> = frames
+            .into_iter()
+            .map(|frame| match frame {
+                Value::Bytes(key) => String::from_utf8(key.into_owned()).unwrap(),
+                frame => panic!("unexpected frame {frame:?}"),
+            })
+            .collect();
+        keys.sort_unstable();
+        let mut expected: Vec<_> = (0..100).map(|n| format!("user:{n}")).collect();
+        expected.sort_unstable();
+        assert_eq!(keys, expected);
+    }
+}
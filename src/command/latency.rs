@@ -0,0 +1,139 @@
+use std::borrow::Cow;
+
+use crate::{
+    command::{get::EMPTY, Command},
+    error::ProtocolError,
+    protocol::Value,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LatencySubcommand {
+    /// Replies with the latency in microseconds which `p` percent of executions did not exceed,
+    /// of `command` only if given, or nil if nothing was recorded.
+    Percentile { p: f64, command: Option<String> },
+}
+
+/// Exposes execution times of commands, see [`crate::db::latency`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Latency {
+    pub subcommand: LatencySubcommand,
+}
+
+impl Command for Latency {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match &self.subcommand {
+            LatencySubcommand::Percentile { p, command } => db
+                .latencies
+                .histogram(command.as_deref())
+                .percentile(*p)
+                .map(Value::Positive)
+                .unwrap_or(Value::Error(Cow::Borrowed(EMPTY))),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let [Value::String(subcommand), p, command @ ..] = req.as_ref() else {
+            return Err(ProtocolError::Command);
+        };
+        if subcommand != "PERCENTILE" {
+            return Err(ProtocolError::Command);
+        }
+        // Fractional percentiles like 99.9 are sent as strings.
+        let p = match p {
+            Value::Positive(p) => *p as f64,
+            Value::String(p) => p.parse().map_err(|_| ProtocolError::Command)?,
+            _ => return Err(ProtocolError::Command),
+        };
+        if !(0.0..=100.0).contains(&p) {
+            return Err(ProtocolError::Command);
+        }
+        let command = match command {
+            [] => None,
+            [Value::String(command)] => Some(command.to_string()),
+            _ => return Err(ProtocolError::Command),
+        };
+        Ok(Self {
+            subcommand: LatencySubcommand::Percentile { p, command },
+        })
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let LatencySubcommand::Percentile { p, command } = &self.subcommand;
+        let mut array = vec![
+            Value::String(Cow::Borrowed("LATENCY")),
+            Value::String(Cow::Borrowed("PERCENTILE")),
+            Value::String(Cow::Owned(p.to_string())),
+        ];
+        if let Some(command) = command {
+            array.push(Value::String(Cow::Borrowed(command)));
+        }
+        Value::Array(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{
+        command::{
+            debug::{Debug, DebugSubcommand},
+            entry::CommandEntry,
+            latency::{Latency, LatencySubcommand},
+            ping::Ping,
+        },
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    async fn percentile(db: &Arc<Db>, p: f64, command: Option<&str>) -> u64 {
+        let latency = CommandEntry::Latency(Latency {
+            subcommand: LatencySubcommand::Percentile {
+                p,
+                command: command.map(str::to_string),
+            },
+        });
+        let reply = execute(db, latency).await;
+        let [Value::Positive(micros)] = reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        micros
+    }
+
+    #[tokio::test]
+    async fn tail_latency() {
+        let db = Arc::new(Db::default());
+        for _ in 0..98 {
+            execute(&db, CommandEntry::Ping(Ping)).await;
+        }
+        for _ in 0..2 {
+            let sleep = CommandEntry::Debug(Debug {
+                subcommand: DebugSubcommand::Sleep(Duration::from_millis(20)),
+            });
+            execute(&db, sleep).await;
+        }
+
+        assert!(percentile(&db, 50.0, None).await < 10_000);
+        let p99 = percentile(&db, 99.0, None).await;
+        assert!((20_000..100_000).contains(&p99), "{p99}");
+        assert!(percentile(&db, 50.0, Some("DEBUG")).await >= 20_000);
+        assert!(percentile(&db, 99.0, Some("PING")).await < 10_000);
+    }
+}
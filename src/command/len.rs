@@ -0,0 +1,116 @@
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    error::{CommandError, ProtocolError},
+    protocol::Value,
+};
+
+/// Replies with the number of elements of the list or hash at `key`, see
+/// [`Value::container_len`], or 0 if the key does not exist.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Len {
+    pub key: BytesMut,
+}
+
+impl Command for Len {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let len = {
+            let (key, shard) = db.map.read(&self.key);
+            match shard.get(key) {
+                Some(value) => value.container_len().ok_or(CommandError::WrongType),
+                None => Ok(0),
+            }
+        };
+        let reply = match len {
+            Ok(len) => Value::Positive(len as u64),
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("LEN")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{entry::CommandEntry, hash::field_key, len::Len},
+        db::Db,
+        error::CommandError,
+        protocol::Value,
+        testing::execute,
+    };
+
+    async fn len(db: &Arc<Db>, key: &str) -> Vec<Value<'static>> {
+        execute(
+            db,
+            CommandEntry::Len(Len {
+                key: BytesMut::from(key),
+            }),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn containers() {
+        let db = Arc::new(Db::default());
+        db.map.insert(
+            BytesMut::from("list"),
+            Value::Array(vec![Value::Positive(1), Value::Positive(2)]),
+        );
+        db.map.insert(
+            BytesMut::from("hash"),
+            Value::Map(HashMap::from([
+                (field_key(b"a"), Value::Positive(1)),
+                (field_key(b"b"), Value::Positive(2)),
+                (field_key(b"c"), Value::Positive(3)),
+            ])),
+        );
+        db.map.insert(BytesMut::from("number"), Value::Positive(42));
+
+        assert_eq!(len(&db, "list").await, vec![Value::Positive(2)]);
+        assert_eq!(len(&db, "hash").await, vec![Value::Positive(3)]);
+        assert_eq!(len(&db, "missing").await, vec![Value::Positive(0)]);
+        assert_eq!(
+            len(&db, "number").await,
+            vec![Value::from(CommandError::WrongType)]
+        );
+    }
+}
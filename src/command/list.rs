@@ -0,0 +1,846 @@
+use std::{borrow::Cow, cmp::Ordering, collections::HashSet, time::Duration};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+use tokio::time::Instant;
+
+use crate::{
+    command::{decode_integer, get::EMPTY, Command},
+    db::Db,
+    error::{CommandError, ProtocolError},
+    protocol::{EncodeOptions, Value},
+};
+
+/// Pushes `values` to the front of the list at `key`, creating it if needed.
+///
+/// Values are pushed one after another, so they end up in reverse order. Replies with the new
+/// length of the list.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LPush {
+    pub key: BytesMut,
+    pub values: Vec<Value<'static>>,
+}
+
+impl Command for LPush {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            match shard.entry(key).or_insert_with(|| Value::Array(vec![])) {
+                Value::Array(items) => {
+                    for value in &self.values {
+                        items.insert(0, value.clone());
+                    }
+                    Value::Positive(items.len() as u64)
+                }
+                _ => db.error_reply(CommandError::WrongType),
+            }
+        };
+        if let Value::Positive(_) = reply {
+            db.modified(&self.key);
+        }
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), values @ ..] if !values.is_empty() => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                values: values.iter().map(|v| v.clone().to_owned()).collect(),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let mut array = vec![
+            Value::String(Cow::Borrowed("LPUSH")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+        ];
+        array.extend(self.values.iter().cloned());
+        Value::Array(array)
+    }
+}
+
+/// Pops the front element of the first non-empty list among `keys`, blocking until one is
+/// pushed to if they are all empty.
+///
+/// Replies with `[key, element]`, or nil once `timeout_ms` elapses. A timeout of 0 blocks
+/// forever.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlPop {
+    pub keys: Vec<BytesMut>,
+    pub timeout_ms: u64,
+}
+
+impl BlPop {
+    /// Pops from the first non-empty list, `None` if every list is empty.
+    fn pop(&self, db: &Db) -> Option<Value<'static>> {
+        for key in &self.keys {
+            let (key, mut shard) = db.map.write(key.clone());
+            let element = match shard.get_mut(&key) {
+                Some(Value::Array(items)) if !items.is_empty() => {
+                    let element = items.remove(0);
+                    if items.is_empty() {
+                        shard.remove(&key);
+                    }
+                    element
+                }
+                Some(Value::Array(_)) | None => continue,
+                Some(_) => return Some(db.error_reply(CommandError::WrongType)),
+            };
+            drop(shard);
+            db.modified(&key);
+            return Some(Value::Array(vec![
+                Value::Bytes(Cow::Owned(key.to_vec())),
+                element,
+            ]));
+        }
+        None
+    }
+}
+
+impl Command for BlPop {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let deadline =
+            (self.timeout_ms != 0).then(|| Instant::now() + Duration::from_millis(self.timeout_ms));
+        let reply = {
+            let registration = db.waiters.register(&self.keys);
+            loop {
+                if let Some(reply) = self.pop(&db) {
+                    break reply;
+                }
+                match deadline {
+                    Some(deadline) => {
+                        if tokio::time::timeout_at(deadline, registration.notified())
+                            .await
+                            .is_err()
+                        {
+                            break Value::Error(Cow::Borrowed(EMPTY));
+                        }
+                    }
+                    None => registration.notified().await,
+                }
+            }
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let [keys @ .., Value::Positive(timeout_ms)] = req.as_ref() else {
+            return Err(ProtocolError::Command);
+        };
+        if keys.is_empty() {
+            return Err(ProtocolError::Command);
+        }
+        let keys = keys
+            .iter()
+            .map(|key| match key {
+                Value::Bytes(key) => Ok(BytesMut::from(key.as_bytes())),
+                _ => Err(ProtocolError::Command),
+            })
+            .collect::<crate::error::Result<_>>()?;
+        Ok(Self {
+            keys,
+            timeout_ms: *timeout_ms,
+        })
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let mut array = vec![Value::String(Cow::Borrowed("BLPOP"))];
+        array.extend(
+            self.keys
+                .iter()
+                .map(|key| Value::Bytes(Cow::Borrowed(key.as_bytes()))),
+        );
+        array.push(Value::Positive(self.timeout_ms));
+        Value::Array(array)
+    }
+}
+
+/// Replies with the element at `index` of the list at `key`, negative indices count from the end.
+///
+/// Replies with nil if the key does not exist or the index is out of range.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LIndex {
+    pub key: BytesMut,
+    pub index: i64,
+}
+
+impl Command for LIndex {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = {
+            let (key, shard) = db.map.read(&self.key);
+            match shard.get(key) {
+                Some(Value::Array(items)) => {
+                    let index = if self.index < 0 {
+                        items.len().checked_sub(self.index.unsigned_abs() as usize)
+                    } else {
+                        Some(self.index as usize)
+                    };
+                    index.and_then(|index| items.get(index)).cloned()
+                }
+                Some(_) => Some(db.error_reply(CommandError::WrongType)),
+                None => None,
+            }
+        };
+        let reply = reply.unwrap_or(Value::Error(Cow::Borrowed(EMPTY)));
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), index] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                index: decode_integer(index)?,
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("LINDEX")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::from_number(self.index as i128).expect("every i64 fits into a value"),
+        ])
+    }
+}
+
+/// Replies with the elements of the list at `key_a` which are not in the list at `key_b`, in
+/// their order in `key_a`. Elements are compared by their canonical encoding, so equal maps match
+/// whatever order their entries are in. Missing keys are empty lists.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LDiff {
+    pub key_a: BytesMut,
+    pub key_b: BytesMut,
+}
+
+impl LDiff {
+    /// Canonical encoding of a decompressed element.
+    fn encoded(item: &Value<'_>) -> BytesMut {
+        item.decompressed()
+            .into_owned()
+            .encode_with(&EncodeOptions { canonical: true })
+    }
+
+    /// Encoded elements of the list at `key`, `Err` if it holds anything but a list.
+    fn encoded_elements(db: &Db, key: &[u8]) -> Result<HashSet<BytesMut>, CommandError> {
+        let (key, shard) = db.map.read(key);
+        match shard.get(key) {
+            Some(Value::Array(items)) => Ok(items.iter().map(Self::encoded).collect()),
+            Some(_) => Err(CommandError::WrongType),
+            None => Ok(HashSet::new()),
+        }
+    }
+
+    fn diff(&self, db: &Db) -> Result<Vec<Value<'static>>, CommandError> {
+        let exclude = Self::encoded_elements(db, &self.key_b)?;
+        let (key, shard) = db.map.read(&self.key_a);
+        match shard.get(key) {
+            Some(Value::Array(items)) => Ok(items
+                .iter()
+                .filter(|&item| !exclude.contains(&Self::encoded(item)))
+                .cloned()
+                .collect()),
+            Some(_) => Err(CommandError::WrongType),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+impl Command for LDiff {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match self.diff(&db) {
+            Ok(items) => Value::Array(items),
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key_a), Value::Bytes(key_b)] => Ok(Self {
+                key_a: BytesMut::from(key_a.as_bytes()),
+                key_b: BytesMut::from(key_b.as_bytes()),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("LDIFF")),
+            Value::Bytes(Cow::Borrowed(self.key_a.as_bytes())),
+            Value::Bytes(Cow::Borrowed(self.key_b.as_bytes())),
+        ])
+    }
+}
+
+/// Keeps only the elements from `start` to `stop` inclusive of the list at `key` and replies with
+/// `OK`. Negative indices count from the end, indices past either end are clamped.
+///
+/// The key is removed if no element is left, values other than lists reply with
+/// [`CommandError::WrongType`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct LTrim {
+    pub key: BytesMut,
+    pub start: i64,
+    pub stop: i64,
+}
+
+impl LTrim {
+    /// Range of the kept elements in a list of `len` elements.
+    fn range(&self, len: usize) -> std::ops::Range<usize> {
+        let len = len as i64;
+        let resolve = |index: i64| if index < 0 { len + index } else { index };
+        let start = resolve(self.start).max(0);
+        let end = resolve(self.stop).min(len - 1) + 1;
+        if start >= end {
+            return 0..0;
+        }
+        start as usize..end as usize
+    }
+}
+
+impl Command for LTrim {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let result = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            match shard.get_mut(&key) {
+                Some(Value::Array(items)) => {
+                    let range = self.range(items.len());
+                    items.truncate(range.end);
+                    items.drain(..range.start);
+                    if items.is_empty() {
+                        shard.remove(&key);
+                    }
+                    Ok(true)
+                }
+                Some(_) => Err(CommandError::WrongType),
+                None => Ok(false),
+            }
+        };
+        let reply = match result {
+            Ok(changed) => {
+                if changed {
+                    db.modified(&self.key);
+                }
+                Value::String(Cow::Borrowed("OK"))
+            }
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), start, stop] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                start: decode_integer(start)?,
+                stop: decode_integer(stop)?,
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("LTRIM")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::from_number(self.start as i128).expect("every i64 fits into a value"),
+            Value::from_number(self.stop as i128).expect("every i64 fits into a value"),
+        ])
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Replies with a sorted copy of the list at `key`, leaving the list as is.
+///
+/// Elements which are numbers, see [`Value::coerce_number`], are compared numerically and sorted
+/// before all others, which are compared by their bytes. `LIMIT offset count` replies with only
+/// that part of the sorted list. A missing key is an empty list, values other than lists reply
+/// with [`CommandError::WrongType`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Sort {
+    pub key: BytesMut,
+    pub order: SortOrder,
+    /// Offset and count of the replied elements.
+    pub limit: Option<(usize, usize)>,
+}
+
+/// Ordering of list elements, see [`Sort`].
+fn compare(a: &Value<'_>, b: &Value<'_>) -> Ordering {
+    fn bytes<'v>(value: &'v Value<'_>) -> Cow<'v, [u8]> {
+        match value {
+            Value::Bytes(bytes) => Cow::Borrowed(bytes),
+            Value::String(string) => Cow::Borrowed(string.as_bytes()),
+            value => Cow::Owned(value.clone().encode().to_vec()),
+        }
+    }
+    match (a.coerce_number(), b.coerce_number()) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => bytes(&a.decompressed()).cmp(&bytes(&b.decompressed())),
+    }
+}
+
+impl Command for Sort {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let items = {
+            let (key, shard) = db.map.read(&self.key);
+            match shard.get(key) {
+                Some(Value::Array(items)) => Ok(items.clone()),
+                Some(_) => Err(CommandError::WrongType),
+                None => Ok(vec![]),
+            }
+        };
+        let reply = match items {
+            Ok(mut items) => {
+                items.sort_by(|a, b| match self.order {
+                    SortOrder::Asc => compare(a, b),
+                    SortOrder::Desc => compare(b, a),
+                });
+                let (offset, count) = self.limit.unwrap_or((0, items.len()));
+                Value::Array(items.into_iter().skip(offset).take(count).collect())
+            }
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let [Value::Bytes(key), options @ ..] = req.as_ref() else {
+            return Err(ProtocolError::Command);
+        };
+        let mut options = options;
+        let mut sort = Self {
+            key: BytesMut::from(key.as_bytes()),
+            order: SortOrder::Asc,
+            limit: None,
+        };
+        loop {
+            options = match options {
+                [] => return Ok(sort),
+                [Value::String(order), rest @ ..] if order == "ASC" => {
+                    sort.order = SortOrder::Asc;
+                    rest
+                }
+                [Value::String(order), rest @ ..] if order == "DESC" => {
+                    sort.order = SortOrder::Desc;
+                    rest
+                }
+                [Value::String(limit), Value::Positive(offset), Value::Positive(count), rest @ ..]
+                    if limit == "LIMIT" =>
+                {
+                    sort.limit = Some((*offset as usize, *count as usize));
+                    rest
+                }
+                _ => return Err(ProtocolError::Command),
+            };
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let order = match self.order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+        let mut array = vec![
+            Value::String(Cow::Borrowed("SORT")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::String(Cow::Borrowed(order)),
+        ];
+        if let Some((offset, count)) = self.limit {
+            array.push(Value::String(Cow::Borrowed("LIMIT")));
+            array.push(Value::Positive(offset as u64));
+            array.push(Value::Positive(count as u64));
+        }
+        Value::Array(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap, sync::Arc, time::Duration};
+
+    use bytes::BytesMut;
+    use test_case::test_case;
+
+    use crate::{
+        command::{
+            entry::CommandEntry,
+            get::EMPTY,
+            hash::field_key,
+            list::{BlPop, LDiff, LIndex, LPush, LTrim, Sort, SortOrder},
+            set::Set,
+        },
+        db::Db,
+        error::CommandError,
+        protocol::Value,
+        testing::execute,
+    };
+
+    #[tokio::test]
+    async fn blpop_unblocked_by_lpush() {
+        let db = Arc::new(Db::default());
+        let key = BytesMut::from(&b"queue"[..]);
+        let blpop = CommandEntry::BlPop(BlPop {
+            keys: vec![BytesMut::from(&b"other"[..]), key.clone()],
+            timeout_ms: 0,
+        });
+        let lpush = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            execute(
+                &db,
+                CommandEntry::LPush(LPush {
+                    key: key.clone(),
+                    values: vec![Value::Positive(42)],
+                }),
+            )
+            .await
+        };
+        let (popped, pushed) = tokio::join!(execute(&db, blpop), lpush);
+        assert_eq!(pushed, vec![Value::Positive(1)]);
+        assert_eq!(
+            popped,
+            vec![Value::Array(vec![
+                Value::Bytes(Cow::Borrowed(b"queue")),
+                Value::Positive(42)
+            ])]
+        );
+        assert!(!db.map.contains(&key));
+    }
+
+    #[tokio::test]
+    async fn blpop_unblocked_by_set() {
+        let db = Arc::new(Db::default());
+        let key = BytesMut::from(&b"queue"[..]);
+        let blpop = CommandEntry::BlPop(BlPop {
+            keys: vec![key.clone()],
+            timeout_ms: 5000,
+        });
+        let set = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            execute(
+                &db,
+                CommandEntry::Set(Set {
+                    key: key.clone(),
+                    value: Value::Array(vec![Value::Positive(42)]),
+                }),
+            )
+            .await;
+        };
+        let start = std::time::Instant::now();
+        let (popped, _) = tokio::join!(execute(&db, blpop), set);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            popped,
+            vec![Value::Array(vec![
+                Value::Bytes(Cow::Borrowed(b"queue")),
+                Value::Positive(42)
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn blpop_timeout() {
+        let db = Arc::new(Db::default());
+        let reply = execute(
+            &db,
+            CommandEntry::BlPop(BlPop {
+                keys: vec![BytesMut::from(&b"queue"[..])],
+                timeout_ms: 10,
+            }),
+        )
+        .await;
+        assert_eq!(reply, vec![Value::Error(Cow::Borrowed(EMPTY))]);
+    }
+
+    #[test_case(0, Some(10); "first")]
+    #[test_case(2, Some(12); "last")]
+    #[test_case(-1, Some(12); "negative last")]
+    #[test_case(-3, Some(10); "negative first")]
+    #[test_case(3, None; "past the end")]
+    #[test_case(-4, None; "before the start")]
+    #[tokio::test]
+    async fn lindex(index: i64, element: Option<u64>) {
+        let db = Arc::new(Db::default());
+        db.map.insert(
+            BytesMut::from("list"),
+            Value::Array((10..13).map(Value::Positive).collect()),
+        );
+        let reply = execute(
+            &db,
+            CommandEntry::LIndex(LIndex {
+                key: BytesMut::from("list"),
+                index,
+            }),
+        )
+        .await;
+        let expected = element
+            .map(Value::Positive)
+            .unwrap_or(Value::Error(Cow::Borrowed(EMPTY)));
+        assert_eq!(reply, vec![expected]);
+    }
+
+    #[tokio::test]
+    async fn ldiff_keeps_order() {
+        let db = Arc::new(Db::default());
+        let list = |items: &[&'static str]| {
+            Value::Array(
+                items
+                    .iter()
+                    .map(|item| Value::String(Cow::Borrowed(*item)))
+                    .collect(),
+            )
+        };
+        db.map
+            .insert(BytesMut::from("a"), list(&["e", "b", "d", "a", "c", "b"]));
+        db.map.insert(BytesMut::from("b"), list(&["b", "c", "x"]));
+        let ldiff = |key_a: &str, key_b: &str| {
+            CommandEntry::LDiff(LDiff {
+                key_a: BytesMut::from(key_a),
+                key_b: BytesMut::from(key_b),
+            })
+        };
+
+        assert_eq!(
+            execute(&db, ldiff("a", "b")).await,
+            vec![list(&["e", "d", "a"])]
+        );
+        assert_eq!(
+            execute(&db, ldiff("a", "missing")).await,
+            vec![list(&["e", "b", "d", "a", "c", "b"])]
+        );
+        assert_eq!(execute(&db, ldiff("missing", "a")).await, vec![list(&[])]);
+    }
+
+    #[tokio::test]
+    async fn ldiff_matches_equal_maps() {
+        let db = Arc::new(Db::default());
+        let fields = (0..32).map(|n| (field_key(n.to_string().as_bytes()), Value::Positive(n)));
+        let map = |fields: Vec<_>| Value::Map(fields.into_iter().collect::<HashMap<_, _>>());
+        db.map.insert(
+            BytesMut::from("a"),
+            Value::Array(vec![map(fields.clone().collect()), Value::Positive(1)]),
+        );
+        db.map.insert(
+            BytesMut::from("b"),
+            Value::Array(vec![map(fields.rev().collect())]),
+        );
+        assert_eq!(
+            execute(
+                &db,
+                CommandEntry::LDiff(LDiff {
+                    key_a: BytesMut::from("a"),
+                    key_b: BytesMut::from("b"),
+                })
+            )
+            .await,
+            vec![Value::Array(vec![Value::Positive(1)])]
+        );
+    }
+
+    #[test_case(1, 3, Some(&[11, 12, 13]); "sub-range")]
+    #[test_case(-2, -1, Some(&[13, 14]); "negative")]
+    #[test_case(-100, 100, Some(&[10, 11, 12, 13, 14]); "clamped")]
+    #[test_case(3, 1, None; "start after stop")]
+    #[test_case(5, 10, None; "past the end")]
+    #[test_case(-100, -6, None; "before the start")]
+    #[tokio::test]
+    async fn ltrim(start: i64, stop: i64, kept: Option<&[u64]>) {
+        let db = Arc::new(Db::default());
+        db.map.insert(
+            BytesMut::from("list"),
+            Value::Array((10..15).map(Value::Positive).collect()),
+        );
+        let reply = execute(
+            &db,
+            CommandEntry::LTrim(LTrim {
+                key: BytesMut::from("list"),
+                start,
+                stop,
+            }),
+        )
+        .await;
+        assert_eq!(reply, vec![Value::String(Cow::Borrowed("OK"))]);
+        let kept =
+            kept.map(|kept| Value::Array(kept.iter().copied().map(Value::Positive).collect()));
+        assert_eq!(db.map.get_owned(b"list".as_slice()), kept);
+    }
+
+    #[tokio::test]
+    async fn ltrim_wrong_type() {
+        let db = Arc::new(Db::default());
+        db.map.insert(BytesMut::from("key"), Value::Positive(1));
+        let reply = execute(
+            &db,
+            CommandEntry::LTrim(LTrim {
+                key: BytesMut::from("key"),
+                start: 0,
+                stop: 1,
+            }),
+        )
+        .await;
+        assert_eq!(reply, vec![Value::from(CommandError::WrongType)]);
+    }
+
+    #[test_case(SortOrder::Asc, None, &[1, 2, 10, 30, 100]; "ascending")]
+    #[test_case(SortOrder::Desc, None, &[100, 30, 10, 2, 1]; "descending")]
+    #[test_case(SortOrder::Asc, Some((1, 3)), &[2, 10, 30]; "ascending limit")]
+    #[test_case(SortOrder::Desc, Some((3, 10)), &[2, 1]; "descending limit")]
+    #[tokio::test]
+    async fn sort_numbers(order: SortOrder, limit: Option<(usize, usize)>, sorted: &[u64]) {
+        let db = Arc::new(Db::default());
+        db.map.insert(
+            BytesMut::from("list"),
+            Value::Array(vec![
+                Value::Positive(10),
+                Value::String(Cow::Borrowed("2")),
+                Value::Positive(100),
+                Value::Positive(1),
+                Value::Bytes(Cow::Borrowed(b"30")),
+            ]),
+        );
+        let reply = execute(
+            &db,
+            CommandEntry::Sort(Sort {
+                key: BytesMut::from("list"),
+                order,
+                limit,
+            }),
+        )
+        .await;
+        let [Value::Array(items)] = &reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        let numbers = items
+            .iter()
+            .map(|item| item.coerce_number().unwrap() as u64)
+            .collect::<Vec<_>>();
+        assert_eq!(numbers, sorted);
+    }
+
+    #[tokio::test]
+    async fn sort_strings_after_numbers() {
+        let db = Arc::new(Db::default());
+        let string = |s: &'static str| Value::String(Cow::Borrowed(s));
+        db.map.insert(
+            BytesMut::from("list"),
+            Value::Array(vec![string("b"), Value::Positive(3), string("a")]),
+        );
+        let reply = execute(
+            &db,
+            CommandEntry::Sort(Sort {
+                key: BytesMut::from("list"),
+                order: SortOrder::Asc,
+                limit: None,
+            }),
+        )
+        .await;
+        assert_eq!(
+            reply,
+            vec![Value::Array(vec![
+                Value::Positive(3),
+                string("a"),
+                string("b")
+            ])]
+        );
+    }
+}
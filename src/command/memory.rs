@@ -0,0 +1,388 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::{get::EMPTY, Command},
+    error::ProtocolError,
+    protocol::Value,
+};
+
+/// Replies with the approximate number of bytes the entry at `key` consumes, see
+/// [`Value::deep_size_of`], or nil if the key does not exist. The key itself is included.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MemUsage {
+    pub key: BytesMut,
+}
+
+impl Command for MemUsage {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = {
+            let (key, shard) = db.map.read(&self.key);
+            match shard.get(key) {
+                Some(value) => Value::Positive((key.len() + value.deep_size_of()) as u64),
+                None => Value::Error(Cow::Borrowed(EMPTY)),
+            }
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("MEMUSAGE")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+        ])
+    }
+}
+
+/// Replies with `[keys, bytes]` for every shard of the keyspace, where `bytes` is the
+/// approximate memory its entries consume, counted like [`MemUsage`].
+///
+/// Meant for spotting shards much fuller than the others, which hints at a poor hash or a few hot
+/// keys.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ShardStats;
+
+impl Command for ShardStats {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let stats = db
+            .map
+            .shard_stats(|key, value| key.len() + value.deep_size_of())
+            .into_iter()
+            .map(|(keys, bytes)| {
+                Value::Array(vec![
+                    Value::Positive(keys as u64),
+                    Value::Positive(bytes as u64),
+                ])
+            })
+            .collect();
+        let _ = connection.write_frame(Value::Array(stats)).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        if req.as_ref().is_empty() {
+            Ok(Self)
+        } else {
+            Err(ProtocolError::Command)
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![Value::String(Cow::Borrowed("SHARDSTATS"))])
+    }
+}
+
+/// Replies with a map from size to the number of values whose encoded size is at most that size
+/// but larger than half of it, i.e. a histogram with power of two buckets. Empty buckets are left
+/// out.
+///
+/// Shards are walked one at a time, see [`crate::db::map::Map::for_each`], so the whole keyspace
+/// is never locked at once, but the result is not a consistent snapshot either.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SizeHistogram;
+
+impl SizeHistogram {
+    /// Bucket counts keyed by the upper bound of the bucket.
+    pub fn buckets(db: &crate::db::Db) -> HashMap<u64, u64> {
+        let mut buckets = HashMap::new();
+        let mut scratch = BytesMut::new();
+        db.map.for_each(|_, value| {
+            scratch.clear();
+            value.clone().encode_into(&mut scratch);
+            *buckets
+                .entry(scratch.len().next_power_of_two() as u64)
+                .or_default() += 1;
+        });
+        buckets
+    }
+}
+
+impl Command for SizeHistogram {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let histogram = Self::buckets(&db)
+            .into_iter()
+            .map(|(size, count)| (Value::Positive(size).encode(), Value::Positive(count)))
+            .collect();
+        let _ = connection.write_frame(Value::Map(histogram)).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        if req.as_ref().is_empty() {
+            Ok(Self)
+        } else {
+            Err(ProtocolError::Command)
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![Value::String(Cow::Borrowed("SIZEHISTOGRAM"))])
+    }
+}
+
+/// Replies with `[used, resident, ratio]`: the approximate bytes all entries consume, counted
+/// like [`MemUsage`], the resident set size of the process, and the latter divided by the former
+/// as a string with two decimals, e.g. `"1.42"`.
+///
+/// A ratio well above 1 means most memory of the process is not held by entries, e.g. because
+/// the allocator keeps freed memory around after many deletes, which a restart reclaims. The
+/// resident size is read from `/proc/self/status`, so on other platforms `resident` and `ratio`
+/// are nil.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fragmentation;
+
+impl Fragmentation {
+    /// Resident set size of the process in bytes, `None` if the platform does not report it.
+    fn resident_bytes() -> Option<u64> {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let kilobytes = status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))?
+            .trim()
+            .strip_suffix("kB")?
+            .trim()
+            .parse::<u64>()
+            .ok()?;
+        Some(kilobytes * 1024)
+    }
+}
+
+impl Command for Fragmentation {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let mut used = 0;
+        db.map
+            .for_each(|key, value| used += (key.len() + value.deep_size_of()) as u64);
+        let nil = || Value::Error(Cow::Borrowed(EMPTY));
+        let reply = match Self::resident_bytes() {
+            Some(resident) => vec![
+                Value::Positive(used),
+                Value::Positive(resident),
+                Value::String(Cow::Owned(format!(
+                    "{:.2}",
+                    resident as f64 / used.max(1) as f64
+                ))),
+            ],
+            None => vec![Value::Positive(used), nil(), nil()],
+        };
+        let _ = connection.write_frame(Value::Array(reply)).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        if req.as_ref().is_empty() {
+            Ok(Self)
+        } else {
+            Err(ProtocolError::Command)
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![Value::String(Cow::Borrowed("FRAGMENTATION"))])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{
+            entry::CommandEntry,
+            get::EMPTY,
+            memory::{Fragmentation, MemUsage, ShardStats, SizeHistogram},
+        },
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    async fn usage(db: &Arc<Db>, key: &str) -> Value<'static> {
+        let mut reply = execute(
+            db,
+            CommandEntry::MemUsage(MemUsage {
+                key: BytesMut::from(key),
+            }),
+        )
+        .await;
+        reply.pop().unwrap()
+    }
+
+    #[tokio::test]
+    async fn small_and_large() {
+        let db = Arc::new(Db::default());
+        db.map.insert(
+            BytesMut::from("small"),
+            Value::Bytes(Cow::Owned(vec![0; 10])),
+        );
+        db.map.insert(
+            BytesMut::from("large"),
+            Value::Bytes(Cow::Owned(vec![0; 10_010])),
+        );
+
+        let (Value::Positive(small), Value::Positive(large)) =
+            (usage(&db, "small").await, usage(&db, "large").await)
+        else {
+            panic!("expected sizes");
+        };
+        assert_eq!(large - small, 10_000);
+        assert_eq!(
+            usage(&db, "missing").await,
+            Value::Error(Cow::Borrowed(EMPTY))
+        );
+    }
+
+    #[tokio::test]
+    async fn shard_stats_sum_to_total() {
+        let db = Arc::new(Db::default());
+        for i in 0..1000u64 {
+            db.map.insert(
+                BytesMut::from(format!("key:{i}").as_str()),
+                Value::Positive(i),
+            );
+        }
+        let reply = execute(&db, CommandEntry::ShardStats(ShardStats)).await;
+        let [Value::Array(shards)] = &reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        let (mut keys, mut bytes) = (0, 0);
+        for shard in shards {
+            let Value::Array(stats) = shard else {
+                panic!("unexpected shard {shard:?}");
+            };
+            let [Value::Positive(shard_keys), Value::Positive(shard_bytes)] = &stats[..] else {
+                panic!("unexpected shard {shard:?}");
+            };
+            keys += shard_keys;
+            bytes += shard_bytes;
+        }
+        assert!(shards.len() > 1);
+        assert_eq!(keys, 1000);
+        let mut total = 0;
+        db.map
+            .for_each(|key, value| total += (key.len() + value.deep_size_of()) as u64);
+        assert_eq!(bytes, total);
+    }
+
+    #[tokio::test]
+    async fn size_histogram() {
+        let db = Arc::new(Db::default());
+        // Encoded sizes 1, 2, 4, 11, 202 and 202.
+        let values = [
+            Value::Positive(1),
+            Value::Positive(100),
+            Value::Bytes(Cow::Owned(vec![0; 3])),
+            Value::Bytes(Cow::Owned(vec![0; 10])),
+            Value::Array(vec![Value::Positive(1); 200]),
+            Value::Array(vec![Value::Positive(2); 200]),
+        ];
+        for (i, value) in values.into_iter().enumerate() {
+            db.map
+                .insert(BytesMut::from(format!("key:{i}").as_str()), value);
+        }
+        let reply = execute(&db, CommandEntry::SizeHistogram(SizeHistogram)).await;
+        let expected = [(1, 1), (2, 1), (4, 1), (16, 1), (256, 2)]
+            .into_iter()
+            .map(|(size, count)| (Value::Positive(size).encode(), Value::Positive(count)))
+            .collect();
+        assert_eq!(reply, vec![Value::Map(expected)]);
+    }
+
+    #[tokio::test]
+    async fn fragmentation() {
+        let db = Arc::new(Db::default());
+        for i in 0..100 {
+            db.map.insert(
+                BytesMut::from(format!("key:{i}").as_str()),
+                Value::Bytes(Cow::Owned(vec![0; 1000])),
+            );
+        }
+        let reply = execute(&db, CommandEntry::Fragmentation(Fragmentation)).await;
+        let [Value::Array(stats)] = &reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        let [Value::Positive(used), resident, ratio] = &stats[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        assert!((100_000..200_000).contains(used), "{used}");
+        match (resident, ratio) {
+            (Value::Positive(resident), Value::String(ratio)) => {
+                assert!(resident > used, "{resident} <= {used}");
+                let ratio: f64 = ratio.parse().unwrap();
+                assert!((ratio - *resident as f64 / *used as f64).abs() < 0.01);
+            }
+            (resident, ratio) => {
+                assert!(resident.is_nil() && ratio.is_nil(), "{reply:?}");
+            }
+        }
+    }
+}
@@ -1,24 +1,69 @@
+pub mod aggregate;
+pub mod apply;
+pub mod client;
+pub mod compare;
+pub mod config;
+pub mod debug;
 pub mod decr;
+pub mod del;
+pub mod dump;
 pub mod entry;
+pub mod errors;
+pub mod fingerprint;
 pub mod get;
+pub mod hash;
+pub mod hello;
 pub mod incr;
+pub mod keys;
+pub mod latency;
+pub mod len;
+pub mod list;
+pub mod memory;
+pub mod mset;
 pub mod ping;
+pub mod ratelimit;
+pub mod recent;
+pub mod retry;
+pub mod save;
+pub mod scan;
 pub mod set;
+#[cfg(feature = "signing")]
+pub mod sign;
+pub mod slowlog;
+pub mod stream;
+pub mod streaming;
+pub mod swap;
+pub mod time;
+pub mod upload;
+pub mod validate;
+pub mod wait;
 
 use std::sync::Arc;
 
-use bytes::BytesMut;
-use sharded::Map;
 use tokio::io::{AsyncRead, AsyncWrite};
 
-use crate::{codec::Connection, error::Result, protocol::Value};
+use crate::{
+    codec::Connection,
+    db::{storage::Storage, Db},
+    error::{ProtocolError, Result},
+    protocol::Value,
+};
+
+/// Integer argument of either sign which fits into an `i64`.
+pub(crate) fn decode_integer(value: &Value<'_>) -> Result<i64> {
+    match value {
+        Value::Positive(n) => i64::try_from(*n).map_err(|_| ProtocolError::Command),
+        Value::Negative(n) => Ok(*n),
+        _ => Err(ProtocolError::Command),
+    }
+}
 
 pub trait Command {
     type ExecutionResult;
     fn execute<W, R>(
         &self,
         connection: &mut Connection<R, W>,
-        db: Arc<Map<BytesMut, Value<'static>>>,
+        db: Arc<Db>,
     ) -> impl std::future::Future<Output = Self::ExecutionResult>
     where
         R: AsyncRead + Unpin,
@@ -29,3 +74,22 @@ pub trait Command {
         V: AsRef<[Value<'c>]>;
     fn encode(&self) -> Value<'_>;
 }
+
+/// Command which only reads and writes whole values, so it can run on any [`Storage`] instead
+/// of only on [`Db::map`], see [`crate::db::storage`].
+///
+/// Implementors implement [`Command::execute`] by calling [`StorageCommand::execute_on`] with
+/// the map. `db` is still used for everything besides the keyspace, e.g. configuration and
+/// recently modified keys.
+pub trait StorageCommand {
+    fn execute_on<S, R, W>(
+        &self,
+        connection: &mut Connection<R, W>,
+        db: &Db,
+        storage: &S,
+    ) -> impl std::future::Future<Output = Result<()>>
+    where
+        S: Storage,
+        R: AsyncRead + Unpin,
+        W: Unpin + AsyncWrite;
+}
@@ -0,0 +1,134 @@
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{command::Command, error::ProtocolError, protocol::Value};
+
+/// Sets every key to its value, but only if none of the keys exist yet.
+///
+/// The shards of all keys are locked before any of them is checked, so no other client can create
+/// one of the keys between the check and the write. Replies with 1 if the pairs were set, 0 if
+/// nothing changed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MSetNx {
+    pub pairs: Vec<(BytesMut, Value<'static>)>,
+}
+
+impl Command for MSetNx {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let applied = {
+            let mut shards = db.map.write_keys(self.pairs.iter().map(|(key, _)| key));
+            let exists = self
+                .pairs
+                .iter()
+                .any(|(key, _)| shards.shard(key).contains_key(key));
+            if !exists {
+                for (key, value) in &self.pairs {
+                    shards
+                        .shard(key)
+                        .insert(key.clone(), db.stored(value.clone()));
+                }
+            }
+            !exists
+        };
+        if applied {
+            for (key, _) in &self.pairs {
+                db.modified(key);
+            }
+        }
+        let _ = connection
+            .write_frame(Value::Positive(applied as u64))
+            .await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let req = req.as_ref();
+        if req.is_empty() || req.len() % 2 != 0 {
+            return Err(ProtocolError::Command);
+        }
+        let pairs = req
+            .chunks_exact(2)
+            .map(|pair| match pair {
+                [Value::Bytes(key), value] => {
+                    Ok((BytesMut::from(key.as_bytes()), value.clone().to_owned()))
+                }
+                _ => Err(ProtocolError::Command),
+            })
+            .collect::<crate::error::Result<_>>()?;
+        Ok(Self { pairs })
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let mut array = vec![Value::String(Cow::Borrowed("MSETNX"))];
+        for (key, value) in &self.pairs {
+            array.push(Value::Bytes(Cow::Borrowed(key.as_bytes())));
+            array.push(value.clone());
+        }
+        Value::Array(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{entry::CommandEntry, mset::MSetNx},
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn msetnx(keys: &[&str]) -> CommandEntry {
+        CommandEntry::MSetNx(MSetNx {
+            pairs: keys
+                .iter()
+                .enumerate()
+                .map(|(i, key)| (BytesMut::from(*key), Value::Positive(i as u64 + 10)))
+                .collect(),
+        })
+    }
+
+    #[tokio::test]
+    async fn all_fresh() {
+        let db = Arc::new(Db::default());
+        assert_eq!(
+            execute(&db, msetnx(&["a", "b", "c"])).await,
+            vec![Value::Positive(1)]
+        );
+        assert_eq!(db.map.get_owned(b"a".as_slice()), Some(Value::Positive(10)));
+        assert_eq!(db.map.get_owned(b"c".as_slice()), Some(Value::Positive(12)));
+        assert_eq!(db.map.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn partial_collision() {
+        let db = Arc::new(Db::default());
+        db.map.insert(BytesMut::from("b"), Value::Positive(1));
+        assert_eq!(
+            execute(&db, msetnx(&["a", "b", "c"])).await,
+            vec![Value::Positive(0)]
+        );
+        assert_eq!(db.map.get_owned(b"b".as_slice()), Some(Value::Positive(1)));
+        assert!(!db.map.contains(b"a".as_slice()));
+        assert!(!db.map.contains(b"c".as_slice()));
+    }
+}
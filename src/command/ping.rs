@@ -3,11 +3,9 @@ use crate::error::{ProtocolError, Result};
 use std::borrow::Cow;
 use std::sync::Arc;
 
-use bytes::BytesMut;
-use sharded::Map;
 use tokio::io::{AsyncRead, AsyncWrite};
 
-use crate::{codec::Connection, protocol::Value};
+use crate::{codec::Connection, db::Db, protocol::Value};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Ping;
@@ -17,7 +15,7 @@ impl Command for Ping {
     async fn execute<W, R>(
         &self,
         connection: &mut Connection<R, W>,
-        _: Arc<Map<BytesMut, Value<'static>>>,
+        _: Arc<Db>,
     ) -> Self::ExecutionResult
     where
         W: AsyncWrite + Unpin,
@@ -0,0 +1,197 @@
+use std::{
+    borrow::Cow,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    db::Db,
+    error::{CommandError, ProtocolError},
+    protocol::Value,
+};
+
+/// Takes `tokens` from the token bucket at `key` if it holds enough of them.
+///
+/// The bucket holds up to `capacity` tokens, starts full and gains `refill_per_sec` tokens per
+/// second. It is stored as `[tokens, last_refill]`, the latter in Unix milliseconds, so it is an
+/// ordinary value which can be inspected or deleted to reset it.
+///
+/// Replies with `[1, 0]` if the tokens were taken, or `[0, retry_after]` with the seconds until
+/// enough tokens are available otherwise, leaving the bucket unchanged.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RateLimit {
+    pub key: BytesMut,
+    pub capacity: u64,
+    pub refill_per_sec: u64,
+    pub tokens: u64,
+}
+
+impl RateLimit {
+    /// Consumes from the bucket as of `now`, in Unix milliseconds. `Ok(None)` if the tokens were
+    /// taken, otherwise the seconds to wait before retrying.
+    fn consume(&self, db: &Db, now: u64) -> Result<Option<u64>, CommandError> {
+        let (key, mut shard) = db.map.write(self.key.clone());
+        let (tokens, last_refill) = match shard.get(&key) {
+            None => (self.capacity, now),
+            Some(Value::Array(bucket)) => match bucket[..] {
+                [Value::Positive(tokens), Value::Positive(last_refill)] => (tokens, last_refill),
+                _ => return Err(CommandError::WrongType),
+            },
+            Some(_) => return Err(CommandError::WrongType),
+        };
+
+        let elapsed = now.saturating_sub(last_refill);
+        let refilled = (elapsed as u128 * self.refill_per_sec as u128 / 1000) as u64;
+        let mut tokens = tokens.saturating_add(refilled).min(self.capacity);
+        let last_refill = if tokens == self.capacity {
+            now
+        } else {
+            // Only the time which made up whole tokens is used, the rest counts towards the next.
+            last_refill + (refilled as u128 * 1000).div_ceil(self.refill_per_sec as u128) as u64
+        };
+
+        if tokens < self.tokens {
+            let missing = (self.tokens - tokens) as u128;
+            let wait = (missing * 1000).div_ceil(self.refill_per_sec as u128) as u64;
+            let wait = (last_refill + wait).saturating_sub(now);
+            return Ok(Some(wait.div_ceil(1000)));
+        }
+        tokens -= self.tokens;
+        shard.insert(
+            key,
+            Value::Array(vec![Value::Positive(tokens), Value::Positive(last_refill)]),
+        );
+        Ok(None)
+    }
+}
+
+impl Command for RateLimit {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_millis() as u64)
+            .unwrap_or_default();
+        let reply = match self.consume(&db, now) {
+            Ok(None) => {
+                db.modified(&self.key);
+                Value::Array(vec![Value::Positive(1), Value::Positive(0)])
+            }
+            Ok(Some(retry_after)) => {
+                Value::Array(vec![Value::Positive(0), Value::Positive(retry_after)])
+            }
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    /// A bucket which never refills, or a request for more tokens than it can ever hold, is
+    /// rejected, since it could never be allowed once the bucket runs dry.
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), Value::Positive(capacity), Value::Positive(refill_per_sec), Value::Positive(tokens)]
+                if *refill_per_sec != 0 && tokens <= capacity =>
+            {
+                Ok(Self {
+                    key: BytesMut::from(key.as_bytes()),
+                    capacity: *capacity,
+                    refill_per_sec: *refill_per_sec,
+                    tokens: *tokens,
+                })
+            }
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("RATELIMIT")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::Positive(self.capacity),
+            Value::Positive(self.refill_per_sec),
+            Value::Positive(self.tokens),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{entry::CommandEntry, ratelimit::RateLimit},
+        db::Db,
+        error::CommandError,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn rate_limit(tokens: u64) -> RateLimit {
+        RateLimit {
+            key: BytesMut::from("api"),
+            capacity: 3,
+            refill_per_sec: 2,
+            tokens,
+        }
+    }
+
+    #[test]
+    fn exhaust_and_refill() {
+        let db = Db::default();
+        let start = 1_700_000_000_000;
+        for _ in 0..3 {
+            assert_eq!(rate_limit(1).consume(&db, start), Ok(None));
+        }
+        assert_eq!(rate_limit(1).consume(&db, start), Ok(Some(1)));
+
+        // A token every 500ms.
+        assert_eq!(rate_limit(1).consume(&db, start + 499), Ok(Some(1)));
+        assert_eq!(rate_limit(1).consume(&db, start + 500), Ok(None));
+        assert_eq!(rate_limit(2).consume(&db, start + 1200), Ok(Some(1)));
+        assert_eq!(rate_limit(2).consume(&db, start + 1500), Ok(None));
+
+        // Never more than the capacity.
+        assert_eq!(rate_limit(3).consume(&db, start + 60_000), Ok(None));
+        assert_eq!(rate_limit(1).consume(&db, start + 60_000), Ok(Some(1)));
+    }
+
+    #[tokio::test]
+    async fn replies() {
+        let db = Arc::new(Db::default());
+        let command = CommandEntry::RateLimit(rate_limit(3));
+        assert_eq!(
+            execute(&db, command.clone()).await,
+            vec![Value::Array(vec![Value::Positive(1), Value::Positive(0)])]
+        );
+        assert_eq!(
+            execute(&db, command).await,
+            vec![Value::Array(vec![Value::Positive(0), Value::Positive(2)])]
+        );
+
+        db.map
+            .insert(BytesMut::from("api"), Value::String(Cow::Borrowed("text")));
+        assert_eq!(
+            execute(&db, CommandEntry::RateLimit(rate_limit(1))).await,
+            vec![Value::from(CommandError::WrongType)]
+        );
+    }
+}
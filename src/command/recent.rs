@@ -0,0 +1,97 @@
+use std::borrow::Cow;
+
+use nom::AsBytes;
+
+use crate::{command::Command, error::ProtocolError, protocol::Value};
+
+/// Replies with up to `count` most recently modified keys, newest first.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecentChanges {
+    pub count: u64,
+}
+
+impl Command for RecentChanges {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let keys = db
+            .recent
+            .latest(self.count as usize)
+            .into_iter()
+            .map(|key| Value::Bytes(Cow::Owned(key.as_bytes().to_vec())))
+            .collect();
+        let _ = connection.write_frame(Value::Array(keys)).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Positive(count)] => Ok(Self { count: *count }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("RECENTCHANGES")),
+            Value::Positive(self.count),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{entry::CommandEntry, incr::Incr, recent::RecentChanges, set::Set},
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    #[tokio::test]
+    async fn newest_first() {
+        let db = Arc::new(Db::default());
+        for key in ["a", "b", "c"] {
+            execute(
+                &db,
+                CommandEntry::Set(Set {
+                    key: BytesMut::from(key),
+                    value: Value::Positive(1),
+                }),
+            )
+            .await;
+        }
+        execute(
+            &db,
+            CommandEntry::Incr(Incr {
+                key: BytesMut::from("a"),
+            }),
+        )
+        .await;
+
+        let reply = execute(&db, CommandEntry::RecentChanges(RecentChanges { count: 2 })).await;
+        assert_eq!(
+            reply,
+            vec![Value::Array(vec![
+                Value::Bytes(Cow::Borrowed(b"a")),
+                Value::Bytes(Cow::Borrowed(b"c")),
+            ])]
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use crate::error::{ProtocolError, Result};
+
+/// Attempts made before a storage operation failing with [`ProtocolError::Retryable`] is given
+/// up on.
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry, doubled before every next one.
+pub const BACKOFF: Duration = Duration::from_millis(5);
+
+/// Runs the storage operation `attempt` again while it fails with [`ProtocolError::Retryable`],
+/// at most [`MAX_ATTEMPTS`] times in total, and returns the result of the last attempt.
+///
+/// Only the operation is retried, so commands call it before writing any reply and reply with
+/// [`crate::error::CommandError::Unavailable`] if it still fails.
+pub async fn retry<T>(mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut backoff = BACKOFF;
+    for _ in 1..MAX_ATTEMPTS {
+        match attempt() {
+            Err(ProtocolError::Retryable) => {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    attempt()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{get::Get, retry::MAX_ATTEMPTS, set::Set},
+        db::Db,
+        error::CommandError,
+        protocol::Value,
+        testing::{execute_on, MockStorage},
+    };
+
+    fn get() -> Get {
+        Get {
+            key: BytesMut::from("key"),
+        }
+    }
+
+    #[tokio::test]
+    async fn succeeds_after_failure() {
+        let db = Db::default();
+        let storage = MockStorage::default();
+        storage.failures.store(1, Ordering::Relaxed);
+        let set = Set {
+            key: BytesMut::from("key"),
+            value: Value::Positive(42),
+        };
+        assert_eq!(execute_on(&db, &storage, set).await, vec![]);
+
+        storage.failures.store(1, Ordering::Relaxed);
+        assert_eq!(
+            execute_on(&db, &storage, get()).await,
+            vec![Value::Positive(42)]
+        );
+        assert_eq!(storage.calls.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn gives_up() {
+        let db = Db::default();
+        let storage = MockStorage::default();
+        storage.failures.store(u32::MAX, Ordering::Relaxed);
+        assert_eq!(
+            execute_on(&db, &storage, get()).await,
+            vec![Value::from(CommandError::Unavailable)]
+        );
+        assert_eq!(storage.calls.load(Ordering::Relaxed), MAX_ATTEMPTS);
+    }
+}
@@ -0,0 +1,232 @@
+use std::{borrow::Cow, path::PathBuf};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    db::persist::file_name,
+    error::{CommandError, ProtocolError},
+    protocol::Value,
+};
+
+/// Writes a single key to the file `path` in the data directory of the server, see
+/// [`crate::db::Db::save_key`].
+///
+/// Cheaper than saving the whole keyspace when only one key has to be durable. Replies with 1 if
+/// the key was saved, 0 if it does not exist. `path` has to be a bare file name, see
+/// [`file_name`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct SaveKey {
+    pub key: BytesMut,
+    pub path: PathBuf,
+}
+
+impl Command for SaveKey {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match db.save_key(&self.key, &db.data_path(&self.path)).await {
+            Ok(saved) => Value::Positive(saved as u64),
+            Err(_) => db.error_reply(CommandError::Io),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), Value::String(path)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                path: file_name(path).ok_or(ProtocolError::Command)?,
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("SAVEKEY")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::String(self.path.to_string_lossy()),
+        ])
+    }
+}
+
+/// Writes every key matching the glob `pattern` to the file `path` in the data directory of the
+/// server, see [`crate::db::Db::save_matching`], and replies with the number of keys written.
+///
+/// Meant for partial backups, e.g. of all `user:*` keys. The file is written even if no key
+/// matches. Like for [`SaveKey`], `path` has to be a bare file name.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DumpPattern {
+    pub pattern: BytesMut,
+    pub path: PathBuf,
+}
+
+impl Command for DumpPattern {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let path = db.data_path(&self.path);
+        let reply = match db.save_matching(&self.pattern, &path).await {
+            Ok(saved) => Value::Positive(saved),
+            Err(_) => db.error_reply(CommandError::Io),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(pattern), Value::String(path)] => Ok(Self {
+                pattern: BytesMut::from(pattern.as_bytes()),
+                path: file_name(path).ok_or(ProtocolError::Command)?,
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("DUMPPATTERN")),
+            Value::Bytes(Cow::Borrowed(self.pattern.as_bytes())),
+            Value::String(self.path.to_string_lossy()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, path::PathBuf, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{
+            entry::CommandEntry,
+            save::{DumpPattern, SaveKey},
+            Command,
+        },
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    #[tokio::test]
+    async fn save_and_reload() {
+        let file = PathBuf::from(format!("kvs-savekey-{}", std::process::id()));
+        let path = std::env::temp_dir().join(&file);
+        let key = BytesMut::from("config");
+        let value = Value::Array(vec![
+            Value::String(Cow::Borrowed("durable")),
+            Value::Positive(42),
+        ]);
+        let db = Arc::new(Db {
+            data_dir: std::env::temp_dir(),
+            ..Default::default()
+        });
+        db.map.insert(key.clone(), value.clone());
+        db.map.insert(BytesMut::from("other"), Value::Positive(1));
+        let save = |key: &BytesMut| {
+            CommandEntry::SaveKey(SaveKey {
+                key: key.clone(),
+                path: file.clone(),
+            })
+        };
+
+        assert_eq!(
+            execute(&db, save(&BytesMut::from("missing"))).await,
+            vec![Value::Positive(0)]
+        );
+        assert!(!path.exists());
+        assert_eq!(execute(&db, save(&key)).await, vec![Value::Positive(1)]);
+
+        let fresh = Db::default();
+        fresh.load_key(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(fresh.map.get_owned(&key), Some(value));
+        assert_eq!(fresh.map.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dump_pattern_and_reload() {
+        let file = PathBuf::from(format!("kvs-dumppattern-{}", std::process::id()));
+        let path = std::env::temp_dir().join(&file);
+        let db = Arc::new(Db {
+            data_dir: std::env::temp_dir(),
+            ..Default::default()
+        });
+        for i in 0..50u64 {
+            let key = format!("user:{i}");
+            db.map
+                .insert(BytesMut::from(key.as_str()), Value::Positive(i));
+            let key = format!("session:{i}");
+            db.map
+                .insert(BytesMut::from(key.as_str()), Value::Positive(i));
+        }
+        let dump = CommandEntry::DumpPattern(DumpPattern {
+            pattern: BytesMut::from("user:*"),
+            path: file,
+        });
+        assert_eq!(execute(&db, dump).await, vec![Value::Positive(50)]);
+
+        let fresh = Db::default();
+        fresh.load_keys(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(fresh.map.len(), 50);
+        for i in 0..50u64 {
+            let key = format!("user:{i}");
+            assert_eq!(
+                fresh.map.get_owned(key.as_bytes()),
+                Some(Value::Positive(i))
+            );
+        }
+    }
+
+    #[test]
+    fn only_file_names() {
+        let save = |path: &'static str| {
+            SaveKey::decode([
+                Value::Bytes(Cow::Borrowed(b"key")),
+                Value::String(Cow::Borrowed(path)),
+            ])
+        };
+        assert_eq!(save("key.kvs").unwrap().path, PathBuf::from("key.kvs"));
+        for path in [
+            "",
+            ".",
+            "..",
+            "../key.kvs",
+            "/etc/passwd",
+            "dir/key.kvs",
+            "key.kvs/",
+        ] {
+            assert!(save(path).is_err(), "{path}");
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::borrow::Cow;
+
+use nom::AsBytes;
+
+use crate::{command::Command, error::ProtocolError, protocol::Value};
+
+pub const DEFAULT_COUNT: u64 = 10;
+
+/// Incrementally iterates over the keyspace, see [`crate::db::map::Map::scan`].
+///
+/// Replies with `[next_cursor, [keys]]`, the scan is complete once `next_cursor` is 0.
+///
+/// With `TYPE`, only keys whose value has that [`Value::type_name`] are returned. Keys are
+/// filtered while walking the shards, so a call may return fewer than `count` keys, or none,
+/// before the scan is complete.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Scan {
+    pub cursor: u64,
+    pub count: u64,
+    pub kind: Option<String>,
+}
+
+impl Command for Scan {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let (cursor, keys) = db.map.scan(self.cursor, self.count as usize, |_, value| {
+            self.kind
+                .as_deref()
+                .is_none_or(|kind| value.type_name() == kind)
+        });
+        let keys = keys
+            .into_iter()
+            .map(|key| Value::Bytes(Cow::Owned(key.as_bytes().to_vec())))
+            .collect();
+        let _ = connection
+            .write_frame(Value::Array(vec![
+                Value::Positive(cursor),
+                Value::Array(keys),
+            ]))
+            .await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let [Value::Positive(cursor), options @ ..] = req.as_ref() else {
+            return Err(ProtocolError::Command);
+        };
+        let mut scan = Self {
+            cursor: *cursor,
+            count: DEFAULT_COUNT,
+            kind: None,
+        };
+        for option in options.chunks(2) {
+            match option {
+                [Value::String(option), Value::Positive(count)] if option == "COUNT" => {
+                    scan.count = *count;
+                }
+                [Value::String(option), Value::String(kind)] if option == "TYPE" => {
+                    scan.kind = Some(kind.to_string());
+                }
+                _ => return Err(ProtocolError::Command),
+            }
+        }
+        Ok(scan)
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let mut array = vec![
+            Value::String(Cow::Borrowed("SCAN")),
+            Value::Positive(self.cursor),
+            Value::String(Cow::Borrowed("COUNT")),
+            Value::Positive(self.count),
+        ];
+        if let Some(kind) = &self.kind {
+            array.push(Value::String(Cow::Borrowed("TYPE")));
+            array.push(Value::String(Cow::Borrowed(kind)));
+        }
+        Value::Array(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashSet, sync::Arc};
+
+    use bytes::BytesMut;
+    use nom::AsBytes;
+
+    use crate::{
+        command::{entry::CommandEntry, scan::Scan, set::Set},
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    #[tokio::test]
+    async fn scan_while_inserting() {
+        let db = Arc::new(Db::default());
+        for i in 0..100 {
+            db.map.insert(
+                BytesMut::from(format!("key:{i}").as_str()),
+                Value::Positive(i),
+            );
+        }
+
+        let mut cursor = 0;
+        let mut visited = HashSet::new();
+        for i in 0.. {
+            execute(
+                &db,
+                CommandEntry::Set(Set {
+                    key: BytesMut::from(format!("new:{i}").as_str()),
+                    value: Value::Positive(i),
+                }),
+            )
+            .await;
+            let reply = execute(
+                &db,
+                CommandEntry::Scan(Scan {
+                    cursor,
+                    count: 5,
+                    kind: None,
+                }),
+            )
+            .await;
+            let [Value::Array(reply)] = &reply[..] else {
+                panic!("unexpected reply {reply:?}");
+            };
+            let [Value::Positive(next), Value::Array(keys)] = &reply[..] else {
+                panic!("unexpected reply {reply:?}");
+            };
+            visited.extend(keys.iter().map(|key| match key {
+                Value::Bytes(key) => BytesMut::from(key.as_bytes()),
+                _ => panic!("unexpected key {key:?}"),
+            }));
+            if *next == 0 {
+                break;
+            }
+            cursor = *next;
+        }
+
+        assert!((0..100).all(|i| visited.contains(format!("key:{i}").as_bytes())));
+    }
+
+    #[tokio::test]
+    async fn scan_type() {
+        let db = Arc::new(Db::default());
+        for i in 0..20 {
+            let value = match i % 3 {
+                0 => Value::Positive(i),
+                1 => Value::String(Cow::Borrowed("text")),
+                _ => Value::Array(vec![Value::Positive(i)]),
+            };
+            db.map
+                .insert(BytesMut::from(format!("key:{i}").as_str()), value);
+        }
+
+        let mut cursor = 0;
+        let mut visited = HashSet::new();
+        loop {
+            let scan = Scan {
+                cursor,
+                count: 4,
+                kind: Some("array".to_string()),
+            };
+            let reply = execute(&db, CommandEntry::Scan(scan)).await;
+            let [Value::Array(reply)] = &reply[..] else {
+                panic!("unexpected reply {reply:?}");
+            };
+            let [Value::Positive(next), Value::Array(keys)] = &reply[..] else {
+                panic!("unexpected reply {reply:?}");
+            };
+            visited.extend(keys.iter().map(|key| match key {
+                Value::Bytes(key) => String::from_utf8(key.to_vec()).unwrap(),
+                _ => panic!("unexpected key {key:?}"),
+            }));
+            if *next == 0 {
+                break;
+            }
+            cursor = *next;
+        }
+
+        let arrays = (0..20)
+            .filter(|i| i % 3 == 2)
+            .map(|i| format!("key:{i}"))
+            .collect::<HashSet<_>>();
+        assert_eq!(visited, arrays);
+    }
+}
@@ -4,8 +4,9 @@ use bytes::BytesMut;
 use nom::AsBytes;
 
 use crate::{
-    command::{get::EMPTY, Command},
-    error::ProtocolError,
+    command::{get::EMPTY, retry::retry, Command, StorageCommand},
+    db::{storage::Storage, Db},
+    error::{CommandError, ProtocolError},
     protocol::Value,
 };
 
@@ -15,22 +16,45 @@ pub struct Set {
     pub value: Value<'static>,
 }
 
+impl StorageCommand for Set {
+    async fn execute_on<S, R, W>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: &Db,
+        storage: &S,
+    ) -> crate::error::Result<()>
+    where
+        S: Storage,
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let value = db.stored(self.value.clone());
+        match retry(|| storage.set(self.key.clone(), value.clone())).await {
+            Ok(_) => db.modified(&self.key),
+            Err(ProtocolError::Retryable) => {
+                let _ = connection
+                    .write_frame(db.error_reply(CommandError::Unavailable))
+                    .await;
+            }
+            Err(error) => return Err(error),
+        }
+        Ok(())
+    }
+}
+
 impl Command for Set {
     type ExecutionResult = crate::error::Result<()>;
 
-    #[allow(clippy::await_holding_lock)]
     async fn execute<W, R>(
         &self,
-        _: &mut crate::codec::Connection<R, W>,
-        db: std::sync::Arc<sharded::Map<BytesMut, Value<'static>>>,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
     ) -> Self::ExecutionResult
     where
         R: tokio::io::AsyncRead + Unpin,
         W: Unpin + tokio::io::AsyncWrite,
     {
-        let (key, mut shard) = db.write(self.key.clone());
-        let _ = shard.insert(key, self.value.clone());
-        Ok(())
+        self.execute_on(connection, &db, &db.map).await
     }
 
     fn decode<'c, V>(req: V) -> crate::error::Result<Self>
@@ -39,6 +63,8 @@ impl Command for Set {
         V: AsRef<[Value<'c>]>,
     {
         match req.as_ref() {
+            // The key ends up stored in the map, which outlives the frame, so unlike GET the
+            // copy can not be avoided here.
             [Value::Bytes(key), value] => Ok(Self {
                 key: BytesMut::from(key.as_bytes()),
                 value: value.clone().to_owned(),
@@ -65,28 +91,23 @@ pub struct GetSet {
 impl Command for GetSet {
     type ExecutionResult = crate::error::Result<()>;
 
-    #[allow(clippy::await_holding_lock)]
     async fn execute<W, R>(
         &self,
         connection: &mut crate::codec::Connection<R, W>,
-        db: std::sync::Arc<sharded::Map<BytesMut, Value<'static>>>,
+        db: std::sync::Arc<crate::db::Db>,
     ) -> Self::ExecutionResult
     where
         R: tokio::io::AsyncRead + Unpin,
         W: Unpin + tokio::io::AsyncWrite,
     {
-        let (key, mut shard) = db.write(self.key.clone());
-        let prev = shard.insert(key, self.value.clone());
-        match prev {
-            Some(value) => {
-                let _ = connection.write_frame(value).await;
-            }
-            None => {
-                let _ = connection
-                    .write_frame(Value::Error(Cow::Borrowed(EMPTY)))
-                    .await;
-            }
+        let prev = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            shard.insert(key, db.stored(self.value.clone()))
         };
+        db.modified(&self.key);
+        let _ = connection
+            .write_frame(prev.unwrap_or(Value::Error(Cow::Borrowed(EMPTY))))
+            .await;
         Ok(())
     }
 
@@ -112,3 +133,210 @@ impl Command for GetSet {
         ])
     }
 }
+
+/// Sets `key` like [`GetSet`] and replies with the previous value, at most once per `token`.
+///
+/// Replaying a token replies with the reply of its first use and leaves the key as is, see
+/// [`crate::db::tokens`]. Only the most recent tokens are remembered, a token which was dropped
+/// from the cache is applied again.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SetIdempotent {
+    pub key: BytesMut,
+    pub value: Value<'static>,
+    pub token: BytesMut,
+}
+
+impl Command for SetIdempotent {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = db.tokens.apply_once(&self.token, || {
+            let prev = db
+                .map
+                .insert(self.key.clone(), db.stored(self.value.clone()));
+            db.modified(&self.key);
+            prev.unwrap_or(Value::Error(Cow::Borrowed(EMPTY)))
+        });
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), value, Value::Bytes(token)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                value: value.clone().to_owned(),
+                token: BytesMut::from(token.as_bytes()),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("SETIDEMPOTENT")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            self.value.clone(),
+            Value::Bytes(Cow::Borrowed(self.token.as_bytes())),
+        ])
+    }
+}
+
+/// Replies with the value of `key`, first setting it to `default` if it does not exist, so
+/// initializing a key on first use needs a single round trip and no race with other clients.
+///
+/// The reply is `[value, created]`, with `created` 1 if `default` was set and 0 if the key
+/// already existed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GetOrSet {
+    pub key: BytesMut,
+    pub default: Value<'static>,
+}
+
+impl Command for GetOrSet {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let existing = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            let existing = shard.get(&key).cloned();
+            if existing.is_none() {
+                shard.insert(key, db.stored(self.default.clone()));
+            }
+            existing
+        };
+        let reply = match existing {
+            Some(value) => vec![value, Value::Positive(0)],
+            None => {
+                db.modified(&self.key);
+                vec![self.default.clone(), Value::Positive(1)]
+            }
+        };
+        let _ = connection.write_frame(Value::Array(reply)).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), default] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                default: default.clone().to_owned(),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("GETORSET")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            self.default.clone(),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{
+            entry::CommandEntry,
+            get::EMPTY,
+            set::{GetOrSet, Set, SetIdempotent},
+        },
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn set_idempotent(value: u64, token: &str) -> CommandEntry {
+        CommandEntry::SetIdempotent(SetIdempotent {
+            key: BytesMut::from("key"),
+            value: Value::Positive(value),
+            token: BytesMut::from(token),
+        })
+    }
+
+    #[tokio::test]
+    async fn replayed_token_is_a_no_op() {
+        let db = Arc::new(Db::default());
+        let nil = Value::Error(Cow::Borrowed(EMPTY));
+        assert_eq!(
+            execute(&db, set_idempotent(1, "token")).await,
+            vec![nil.clone()]
+        );
+        execute(
+            &db,
+            CommandEntry::Set(Set {
+                key: BytesMut::from("key"),
+                value: Value::Positive(2),
+            }),
+        )
+        .await;
+
+        assert_eq!(execute(&db, set_idempotent(3, "token")).await, vec![nil]);
+        assert_eq!(
+            db.map.get_owned(b"key".as_slice()),
+            Some(Value::Positive(2))
+        );
+
+        assert_eq!(
+            execute(&db, set_idempotent(3, "other")).await,
+            vec![Value::Positive(2)]
+        );
+        assert_eq!(
+            db.map.get_owned(b"key".as_slice()),
+            Some(Value::Positive(3))
+        );
+    }
+
+    #[tokio::test]
+    async fn get_or_set() {
+        let db = Arc::new(Db::default());
+        let get_or_set = |default| {
+            CommandEntry::GetOrSet(GetOrSet {
+                key: BytesMut::from("key"),
+                default: Value::Positive(default),
+            })
+        };
+        assert_eq!(
+            execute(&db, get_or_set(1)).await,
+            vec![Value::Array(vec![Value::Positive(1), Value::Positive(1)])]
+        );
+        assert_eq!(
+            execute(&db, get_or_set(2)).await,
+            vec![Value::Array(vec![Value::Positive(1), Value::Positive(0)])]
+        );
+        assert_eq!(
+            db.map.get_owned(b"key".as_slice()),
+            Some(Value::Positive(1))
+        );
+    }
+}
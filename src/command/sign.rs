@@ -0,0 +1,239 @@
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+use hmac::{Hmac, Mac};
+use nom::AsBytes;
+use sha2::Sha256;
+
+use crate::{
+    command::Command,
+    db::Db,
+    error::ProtocolError,
+    protocol::{EncodeOptions, Value, EMPTY},
+};
+
+/// Bytes of a signature: a whole HMAC-SHA256.
+pub const SIGNATURE_LEN: usize = 32;
+
+/// HMAC of the value at `key` encoded canonically, so equal maps are signed alike whatever order
+/// their entries are in, `None` if the key does not exist.
+fn mac(db: &Db, key: &[u8], secret: &[u8]) -> Option<Hmac<Sha256>> {
+    let value = db.map.get_owned(key)?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&value.encode_with(&EncodeOptions { canonical: true }));
+    Some(mac)
+}
+
+/// Replies with the signature of the value at `key` under `secret`, see [`SIGNATURE_LEN`], or
+/// nil if the key does not exist.
+///
+/// The signature covers the encoded value, so values of different types never share one.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Sign {
+    pub key: BytesMut,
+    pub secret: Vec<u8>,
+}
+
+impl Command for Sign {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match mac(&db, &self.key, &self.secret) {
+            Some(mac) => Value::Bytes(Cow::Owned(mac.finalize().into_bytes().to_vec())),
+            None => Value::Error(Cow::Borrowed(EMPTY)),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), Value::Bytes(secret)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                secret: secret.to_vec(),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("SIGN")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::Bytes(Cow::Borrowed(&self.secret)),
+        ])
+    }
+}
+
+/// Replies with 1 if `signature` is the signature [`Sign`] gives the value at `key` under
+/// `secret`, 0 otherwise or if the key does not exist. Signatures are compared in constant time.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Verify {
+    pub key: BytesMut,
+    pub secret: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl Command for Verify {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let valid = mac(&db, &self.key, &self.secret)
+            .is_some_and(|mac| mac.verify_slice(&self.signature).is_ok());
+        let _ = connection.write_frame(Value::Positive(valid as u64)).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), Value::Bytes(secret), Value::Bytes(signature)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                secret: secret.to_vec(),
+                signature: signature.to_vec(),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("VERIFY")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::Bytes(Cow::Borrowed(&self.secret)),
+            Value::Bytes(Cow::Borrowed(&self.signature)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{
+            entry::CommandEntry,
+            hash::field_key,
+            sign::{Sign, Verify, SIGNATURE_LEN},
+        },
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    async fn sign(db: &Arc<Db>, secret: &[u8]) -> Vec<u8> {
+        let reply = execute(
+            db,
+            CommandEntry::Sign(Sign {
+                key: BytesMut::from("key"),
+                secret: secret.to_vec(),
+            }),
+        )
+        .await;
+        let [Value::Bytes(signature)] = &reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        signature.to_vec()
+    }
+
+    async fn verify(db: &Arc<Db>, secret: &[u8], signature: &[u8]) -> Vec<Value<'static>> {
+        execute(
+            db,
+            CommandEntry::Verify(Verify {
+                key: BytesMut::from("key"),
+                secret: secret.to_vec(),
+                signature: signature.to_vec(),
+            }),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sign_and_verify() {
+        let db = Arc::new(Db::default());
+        db.map
+            .insert(BytesMut::from("key"), Value::String(Cow::Borrowed("value")));
+        let signature = sign(&db, b"secret").await;
+        assert_eq!(signature.len(), SIGNATURE_LEN);
+        assert_eq!(sign(&db, b"secret").await, signature);
+        assert_eq!(
+            verify(&db, b"secret", &signature).await,
+            vec![Value::Positive(1)]
+        );
+        assert_eq!(
+            verify(&db, b"other", &signature).await,
+            vec![Value::Positive(0)]
+        );
+        assert_eq!(
+            verify(&db, b"secret", &signature[1..]).await,
+            vec![Value::Positive(0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn tampered_value() {
+        let db = Arc::new(Db::default());
+        db.map
+            .insert(BytesMut::from("key"), Value::String(Cow::Borrowed("value")));
+        let signature = sign(&db, b"secret").await;
+
+        // Same bytes under another type is a different value.
+        db.map
+            .insert(BytesMut::from("key"), Value::Bytes(Cow::Borrowed(b"value")));
+        assert_eq!(
+            verify(&db, b"secret", &signature).await,
+            vec![Value::Positive(0)]
+        );
+
+        db.map.remove(b"key".as_slice());
+        assert_eq!(
+            verify(&db, b"secret", &signature).await,
+            vec![Value::Positive(0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn map_in_any_order() {
+        let db = Arc::new(Db::default());
+        let fields = (0..32).map(|n| (field_key(n.to_string().as_bytes()), Value::Positive(n)));
+        db.map.insert(
+            BytesMut::from("key"),
+            Value::Map(fields.clone().collect::<HashMap<_, _>>()),
+        );
+        let signature = sign(&db, b"secret").await;
+
+        db.map.insert(
+            BytesMut::from("key"),
+            Value::Map(fields.rev().collect::<HashMap<_, _>>()),
+        );
+        assert_eq!(
+            verify(&db, b"secret", &signature).await,
+            vec![Value::Positive(1)]
+        );
+    }
+}
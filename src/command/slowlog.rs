@@ -0,0 +1,155 @@
+use std::borrow::Cow;
+
+use crate::{command::Command, error::ProtocolError, protocol::Value};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SlowLogSubcommand {
+    /// Replies with all recorded entries, newest first.
+    Get,
+    /// Drops all recorded entries.
+    Reset,
+    /// Replies with the number of recorded entries.
+    Len,
+}
+
+/// Exposes commands which exceeded the slowlog threshold, see [`crate::db::slowlog`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct SlowLog {
+    pub subcommand: SlowLogSubcommand,
+}
+
+impl Command for SlowLog {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match self.subcommand {
+            SlowLogSubcommand::Get => Value::Array(
+                db.slowlog
+                    .entries()
+                    .into_iter()
+                    .map(|entry| {
+                        Value::Array(vec![
+                            Value::Positive(entry.id),
+                            Value::Positive(entry.timestamp),
+                            Value::Positive(entry.duration.as_micros() as u64),
+                            Value::String(Cow::Borrowed(entry.command)),
+                        ])
+                    })
+                    .collect(),
+            ),
+            SlowLogSubcommand::Reset => {
+                db.slowlog.reset();
+                Value::String(Cow::Borrowed("OK"))
+            }
+            SlowLogSubcommand::Len => Value::Positive(db.slowlog.len() as u64),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        let [Value::String(subcommand)] = req.as_ref() else {
+            return Err(ProtocolError::Command);
+        };
+        let subcommand = match subcommand.as_ref() {
+            "GET" => SlowLogSubcommand::Get,
+            "RESET" => SlowLogSubcommand::Reset,
+            "LEN" => SlowLogSubcommand::Len,
+            _ => return Err(ProtocolError::Command),
+        };
+        Ok(Self { subcommand })
+    }
+
+    fn encode(&self) -> Value<'_> {
+        let subcommand = match self.subcommand {
+            SlowLogSubcommand::Get => "GET",
+            SlowLogSubcommand::Reset => "RESET",
+            SlowLogSubcommand::Len => "LEN",
+        };
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("SLOWLOG")),
+            Value::String(Cow::Borrowed(subcommand)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{
+        command::{
+            debug::{Debug, DebugSubcommand},
+            entry::CommandEntry,
+            ping::Ping,
+            slowlog::{SlowLog, SlowLogSubcommand},
+        },
+        db::{slowlog::SlowLogBuffer, Db},
+        protocol::Value,
+        testing::execute,
+    };
+
+    #[tokio::test]
+    async fn records_slow_command() {
+        let db = Arc::new(Db {
+            slowlog: SlowLogBuffer::new(Duration::from_millis(20), 8),
+            ..Default::default()
+        });
+        execute(&db, CommandEntry::Ping(Ping)).await;
+        execute(
+            &db,
+            CommandEntry::Debug(Debug {
+                subcommand: DebugSubcommand::Sleep(Duration::from_millis(30)),
+            }),
+        )
+        .await;
+
+        let len = SlowLog {
+            subcommand: SlowLogSubcommand::Len,
+        };
+        assert_eq!(
+            execute(&db, CommandEntry::SlowLog(len.clone())).await,
+            vec![Value::Positive(1)]
+        );
+
+        let reply = execute(
+            &db,
+            CommandEntry::SlowLog(SlowLog {
+                subcommand: SlowLogSubcommand::Get,
+            }),
+        )
+        .await;
+        let [Value::Array(entries)] = &reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        let [Value::Array(entry)] = &entries[..] else {
+            panic!("unexpected entries {entries:?}");
+        };
+        assert_eq!(entry[3], Value::String("DEBUG".into()));
+        assert!(matches!(entry[2], Value::Positive(micros) if micros >= 30_000));
+
+        execute(
+            &db,
+            CommandEntry::SlowLog(SlowLog {
+                subcommand: SlowLogSubcommand::Reset,
+            }),
+        )
+        .await;
+        assert_eq!(
+            execute(&db, CommandEntry::SlowLog(len)).await,
+            vec![Value::Positive(0)]
+        );
+    }
+}
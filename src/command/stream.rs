@@ -0,0 +1,244 @@
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    error::{CommandError, ProtocolError},
+    protocol::Value,
+};
+
+/// Id of a stream entry, `[id, entry]`, `None` if the value is not an entry.
+fn entry_id(entry: &Value<'_>) -> Option<u64> {
+    match entry {
+        Value::Array(entry) => match entry[..] {
+            [Value::Positive(id), _] => Some(id),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Appends `entry` to the stream at `key`, creating it if needed, and replies with the id it was
+/// assigned.
+///
+/// A stream is an array of `[id, entry]` pairs in the order they were added. Ids start at 1 and
+/// are one more than the last id, so they grow monotonically as long as the stream exists.
+#[derive(Debug, PartialEq, Clone)]
+pub struct XAdd {
+    pub key: BytesMut,
+    pub entry: Value<'static>,
+}
+
+impl Command for XAdd {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let result = {
+            let (key, mut shard) = db.map.write(self.key.clone());
+            match shard.entry(key).or_insert_with(|| Value::Array(Vec::new())) {
+                Value::Array(entries) => {
+                    let last = match entries.last() {
+                        Some(entry) => entry_id(entry).ok_or(CommandError::WrongType),
+                        None => Ok(0),
+                    };
+                    last.map(|last| {
+                        entries.push(Value::Array(vec![
+                            Value::Positive(last + 1),
+                            self.entry.clone(),
+                        ]));
+                        last + 1
+                    })
+                }
+                _ => Err(CommandError::WrongType),
+            }
+        };
+        let reply = match result {
+            Ok(id) => {
+                db.modified(&self.key);
+                Value::Positive(id)
+            }
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(_), Value::Error(_) | Value::Comment(_)] => Err(ProtocolError::Command),
+            [Value::Bytes(key), entry] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                entry: entry.clone().to_owned(),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("XADD")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            self.entry.clone(),
+        ])
+    }
+}
+
+/// Replies with the `[id, entry]` pairs of the stream at `key` whose id is between `start` and
+/// `end`, both inclusive, oldest first. A missing key is an empty stream.
+#[derive(Debug, PartialEq, Clone)]
+pub struct XRange {
+    pub key: BytesMut,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Command for XRange {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let result = {
+            let (key, shard) = db.map.read(&self.key);
+            match shard.get(key) {
+                None => Ok(Vec::new()),
+                Some(Value::Array(entries)) => entries
+                    .iter()
+                    .filter_map(|entry| match entry_id(entry) {
+                        Some(id) if (self.start..=self.end).contains(&id) => {
+                            Some(Ok(entry.clone()))
+                        }
+                        Some(_) => None,
+                        None => Some(Err(CommandError::WrongType)),
+                    })
+                    .collect(),
+                Some(_) => Err(CommandError::WrongType),
+            }
+        };
+        let reply = match result {
+            Ok(entries) => Value::Array(entries),
+            Err(error) => db.error_reply(error),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), Value::Positive(start), Value::Positive(end)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                start: *start,
+                end: *end,
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("XRANGE")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::Positive(self.start),
+            Value::Positive(self.end),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{
+            entry::CommandEntry,
+            stream::{XAdd, XRange},
+        },
+        db::Db,
+        error::CommandError,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn event(n: u64) -> Value<'static> {
+        Value::String(Cow::Owned(format!("event {n}")))
+    }
+
+    fn xrange(start: u64, end: u64) -> CommandEntry {
+        CommandEntry::XRange(XRange {
+            key: BytesMut::from("events"),
+            start,
+            end,
+        })
+    }
+
+    #[tokio::test]
+    async fn append_and_range() {
+        let db = Arc::new(Db::default());
+        for n in 1..=5 {
+            let xadd = CommandEntry::XAdd(XAdd {
+                key: BytesMut::from("events"),
+                entry: event(n),
+            });
+            assert_eq!(execute(&db, xadd).await, vec![Value::Positive(n)]);
+        }
+
+        let expected = (2..=4)
+            .map(|n| Value::Array(vec![Value::Positive(n), event(n)]))
+            .collect();
+        assert_eq!(
+            execute(&db, xrange(2, 4)).await,
+            vec![Value::Array(expected)]
+        );
+        assert_eq!(
+            execute(&db, xrange(6, 10)).await,
+            vec![Value::Array(vec![])]
+        );
+    }
+
+    #[tokio::test]
+    async fn not_a_stream() {
+        let db = Arc::new(Db::default());
+        db.map.insert(
+            BytesMut::from("events"),
+            Value::Array(vec![Value::Positive(1)]),
+        );
+        let xadd = CommandEntry::XAdd(XAdd {
+            key: BytesMut::from("events"),
+            entry: event(1),
+        });
+        assert_eq!(
+            execute(&db, xadd).await,
+            vec![Value::from(CommandError::WrongType)]
+        );
+        assert_eq!(
+            execute(&db, xrange(0, 10)).await,
+            vec![Value::from(CommandError::WrongType)]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc,
+};
+
+use crate::{codec::Connection, db::Db, error::Result, protocol::Value};
+
+/// Frames a streaming command may produce ahead of the client reading them.
+pub const BUFFER: usize = 16;
+
+/// Command replying with any number of frames over time instead of a single reply, e.g. a
+/// subscription pushing every change it sees.
+///
+/// Implementors produce their frames in [`StreamingCommand::stream`] and implement
+/// [`crate::command::Command::execute`] by calling [`execute`], which writes and flushes every
+/// frame as soon as it is produced.
+pub trait StreamingCommand {
+    /// Sends reply frames to `replies` until the command is done or
+    /// [`Replies::send`] reports that the client went away.
+    fn stream(
+        &self,
+        replies: Replies,
+        db: Arc<Db>,
+    ) -> impl std::future::Future<Output = Result<()>>;
+}
+
+/// Sending half of the frames of a [`StreamingCommand`].
+pub struct Replies(mpsc::Sender<Value<'static>>);
+
+impl Replies {
+    /// Queues `frame` to be written, waiting while [`BUFFER`] frames are still unwritten.
+    /// `false` once the frames can no longer be written, so the command should stop.
+    pub async fn send(&self, frame: Value<'static>) -> bool {
+        self.0.send(frame).await.is_ok()
+    }
+}
+
+/// Runs `command` and writes its frames to `connection` while it produces them.
+///
+/// Frames are flushed one by one, so the client sees each as soon as it is produced. If writing
+/// fails, further frames are refused, see [`Replies::send`].
+pub async fn execute<C, R, W>(
+    command: &C,
+    connection: &mut Connection<R, W>,
+    db: Arc<Db>,
+) -> Result<()>
+where
+    C: StreamingCommand,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let (sender, mut receiver) = mpsc::channel(BUFFER);
+    let write = async move {
+        while let Some(frame) = receiver.recv().await {
+            if connection.write_frame(frame).await.is_err()
+                || connection.flush_writer().await.is_err()
+            {
+                break;
+            }
+        }
+    };
+    let (result, ()) = tokio::join!(command.stream(Replies(sender), db), write);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use tokio::time::Instant;
+
+    use crate::{
+        codec::Connection,
+        command::streaming::{execute, Replies, StreamingCommand},
+        db::Db,
+        error::Result,
+        protocol::Value,
+        testing::TestWriter,
+    };
+
+    /// Replies with `count` numbers, one every `interval`.
+    struct Ticks {
+        count: u64,
+        interval: Duration,
+    }
+
+    impl StreamingCommand for Ticks {
+        async fn stream(&self, replies: Replies, _: Arc<Db>) -> Result<()> {
+            for n in 0..self.count {
+                if n != 0 {
+                    tokio::time::sleep(self.interval).await;
+                }
+                if !replies.send(Value::Positive(n)).await {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn frames_over_time() {
+        let ticks = Ticks {
+            count: 3,
+            interval: Duration::from_millis(10),
+        };
+        let mut connection = Connection::new(tokio::io::empty(), TestWriter::new());
+        let start = Instant::now();
+        execute(&ticks, &mut connection, Arc::new(Db::default()))
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(
+            connection.write_half.into_inner().values,
+            vec![Value::Positive(0), Value::Positive(1), Value::Positive(2)]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{command::Command, error::ProtocolError, protocol::Value};
+
+/// Exchanges the values of `a` and `b` and replies with `OK`.
+///
+/// A missing key counts as nil and is swapped too, so if only one of the keys exists its value
+/// moves to the other key. Both shards are locked for the whole swap, see [`Map::write_keys`],
+/// so no client sees one key changed without the other.
+///
+/// [`Map::write_keys`]: crate::db::map::Map::write_keys
+#[derive(Debug, PartialEq, Clone)]
+pub struct SwapKeys {
+    pub a: BytesMut,
+    pub b: BytesMut,
+}
+
+impl Command for SwapKeys {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let changed = {
+            let mut shards = db.map.write_keys([&self.a, &self.b]);
+            let a = shards.shard(&self.a).remove(&self.a);
+            let b = shards.shard(&self.b).remove(&self.b);
+            let changed = a.is_some() || b.is_some();
+            if let Some(b) = b {
+                shards.shard(&self.a).insert(self.a.clone(), b);
+            }
+            if let Some(a) = a {
+                shards.shard(&self.b).insert(self.b.clone(), a);
+            }
+            changed
+        };
+        if changed {
+            db.modified(&self.a);
+            db.modified(&self.b);
+        }
+        let _ = connection
+            .write_frame(Value::String(Cow::Borrowed("OK")))
+            .await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(a), Value::Bytes(b)] => Ok(Self {
+                a: BytesMut::from(a.as_bytes()),
+                b: BytesMut::from(b.as_bytes()),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("SWAPKEYS")),
+            Value::Bytes(Cow::Borrowed(self.a.as_bytes())),
+            Value::Bytes(Cow::Borrowed(self.b.as_bytes())),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{entry::CommandEntry, swap::SwapKeys},
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn swap(a: &str, b: &str) -> CommandEntry {
+        CommandEntry::SwapKeys(SwapKeys {
+            a: BytesMut::from(a),
+            b: BytesMut::from(b),
+        })
+    }
+
+    #[tokio::test]
+    async fn swap_existing() {
+        let db = Arc::new(Db::default());
+        db.map.insert(BytesMut::from("a"), Value::Positive(1));
+        db.map
+            .insert(BytesMut::from("b"), Value::String(Cow::Borrowed("two")));
+        assert_eq!(
+            execute(&db, swap("a", "b")).await,
+            vec![Value::String(Cow::Borrowed("OK"))]
+        );
+        assert_eq!(
+            db.map.get_owned(b"a".as_slice()),
+            Some(Value::String(Cow::Borrowed("two")))
+        );
+        assert_eq!(db.map.get_owned(b"b".as_slice()), Some(Value::Positive(1)));
+    }
+
+    #[tokio::test]
+    async fn swap_with_missing() {
+        let db = Arc::new(Db::default());
+        db.map.insert(BytesMut::from("a"), Value::Positive(1));
+        execute(&db, swap("a", "missing")).await;
+        assert!(!db.map.contains(b"a".as_slice()));
+        assert_eq!(
+            db.map.get_owned(b"missing".as_slice()),
+            Some(Value::Positive(1))
+        );
+
+        execute(&db, swap("missing", "missing")).await;
+        assert_eq!(
+            db.map.get_owned(b"missing".as_slice()),
+            Some(Value::Positive(1))
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    borrow::Cow,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{command::Command, error::ProtocolError, protocol::Value};
+
+/// Replies with the server time as `[unix_seconds, microseconds]`, the microseconds elapsed in
+/// the current second.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Time;
+
+impl Command for Time {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        _: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let _ = connection
+            .write_frame(Value::Array(vec![
+                Value::Positive(now.as_secs()),
+                Value::Positive(now.subsec_micros() as u64),
+            ]))
+            .await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        if req.as_ref().is_empty() {
+            Ok(Self)
+        } else {
+            Err(ProtocolError::Command)
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![Value::String(Cow::Borrowed("TIME"))])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        command::{entry::CommandEntry, time::Time},
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    async fn time(db: &Arc<Db>) -> (u64, u64) {
+        let reply = execute(db, CommandEntry::Time(Time)).await;
+        let [Value::Array(time)] = &reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        let [Value::Positive(seconds), Value::Positive(micros)] = time[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        (seconds, micros)
+    }
+
+    #[tokio::test]
+    async fn non_decreasing() {
+        let db = Arc::new(Db::default());
+        let first = time(&db).await;
+        let second = time(&db).await;
+        // 2020-09-13, any clock showing an earlier time is not plausible.
+        assert!(first.0 > 1_600_000_000);
+        assert!(first.1 < 1_000_000);
+        assert!(second >= first);
+    }
+}
@@ -0,0 +1,285 @@
+use std::{borrow::Cow, time::Instant};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    error::{CommandError, ProtocolError},
+    protocol::Value,
+};
+
+/// Opens an upload of `size` bytes and replies with its handle, see [`crate::db::uploads`].
+/// Fails with [`CommandError::LimitExceeded`] once the pending uploads would exceed
+/// [`crate::db::config::Parameter::MaxUploadSize`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Reserve {
+    pub size: u64,
+}
+
+impl Command for Reserve {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let max_size = usize::try_from(db.limits.max_upload_size()).unwrap_or(usize::MAX);
+        let reply = match usize::try_from(self.size) {
+            Ok(size) => match db.uploads.reserve(size, max_size, Instant::now()) {
+                Ok(handle) => Value::Positive(handle),
+                Err(error) => db.error_reply(error),
+            },
+            Err(_) => db.error_reply(CommandError::OutOfRange),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Positive(size)] => Ok(Self { size: *size }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("RESERVE")),
+            Value::Positive(self.size),
+        ])
+    }
+}
+
+/// Copies `bytes` into the upload at `offset` and replies with the number of bytes written.
+/// Unknown handles and chunks past the reserved size are rejected with
+/// [`CommandError::OutOfRange`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct WriteChunk {
+    pub handle: u64,
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+impl Command for WriteChunk {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let written = usize::try_from(self.offset).is_ok_and(|offset| {
+            db.uploads
+                .write(self.handle, offset, &self.bytes, Instant::now())
+        });
+        let reply = if written {
+            Value::Positive(self.bytes.len() as u64)
+        } else {
+            db.error_reply(CommandError::OutOfRange)
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Positive(handle), Value::Positive(offset), Value::Bytes(bytes)] => Ok(Self {
+                handle: *handle,
+                offset: *offset,
+                bytes: bytes.to_vec(),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("WRITECHUNK")),
+            Value::Positive(self.handle),
+            Value::Positive(self.offset),
+            Value::Bytes(Cow::Borrowed(&self.bytes)),
+        ])
+    }
+}
+
+/// Closes the upload and stores its bytes at `key` in one step, replying with their length.
+/// Bytes no chunk was written to are zero.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Commit {
+    pub handle: u64,
+    pub key: BytesMut,
+}
+
+impl Command for Commit {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match db.uploads.take(self.handle, Instant::now()) {
+            Some(data) => {
+                let len = data.len() as u64;
+                let value = db.stored(Value::Bytes(Cow::Owned(data)));
+                db.map.insert(self.key.clone(), value);
+                db.modified(&self.key);
+                Value::Positive(len)
+            }
+            None => db.error_reply(CommandError::OutOfRange),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Positive(handle), Value::Bytes(key)] => Ok(Self {
+                handle: *handle,
+                key: BytesMut::from(key.as_bytes()),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("COMMIT")),
+            Value::Positive(self.handle),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc, time::Instant};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{
+            entry::CommandEntry,
+            get::Get,
+            upload::{Commit, Reserve, WriteChunk},
+        },
+        db::{config::Parameter, Db},
+        error::CommandError,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn write_chunk(handle: u64, offset: u64, bytes: &[u8]) -> CommandEntry {
+        CommandEntry::WriteChunk(WriteChunk {
+            handle,
+            offset,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    #[tokio::test]
+    async fn assemble_chunks() {
+        let db = Arc::new(Db::default());
+        let blob = (0..200u8).collect::<Vec<_>>();
+        let reply = execute(&db, CommandEntry::Reserve(Reserve { size: 200 })).await;
+        let [Value::Positive(handle)] = reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+
+        // Chunks may arrive in any order.
+        for (i, chunk) in blob.chunks(30).enumerate().rev() {
+            assert_eq!(
+                execute(&db, write_chunk(handle, i as u64 * 30, chunk)).await,
+                vec![Value::Positive(chunk.len() as u64)]
+            );
+        }
+        assert!(!db.map.contains(b"blob".as_slice()));
+
+        let commit = CommandEntry::Commit(Commit {
+            handle,
+            key: BytesMut::from("blob"),
+        });
+        assert_eq!(
+            execute(&db, commit.clone()).await,
+            vec![Value::Positive(200)]
+        );
+        let get = CommandEntry::Get(Get {
+            key: BytesMut::from("blob"),
+        });
+        assert_eq!(
+            execute(&db, get).await,
+            vec![Value::Bytes(Cow::Owned(blob))]
+        );
+
+        // The upload is gone once committed.
+        assert_eq!(
+            execute(&db, commit).await,
+            vec![Value::from(CommandError::OutOfRange)]
+        );
+    }
+
+    #[tokio::test]
+    async fn chunk_out_of_range() {
+        let db = Arc::new(Db::default());
+        let reply = execute(&db, CommandEntry::Reserve(Reserve { size: 4 })).await;
+        let [Value::Positive(handle)] = reply[..] else {
+            panic!("unexpected reply {reply:?}");
+        };
+        assert_eq!(
+            execute(&db, write_chunk(handle, 2, b"abc")).await,
+            vec![Value::from(CommandError::OutOfRange)]
+        );
+        assert_eq!(
+            execute(&db, write_chunk(handle + 1, 0, b"a")).await,
+            vec![Value::from(CommandError::OutOfRange)]
+        );
+        assert_eq!(
+            execute(&db, write_chunk(handle, 1, b"abc")).await,
+            vec![Value::Positive(3)]
+        );
+        assert_eq!(
+            db.uploads.take(handle, Instant::now()),
+            Some(b"\0abc".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn reserve_over_limit() {
+        let db = Arc::new(Db::default());
+        db.config_set(Parameter::MaxUploadSize, 100).unwrap();
+        assert_eq!(
+            execute(&db, CommandEntry::Reserve(Reserve { size: 101 })).await,
+            vec![Value::from(CommandError::LimitExceeded)]
+        );
+        assert!(matches!(
+            execute(&db, CommandEntry::Reserve(Reserve { size: 100 })).await[..],
+            [Value::Positive(_)]
+        ));
+    }
+}
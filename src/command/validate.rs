@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    command::Command,
+    error::{CommandError, ProtocolError},
+    protocol::{parse, Value},
+};
+
+/// Parses `payload` as a single frame without storing or executing it, as a linter for clients
+/// implementing the format.
+///
+/// Replies with `["OK", consumed]` if a frame was parsed, `consumed` being its length in bytes,
+/// which is less than the payload if anything follows the frame. Otherwise replies with a `PARSE`
+/// error giving the offset parsing failed at, e.g. `PARSE Incomplete at 7` if the payload ends
+/// in the middle of the frame.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Validate {
+    pub payload: BytesMut,
+}
+
+impl Validate {
+    fn validate(&self) -> Value<'static> {
+        let code = CommandError::Parse.name();
+        match parse(&self.payload) {
+            Ok((rest, _)) => Value::Array(vec![
+                Value::String(Cow::Borrowed("OK")),
+                Value::Positive((self.payload.len() - rest.len()) as u64),
+            ]),
+            Err(nom::Err::Incomplete(_)) => {
+                Value::error_with_code(code, &format!("Incomplete at {}", self.payload.len()))
+            }
+            Err(nom::Err::Error(error) | nom::Err::Failure(error)) => match error.remaining {
+                Some(remaining) => Value::error_with_code(
+                    code,
+                    &format!("Invalid at {}", self.payload.len() - remaining),
+                ),
+                None => Value::error_with_code(code, "Invalid"),
+            },
+        }
+    }
+}
+
+impl Command for Validate {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        _: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let _ = connection.write_frame(self.validate()).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(payload)] => Ok(Self {
+                payload: BytesMut::from(payload.as_bytes()),
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("VALIDATE")),
+            Value::Bytes(Cow::Borrowed(self.payload.as_bytes())),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{entry::CommandEntry, validate::Validate},
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    async fn validate(payload: &[u8]) -> Value<'static> {
+        let validate = CommandEntry::Validate(Validate {
+            payload: BytesMut::from(payload),
+        });
+        let mut reply = execute(&Arc::new(Db::default()), validate).await;
+        assert_eq!(reply.len(), 1);
+        reply.remove(0)
+    }
+
+    fn frame() -> BytesMut {
+        Value::Array(vec![
+            Value::Positive(1),
+            Value::String(Cow::Borrowed("abc")),
+        ])
+        .encode()
+    }
+
+    #[tokio::test]
+    async fn well_formed() {
+        let mut payload = frame();
+        let len = payload.len() as u64;
+        assert_eq!(
+            validate(&payload).await,
+            Value::Array(vec![
+                Value::String(Cow::Borrowed("OK")),
+                Value::Positive(len)
+            ])
+        );
+
+        payload.extend_from_slice(&frame());
+        assert_eq!(
+            validate(&payload).await,
+            Value::Array(vec![
+                Value::String(Cow::Borrowed("OK")),
+                Value::Positive(len)
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn truncated() {
+        let payload = frame();
+        let truncated = &payload[..payload.len() - 1];
+        assert_eq!(
+            validate(truncated).await,
+            Value::error_with_code("PARSE", &format!("Incomplete at {}", truncated.len()))
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid() {
+        // The second element of the array starts with a float header, which is not supported.
+        assert_eq!(
+            validate(&[0x82, 0x01, 0xE0]).await,
+            Value::error_with_code("PARSE", "Invalid at 2")
+        );
+    }
+}
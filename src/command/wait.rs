@@ -0,0 +1,198 @@
+use std::{borrow::Cow, time::Duration};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+use tokio::time::Instant;
+
+use crate::{
+    command::Command,
+    db::Db,
+    error::ProtocolError,
+    protocol::{Value, EMPTY},
+};
+
+/// Blocks until the value at `key` changes and replies with the new value, or nil if the key was
+/// deleted. A timeout of 0 blocks forever, otherwise nil is replied once `timeout_ms` elapses.
+///
+/// Woken by every write to the key, see [`Db::modified`], but a write storing the value the key
+/// already had does not count as a change.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WaitKey {
+    pub key: BytesMut,
+    pub timeout_ms: u64,
+}
+
+/// Outcome of [`WaitKey::wait`].
+#[derive(Debug, PartialEq)]
+enum Watched {
+    /// The key holds a new value, `None` if it was deleted.
+    Changed(Option<Value<'static>>),
+    TimedOut,
+}
+
+impl WaitKey {
+    async fn wait(&self, db: &Db) -> Watched {
+        let deadline =
+            (self.timeout_ms != 0).then(|| Instant::now() + Duration::from_millis(self.timeout_ms));
+        let registration = db.watchers.register(std::slice::from_ref(&self.key));
+        let initial = db.map.get_owned(&self.key);
+        loop {
+            match deadline {
+                Some(deadline) => {
+                    if tokio::time::timeout_at(deadline, registration.notified())
+                        .await
+                        .is_err()
+                    {
+                        return Watched::TimedOut;
+                    }
+                }
+                None => registration.notified().await,
+            }
+            let current = db.map.get_owned(&self.key);
+            if current != initial {
+                return Watched::Changed(current);
+            }
+        }
+    }
+}
+
+impl Command for WaitKey {
+    type ExecutionResult = crate::error::Result<()>;
+
+    async fn execute<W, R>(
+        &self,
+        connection: &mut crate::codec::Connection<R, W>,
+        db: std::sync::Arc<crate::db::Db>,
+    ) -> Self::ExecutionResult
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: Unpin + tokio::io::AsyncWrite,
+    {
+        let reply = match self.wait(&db).await {
+            Watched::Changed(Some(value)) => value,
+            Watched::Changed(None) | Watched::TimedOut => Value::Error(Cow::Borrowed(EMPTY)),
+        };
+        let _ = connection.write_frame(reply).await;
+        Ok(())
+    }
+
+    fn decode<'c, V>(req: V) -> crate::error::Result<Self>
+    where
+        Self: Sized,
+        V: AsRef<[Value<'c>]>,
+    {
+        match req.as_ref() {
+            [Value::Bytes(key), Value::Positive(timeout_ms)] => Ok(Self {
+                key: BytesMut::from(key.as_bytes()),
+                timeout_ms: *timeout_ms,
+            }),
+            _ => Err(ProtocolError::Command),
+        }
+    }
+
+    fn encode(&self) -> Value<'_> {
+        Value::Array(vec![
+            Value::String(Cow::Borrowed("WAITKEY")),
+            Value::Bytes(Cow::Borrowed(self.key.as_bytes())),
+            Value::Positive(self.timeout_ms),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, sync::Arc, time::Duration};
+
+    use bytes::BytesMut;
+
+    use crate::{
+        codec::Connection,
+        command::{
+            del::Del,
+            entry::CommandEntry,
+            set::{GetSet, Set},
+            wait::{WaitKey, Watched},
+            Command,
+        },
+        db::Db,
+        protocol::Value,
+        testing::execute,
+    };
+
+    fn set(value: Value<'static>) -> CommandEntry {
+        CommandEntry::Set(Set {
+            key: BytesMut::from("key"),
+            value,
+        })
+    }
+
+    fn wait_key(timeout_ms: u64) -> WaitKey {
+        WaitKey {
+            key: BytesMut::from("key"),
+            timeout_ms,
+        }
+    }
+
+    #[tokio::test]
+    async fn unblocked_by_set() {
+        let db = Arc::new(Db::default());
+        execute(&db, set(Value::Positive(1))).await;
+        let writes = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            // Storing the same value again is not a change.
+            execute(&db, set(Value::Positive(1))).await;
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            execute(&db, set(Value::String(Cow::Borrowed("new")))).await;
+        };
+        let (reply, _) = tokio::join!(execute(&db, CommandEntry::WaitKey(wait_key(0))), writes);
+        assert_eq!(reply, vec![Value::String(Cow::Borrowed("new"))]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn unblocked_by_getset_with_pending_reply() {
+        let db = Arc::new(Db::default());
+        // Larger than the write buffer, so the reply waits for the client to read.
+        execute(&db, set(Value::Bytes(Cow::Owned(vec![0; 64 * 1024])))).await;
+        let waiter = tokio::spawn({
+            let db = db.clone();
+            async move { execute(&db, CommandEntry::WaitKey(wait_key(0))).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // A client which never reads the reply.
+        let (_client, server) = tokio::io::duplex(1);
+        let getset = tokio::spawn(async move {
+            let mut connection = Connection::new(tokio::io::empty(), server);
+            let getset = GetSet {
+                key: BytesMut::from("key"),
+                value: Value::Positive(2),
+            };
+            getset.execute(&mut connection, db).await
+        });
+        let reply = tokio::time::timeout(Duration::from_secs(5), waiter).await;
+        assert_eq!(reply.unwrap().unwrap(), vec![Value::Positive(2)]);
+        getset.abort();
+    }
+
+    #[tokio::test]
+    async fn deleted_or_timed_out() {
+        let db = Arc::new(Db::default());
+        assert_eq!(wait_key(10).wait(&db).await, Watched::TimedOut);
+
+        execute(&db, set(Value::Positive(1))).await;
+        let del = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            execute(
+                &db,
+                CommandEntry::Del(Del {
+                    keys: vec![BytesMut::from("key")],
+                    return_value: false,
+                }),
+            )
+            .await;
+        };
+        let wait_key = wait_key(0);
+        let (watched, _) = tokio::join!(wait_key.wait(&db), del);
+        assert_eq!(watched, Watched::Changed(None));
+    }
+}
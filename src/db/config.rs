@@ -0,0 +1,200 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
+    time::Duration,
+};
+
+use crate::{db::Db, error::CommandError, protocol::Value};
+
+/// Server parameters which can be read and changed at runtime with `CONFIG`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Parameter {
+    /// Slowlog threshold in microseconds.
+    SlowlogLogSlowerThan,
+    /// Maximum number of slowlog entries.
+    SlowlogMaxLen,
+    /// Maximum number of keys tracked as recently modified.
+    RecentChangesMaxLen,
+    /// Maximum number of keys `DUMPALL` replies with.
+    DumpMaxKeys,
+    /// What `INCR` and `DECR` do on overflow, see [`OverflowMode`].
+    IncrOverflow,
+    /// Size in bytes from which `SET` compresses bytes and strings, 0 disables compression.
+    /// Ignored unless built with the `compression` feature.
+    CompressThreshold,
+    /// Maximum number of arguments of a single command.
+    MaxArgs,
+    /// Maximum total size in bytes of the uploads pending at once, see [`crate::db::uploads`].
+    MaxUploadSize,
+}
+
+impl Parameter {
+    pub const ALL: &'static [Parameter] = &[
+        Parameter::SlowlogLogSlowerThan,
+        Parameter::SlowlogMaxLen,
+        Parameter::RecentChangesMaxLen,
+        Parameter::DumpMaxKeys,
+        Parameter::IncrOverflow,
+        Parameter::CompressThreshold,
+        Parameter::MaxArgs,
+        Parameter::MaxUploadSize,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Parameter::SlowlogLogSlowerThan => "slowlog-log-slower-than",
+            Parameter::SlowlogMaxLen => "slowlog-max-len",
+            Parameter::RecentChangesMaxLen => "recent-changes-max-len",
+            Parameter::DumpMaxKeys => "dump-max-keys",
+            Parameter::IncrOverflow => "incr-overflow",
+            Parameter::CompressThreshold => "compress-threshold",
+            Parameter::MaxArgs => "max-args",
+            Parameter::MaxUploadSize => "max-upload-size",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|p| p.name() == name)
+    }
+}
+
+impl Db {
+    pub fn config_get(&self, parameter: Parameter) -> u64 {
+        match parameter {
+            Parameter::SlowlogLogSlowerThan => self.slowlog.threshold().as_micros() as u64,
+            Parameter::SlowlogMaxLen => self.slowlog.max_len() as u64,
+            Parameter::RecentChangesMaxLen => self.recent.max_len() as u64,
+            Parameter::DumpMaxKeys => self.limits.dump_max_keys(),
+            Parameter::IncrOverflow => self.limits.overflow_mode() as u64,
+            Parameter::CompressThreshold => self.limits.compress_threshold(),
+            Parameter::MaxArgs => self.limits.max_args(),
+            Parameter::MaxUploadSize => self.limits.max_upload_size(),
+        }
+    }
+
+    /// Changes `parameter`, failing with [`CommandError::OutOfRange`] if `value` is not valid
+    /// for it.
+    pub fn config_set(&self, parameter: Parameter, value: u64) -> Result<(), CommandError> {
+        match parameter {
+            Parameter::SlowlogLogSlowerThan => {
+                self.slowlog.set_threshold(Duration::from_micros(value))
+            }
+            Parameter::SlowlogMaxLen => self.slowlog.set_max_len(value as usize),
+            Parameter::RecentChangesMaxLen => self.recent.set_max_len(value as usize),
+            Parameter::DumpMaxKeys => self.limits.dump_max_keys.store(value, Ordering::Relaxed),
+            Parameter::IncrOverflow => {
+                let mode = OverflowMode::from_u64(value).ok_or(CommandError::OutOfRange)?;
+                self.limits.overflow.store(mode as u8, Ordering::Relaxed);
+            }
+            Parameter::CompressThreshold => self
+                .limits
+                .compress_threshold
+                .store(value, Ordering::Relaxed),
+            Parameter::MaxArgs => self.limits.max_args.store(value, Ordering::Relaxed),
+            Parameter::MaxUploadSize => self.limits.max_upload_size.store(value, Ordering::Relaxed),
+        }
+        Ok(())
+    }
+}
+
+/// What `INCR` and `DECR` do when the result does not fit into an integer value, i.e. is
+/// outside of `i64::MIN..=u64::MAX`.
+///
+/// Configured as a number, see the discriminants.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowMode {
+    /// Reply with [`CommandError::OutOfRange`] and keep the value.
+    #[default]
+    Error = 0,
+    /// Clamp the result to `u64::MAX` or `i64::MIN`.
+    Saturate = 1,
+    /// Wrap the result around like `u64` does above `u64::MAX`, or `i64` below `i64::MIN`.
+    Wrap = 2,
+}
+
+impl OverflowMode {
+    pub fn from_u64(value: u64) -> Option<Self> {
+        match value {
+            0 => Some(Self::Error),
+            1 => Some(Self::Saturate),
+            2 => Some(Self::Wrap),
+            _ => None,
+        }
+    }
+
+    /// Integer value holding `n`, overflowing according to the mode.
+    pub fn apply(self, n: i128) -> Result<Value<'static>, CommandError> {
+        let n = match self {
+            Self::Error => n,
+            Self::Saturate => n.clamp(i64::MIN as i128, u64::MAX as i128),
+            Self::Wrap if n > u64::MAX as i128 => n as u64 as i128,
+            Self::Wrap if n < i64::MIN as i128 => n as i64 as i128,
+            Self::Wrap => n,
+        };
+        Value::from_number(n).ok_or(CommandError::OutOfRange)
+    }
+}
+
+pub const DEFAULT_DUMP_MAX_KEYS: u64 = 1000;
+pub const DEFAULT_MAX_ARGS: u64 = 1 << 16;
+pub const DEFAULT_MAX_UPLOAD_SIZE: u64 = 64 << 20;
+
+/// Limits on requests and replies, changed through [`Parameter`]s.
+pub struct Limits {
+    dump_max_keys: AtomicU64,
+    overflow: AtomicU8,
+    compress_threshold: AtomicU64,
+    max_args: AtomicU64,
+    max_upload_size: AtomicU64,
+}
+
+impl Limits {
+    pub fn dump_max_keys(&self) -> u64 {
+        self.dump_max_keys.load(Ordering::Relaxed)
+    }
+
+    pub fn overflow_mode(&self) -> OverflowMode {
+        OverflowMode::from_u64(self.overflow.load(Ordering::Relaxed) as u64).unwrap_or_default()
+    }
+
+    pub fn compress_threshold(&self) -> u64 {
+        self.compress_threshold.load(Ordering::Relaxed)
+    }
+
+    pub fn max_args(&self) -> u64 {
+        self.max_args.load(Ordering::Relaxed)
+    }
+
+    pub fn max_upload_size(&self) -> u64 {
+        self.max_upload_size.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            dump_max_keys: AtomicU64::new(DEFAULT_DUMP_MAX_KEYS),
+            overflow: AtomicU8::new(OverflowMode::default() as u8),
+            compress_threshold: AtomicU64::new(0),
+            max_args: AtomicU64::new(DEFAULT_MAX_ARGS),
+            max_upload_size: AtomicU64::new(DEFAULT_MAX_UPLOAD_SIZE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use crate::{db::config::OverflowMode, error::CommandError, protocol::Value};
+
+    #[test_case(OverflowMode::Error, u64::MAX as i128 + 1, Err(CommandError::OutOfRange))]
+    #[test_case(OverflowMode::Error, u64::MAX as i128, Ok(Value::Positive(u64::MAX)))]
+    #[test_case(OverflowMode::Saturate, u64::MAX as i128 + 1, Ok(Value::Positive(u64::MAX)))]
+    #[test_case(OverflowMode::Saturate, i64::MIN as i128 - 1, Ok(Value::Negative(i64::MIN)))]
+    #[test_case(OverflowMode::Wrap, u64::MAX as i128 + 2, Ok(Value::Positive(1)))]
+    #[test_case(OverflowMode::Wrap, i64::MIN as i128 - 1, Ok(Value::Positive(i64::MAX as u64)))]
+    #[test_case(OverflowMode::Wrap, -1, Ok(Value::Negative(-1)))]
+    fn overflow(mode: OverflowMode, n: i128, result: Result<Value<'static>, CommandError>) {
+        assert_eq!(mode.apply(n), result);
+    }
+}
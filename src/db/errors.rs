@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::CommandError;
+
+/// Number of error replies of each [`CommandError`] since startup or the last reset.
+pub struct ErrorStats {
+    counts: [AtomicU64; CommandError::ALL.len()],
+}
+
+impl ErrorStats {
+    pub fn record(&self, error: CommandError) {
+        self.counts[error as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self, error: CommandError) -> u64 {
+        self.counts[error as usize].load(Ordering::Relaxed)
+    }
+
+    /// Count of every error, in the order of [`CommandError::ALL`].
+    pub fn counts(&self) -> Vec<(CommandError, u64)> {
+        CommandError::ALL
+            .iter()
+            .map(|&error| (error, self.count(error)))
+            .collect()
+    }
+
+    pub fn reset(&self) {
+        self.counts
+            .iter()
+            .for_each(|count| count.store(0, Ordering::Relaxed));
+    }
+}
+
+impl Default for ErrorStats {
+    fn default() -> Self {
+        Self {
+            counts: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+/// Whether `key` matches the glob `pattern`, with the syntax of Redis' `KEYS`:
+///
+/// - `*` matches any number of bytes, `?` exactly one,
+/// - `[abc]` one of the listed bytes, `[a-c]` one in the range, `[^abc]` one not listed,
+/// - `\` matches the byte after it literally.
+///
+/// Keys are compared byte by byte, so patterns may match keys which are not valid UTF-8.
+pub fn matches(pattern: &[u8], key: &[u8]) -> bool {
+    let (mut p, mut k) = (0, 0);
+    // Where to resume after the last `*` if the rest does not match: the pattern after the star
+    // and the key position it was tried at.
+    let mut backtrack = None;
+    while k < key.len() {
+        let step = match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, k));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match_class(pattern, p + 1, key[k]),
+            Some(b'\\') if p + 1 < pattern.len() => (pattern[p + 1] == key[k]).then_some(p + 2),
+            Some(&byte) => (byte == key[k]).then_some(p + 1),
+            None => None,
+        };
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                k += 1;
+            }
+            (None, Some((star, tried))) => {
+                backtrack = Some((star, tried + 1));
+                p = star;
+                k = tried + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p.min(pattern.len())..].iter().all(|&b| b == b'*')
+}
+
+/// Matches `byte` against the class starting at `start`, right after its `[`. Returns the
+/// position after the closing `]` if it matches. An unterminated class extends to the end of
+/// the pattern.
+fn match_class(pattern: &[u8], start: usize, byte: u8) -> Option<usize> {
+    let mut p = start;
+    let negated = pattern.get(p) == Some(&b'^');
+    if negated {
+        p += 1;
+    }
+    let mut matched = false;
+    while let Some(&c) = pattern.get(p) {
+        match c {
+            b']' => break,
+            b'\\' if p + 1 < pattern.len() => {
+                matched |= pattern[p + 1] == byte;
+                p += 2;
+            }
+            _ if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                let (low, high) = (c.min(pattern[p + 2]), c.max(pattern[p + 2]));
+                matched |= (low..=high).contains(&byte);
+                p += 3;
+            }
+            _ => {
+                matched |= c == byte;
+                p += 1;
+            }
+        }
+    }
+    (matched != negated).then_some(p + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn glob() {
+        let cases = [
+            ("user:*", "user:1", true),
+            ("user:*", "user:", true),
+            ("user:*", "users:1", false),
+            ("*:name", "user:1:name", true),
+            ("*:name", "user:1:names", false),
+            ("a*b*c", "aXbYbZc", true),
+            ("h?llo", "hello", true),
+            ("h?llo", "hllo", false),
+            ("h[ae]llo", "hallo", true),
+            ("h[ae]llo", "hillo", false),
+            ("h[^e]llo", "hallo", true),
+            ("h[^e]llo", "hello", false),
+            ("h[a-c]llo", "hbllo", true),
+            ("h[a-c]llo", "hdllo", false),
+            ("h\\*llo", "h*llo", true),
+            ("h\\*llo", "hello", false),
+            ("", "", true),
+            ("*", "", true),
+        ];
+        for (pattern, key, expected) in cases {
+            assert_eq!(
+                matches(pattern.as_bytes(), key.as_bytes()),
+                expected,
+                "{pattern} against {key}"
+            );
+        }
+    }
+}
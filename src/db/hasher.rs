@@ -0,0 +1,39 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+};
+
+type BuildFn = dyn Fn() -> Box<dyn Hasher + Send> + Send + Sync;
+
+/// Hasher of the keyspace, chosen when the [`crate::db::Db`] is created.
+///
+/// Defaults to [`RandomState`], SipHash keyed with per-process random keys, which keeps an
+/// attacker from crafting keys that collide. Any other [`BuildHasher`] can be plugged in with
+/// [`KeyHasher::new`], e.g. a faster one for trusted workloads.
+#[derive(Clone)]
+pub struct KeyHasher(Arc<BuildFn>);
+
+impl KeyHasher {
+    pub fn new<S>(hash_builder: S) -> Self
+    where
+        S: BuildHasher + Send + Sync + 'static,
+        S::Hasher: Send + 'static,
+    {
+        Self(Arc::new(move || Box::new(hash_builder.build_hasher())))
+    }
+}
+
+impl Default for KeyHasher {
+    fn default() -> Self {
+        Self::new(RandomState::new())
+    }
+}
+
+impl BuildHasher for KeyHasher {
+    type Hasher = Box<dyn Hasher + Send>;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        (self.0)()
+    }
+}
@@ -0,0 +1,137 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+/// Bits of precision kept per power of two: values are counted exactly below
+/// `2 << SUB_BUCKET_BITS` microseconds, larger ones in buckets at most 1/16 of their value wide.
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+/// Enough buckets for any `u64` number of microseconds.
+const BUCKETS: usize = (65 - SUB_BUCKET_BITS as usize) * SUB_BUCKETS;
+
+/// Histogram of durations in the style of HDR histograms: buckets grow with the value, so the
+/// relative error stays bounded while the memory is fixed.
+#[derive(Clone)]
+pub struct Histogram {
+    counts: Box<[u64; BUCKETS]>,
+}
+
+impl Histogram {
+    fn index(micros: u64) -> usize {
+        if micros < 2 * SUB_BUCKETS as u64 {
+            return micros as usize;
+        }
+        let shift = 63 - micros.leading_zeros() - SUB_BUCKET_BITS;
+        shift as usize * SUB_BUCKETS + (micros >> shift) as usize
+    }
+
+    /// Largest value counted in the bucket at `index`.
+    fn highest(index: usize) -> u64 {
+        if index < 2 * SUB_BUCKETS {
+            return index as u64;
+        }
+        let shift = index / SUB_BUCKETS - 1;
+        let sub = (index % SUB_BUCKETS + SUB_BUCKETS) as u64;
+        (sub << shift) + ((1 << shift) - 1)
+    }
+
+    pub fn record(&mut self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        self.counts[Self::index(micros)] += 1;
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Microseconds which `percentile` percent of the recorded durations do not exceed, rounded
+    /// up to the end of their bucket. `None` if nothing was recorded.
+    pub fn percentile(&self, percentile: f64) -> Option<u64> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((percentile / 100.0 * total as f64).ceil() as u64).clamp(1, total);
+        let mut seen = 0;
+        self.counts.iter().enumerate().find_map(|(index, count)| {
+            seen += count;
+            (seen >= rank).then(|| Self::highest(index))
+        })
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: Box::new([0; BUCKETS]),
+        }
+    }
+}
+
+/// Execution times of every command, by the name it is registered under.
+#[derive(Default)]
+pub struct Latencies {
+    commands: Mutex<HashMap<&'static str, Histogram>>,
+}
+
+impl Latencies {
+    pub fn record(&self, command: &'static str, duration: Duration) {
+        self.commands
+            .lock()
+            .unwrap()
+            .entry(command)
+            .or_default()
+            .record(duration);
+    }
+
+    /// Durations of `command`, or of all commands together if `None`.
+    pub fn histogram(&self, command: Option<&str>) -> Histogram {
+        let commands = self.commands.lock().unwrap();
+        let mut histogram = Histogram::default();
+        commands
+            .iter()
+            .filter(|(name, _)| command.is_none_or(|command| command == **name))
+            .for_each(|(_, other)| histogram.merge(other));
+        histogram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Histogram, BUCKETS};
+
+    #[test]
+    fn buckets_cover_every_value() {
+        let mut previous = None;
+        for index in 0..BUCKETS {
+            let highest = Histogram::highest(index);
+            assert_eq!(Histogram::index(highest), index);
+            if let Some(previous) = previous {
+                assert_eq!(Histogram::index(previous + 1), index);
+            }
+            previous = Some(highest);
+        }
+        assert_eq!(previous, Some(u64::MAX));
+    }
+
+    #[test]
+    fn percentiles() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.percentile(50.0), None);
+        for micros in 1..=1000 {
+            histogram.record(Duration::from_micros(micros));
+        }
+        assert_eq!(histogram.percentile(0.0), Some(1));
+        let p50 = histogram.percentile(50.0).unwrap();
+        assert!((500..=500 + 500 / 16).contains(&p50), "{p50}");
+        let p99 = histogram.percentile(99.0).unwrap();
+        assert!((990..=990 + 990 / 16).contains(&p99), "{p99}");
+        assert_eq!(histogram.percentile(100.0), Some(1023));
+    }
+}
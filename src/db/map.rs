@@ -0,0 +1,487 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, BinaryHeap, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Number of shards in a [`Map`].
+pub const SHARD_COUNT: usize = 128;
+
+pub type Shard<K, V, S = RandomState> = HashMap<K, V, S>;
+pub type ShardReadGuard<'a, K, V, S = RandomState> = RwLockReadGuard<'a, Shard<K, V, S>>;
+pub type ShardWriteGuard<'a, K, V, S = RandomState> = RwLockWriteGuard<'a, Shard<K, V, S>>;
+
+/// Index of the shard holding the keys at `position`, see [`Map::scan`], taken from its top bits.
+fn shard_of(position: u64) -> usize {
+    (position >> (u64::BITS - SHARD_COUNT.trailing_zeros())) as usize
+}
+
+/// Sharded, lock-based hash map.
+///
+/// Every key belongs to one of [`SHARD_COUNT`] shards, each behind its own lock. [`Map::read`]
+/// and [`Map::write`] lock the shard of a single key, [`Map::write_keys`] locks the shards of
+/// several keys at once without risking a deadlock.
+pub struct Map<K, V, S = RandomState> {
+    hash_builder: S,
+    shards: Box<[RwLock<Shard<K, V, S>>]>,
+}
+
+impl<K, V> Map<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> Default for Map<K, V, S>
+where
+    S: BuildHasher + Clone + Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> Map<K, V, S>
+where
+    S: BuildHasher + Clone,
+{
+    pub fn with_hasher(hash_builder: S) -> Self {
+        let shards = (0..SHARD_COUNT)
+            .map(|_| RwLock::new(HashMap::with_hasher(hash_builder.clone())))
+            .collect();
+        Self {
+            hash_builder,
+            shards,
+        }
+    }
+}
+
+impl<K, V, S> Map<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Index of the shard `key` belongs to.
+    ///
+    /// `key` may be any borrowed form of `K` that hashes the same way, e.g. `&[u8]` for a
+    /// `BytesMut` key.
+    pub fn shard_index<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        shard_of(self.position(key))
+    }
+
+    /// Position of `key` in the order [`Map::scan`] visits keys in: its hash, spread since
+    /// shards use the same hasher for their tables, to keep keys of one shard from sharing the
+    /// bits the table relies on.
+    fn position<Q>(&self, key: &Q) -> u64
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        self.hash_builder
+            .hash_one(key)
+            .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }
+
+    /// Read-locks the shard `key` belongs to.
+    ///
+    /// Lookups only need a borrowed key, so callers holding the key inside a frame do not have
+    /// to copy it into an owned `K` first.
+    pub fn read<'a, Q>(&'a self, key: &'a Q) -> (&'a Q, ShardReadGuard<'a, K, V, S>)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        (key, self.shards[self.shard_index(key)].read())
+    }
+
+    /// Write-locks the shard `key` belongs to.
+    pub fn write(&self, key: K) -> (K, ShardWriteGuard<'_, K, V, S>) {
+        let shard = self.shards[self.shard_index(&key)].write();
+        (key, shard)
+    }
+
+    /// Write-locks the shards of all `keys`.
+    ///
+    /// Shards are always locked in ascending index order, whatever the order of `keys` is, so
+    /// commands locking overlapping sets of keys can not deadlock each other. A shard shared by
+    /// several keys is locked once.
+    pub fn write_keys<'k>(&self, keys: impl IntoIterator<Item = &'k K>) -> ShardsGuard<'_, K, V, S>
+    where
+        K: 'k,
+    {
+        let mut indices = keys
+            .into_iter()
+            .map(|key| self.shard_index(key))
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        indices.dedup();
+        let guards = indices
+            .into_iter()
+            .map(|index| (index, self.shards[index].write()))
+            .collect();
+        ShardsGuard { map: self, guards }
+    }
+
+    /// Returns up to `count` keys for which `filter` holds, continuing from `cursor`, together
+    /// with the cursor to continue from. Scanning starts and ends at cursor 0.
+    ///
+    /// Keys are visited in order of their [`Map::position`], which does not depend on the
+    /// number of entries or on how shards grow, so every key present for the whole scan is
+    /// returned at least once, however the map changes between calls. Keys inserted or removed
+    /// during the scan may or may not be returned. Keys with equal positions are always returned
+    /// together, so a call may return more than `count` keys.
+    ///
+    /// Every shard holds one range of positions, so a call only reads the shards from the one
+    /// the cursor falls into until `count` keys are found, usually a single one. Only the
+    /// positions of the `count` first keys are kept while reading, and only the returned keys
+    /// are cloned. Shards are locked one at a time.
+    pub fn scan(&self, cursor: u64, count: usize, filter: impl Fn(&K, &V) -> bool) -> (u64, Vec<K>)
+    where
+        K: Clone,
+    {
+        let count = count.max(1);
+        let first = shard_of(cursor);
+        // Positions of the first `count` keys found so far, the last one on top. `count` comes
+        // from clients, so the containers grow with the keys found instead of being sized by it.
+        let mut positions = BinaryHeap::new();
+        let mut end = first;
+        for shard in &self.shards[first..] {
+            end += 1;
+            for (key, value) in shard.read().iter() {
+                let position = self.position(key);
+                if position >= cursor && filter(key, value) {
+                    positions.push(position);
+                    if positions.len() > count {
+                        positions.pop();
+                    }
+                }
+            }
+            // Later shards only hold later positions.
+            if positions.len() == count {
+                break;
+            }
+        }
+
+        let (last, next) = match positions.peek() {
+            Some(&last) if positions.len() == count => (last, last.checked_add(1).unwrap_or(0)),
+            _ => (u64::MAX, 0),
+        };
+        let mut keys = vec![];
+        for shard in &self.shards[first..end] {
+            keys.extend(
+                shard
+                    .read()
+                    .iter()
+                    .map(|(key, value)| (self.position(key), key, value))
+                    .filter(|(position, key, value)| {
+                        (cursor..=last).contains(position) && filter(key, value)
+                    })
+                    .map(|(position, key, _)| (position, key.clone())),
+            );
+        }
+        keys.sort_unstable_by_key(|(position, _)| *position);
+        (next, keys.into_iter().map(|(_, key)| key).collect())
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let (key, mut shard) = self.write(key);
+        shard.insert(key, value)
+    }
+
+    /// Inserts all `entries`, grouping them by shard so every shard is locked at most once.
+    pub fn insert_all(&self, entries: impl IntoIterator<Item = (K, V)>) {
+        let mut groups = (0..SHARD_COUNT).map(|_| Vec::new()).collect::<Vec<_>>();
+        for (key, value) in entries {
+            groups[self.shard_index(&key)].push((key, value));
+        }
+        for (shard, group) in self.shards.iter().zip(groups) {
+            if !group.is_empty() {
+                shard.write().extend(group);
+            }
+        }
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards[self.shard_index(key)].write().remove(key)
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, shard) = self.read(key);
+        shard.contains_key(key)
+    }
+
+    /// Returns a clone of the value at `key`.
+    pub fn get_owned<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        let (key, shard) = self.read(key);
+        shard.get(key).cloned()
+    }
+}
+
+impl<K, V, S> Map<K, V, S> {
+    /// Number of entries in the map, locking every shard in turn.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().len()).sum()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every entry, locking one shard at a time.
+    pub fn clear(&self) {
+        self.shards.iter().for_each(|shard| shard.write().clear());
+    }
+
+    /// Calls `f` on every entry, read-locking one shard at a time.
+    ///
+    /// Entries written to shards which were already visited are missed, the result is not a
+    /// consistent snapshot of the whole map.
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        for shard in self.shards.iter() {
+            shard.read().iter().for_each(|(k, v)| f(k, v));
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Keys of the shard at `index` for which `filter` holds, read-locking only that shard.
+    /// Walking the shards one by one keeps at most one shard's keys in memory.
+    pub fn shard_keys(&self, index: usize, filter: impl Fn(&K, &V) -> bool) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.shards[index]
+            .read()
+            .iter()
+            .filter(|(key, value)| filter(key, value))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Number of entries and their total `size` for every shard, in shard order.
+    ///
+    /// Shards are locked one at a time, each for as long as it takes to size its entries.
+    pub fn shard_stats(&self, size: impl Fn(&K, &V) -> usize) -> Vec<(usize, usize)> {
+        self.shards
+            .iter()
+            .map(|shard| {
+                let shard = shard.read();
+                (shard.len(), shard.iter().map(|(k, v)| size(k, v)).sum())
+            })
+            .collect()
+    }
+}
+
+/// Write guards for the shards of several keys, see [`Map::write_keys`].
+pub struct ShardsGuard<'a, K, V, S = RandomState> {
+    map: &'a Map<K, V, S>,
+    guards: Vec<(usize, ShardWriteGuard<'a, K, V, S>)>,
+}
+
+impl<K, V, S> ShardsGuard<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Shard `key` belongs to.
+    ///
+    /// **Panics** if `key` was not passed to [`Map::write_keys`].
+    pub fn shard(&mut self, key: &K) -> &mut Shard<K, V, S> {
+        let index = self.map.shard_index(key);
+        match self.guards.iter_mut().find(|(i, _)| *i == index) {
+            Some((_, guard)) => guard,
+            None => panic!("the shard of the key is not locked"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use bytes::BytesMut;
+
+    use super::Map;
+
+    #[test]
+    fn basic() {
+        let map = Map::new();
+        assert_eq!(map.insert("a", 1), None);
+        assert_eq!(map.insert("a", 2), Some(1));
+        assert_eq!(map.get_owned(&"a"), Some(2));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.remove(&"a"), Some(2));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn borrowed_lookup() {
+        let map = Map::new();
+        map.insert(BytesMut::from(&b"key"[..]), 1);
+        let key: &[u8] = b"key";
+        assert_eq!(map.get_owned(key), Some(1));
+        assert!(map.contains(key));
+        assert_eq!(map.shard_index(key), map.shard_index(&BytesMut::from(key)));
+    }
+
+    #[test]
+    fn scan_visits_every_key() {
+        let map = Map::new();
+        for key in 0..100u32 {
+            map.insert(key, ());
+        }
+        let mut cursor = 0;
+        let mut visited = vec![];
+        loop {
+            let (next, keys) = map.scan(cursor, 7, |_, _| true);
+            assert!(keys.len() <= 7);
+            visited.extend(keys);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        visited.sort_unstable();
+        assert_eq!(visited, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn scan_reads_few_shards() {
+        let map = Map::new();
+        for key in 0..100_000u32 {
+            map.insert(key, ());
+        }
+        let visited = std::cell::Cell::new(0);
+        let (_, keys) = map.scan(0, 10, |_, _| {
+            visited.set(visited.get() + 1);
+            true
+        });
+        assert_eq!(keys.len(), 10);
+        // A shard holds about 800 keys, every one is checked once per pass.
+        assert!(
+            visited.get() < 100_000 / 16,
+            "{} keys checked",
+            visited.get()
+        );
+    }
+
+    #[test]
+    fn scan_with_huge_count() {
+        let map = Map::new();
+        for key in 0..100u32 {
+            map.insert(key, ());
+        }
+        for count in [1 << 40, usize::MAX] {
+            let (next, mut keys) = map.scan(0, count, |_, _| true);
+            assert_eq!(next, 0);
+            keys.sort_unstable();
+            assert_eq!(keys, (0..100).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn scan_with_concurrent_writes() {
+        let map = Arc::new(Map::new());
+        for key in 0..1000u32 {
+            map.insert(key, ());
+        }
+
+        let writer = {
+            let map = map.clone();
+            thread::spawn(move || {
+                for key in 1000..5000u32 {
+                    map.insert(key, ());
+                    // Keys which are not part of the original set come and go.
+                    if key % 3 == 0 {
+                        map.remove(&(key - 1));
+                    }
+                }
+            })
+        };
+
+        let mut cursor = 0;
+        let mut visited = std::collections::HashSet::new();
+        loop {
+            let (next, keys) = map.scan(cursor, 10, |_, _| true);
+            visited.extend(keys);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        writer.join().unwrap();
+
+        assert!((0..1000).all(|key| visited.contains(&key)));
+    }
+
+    #[test]
+    fn write_keys_same_shard() {
+        let map = Map::new();
+        let mut guard = map.write_keys([&"a", &"a"]);
+        guard.shard(&"a").insert("a", 1);
+        drop(guard);
+        assert_eq!(map.get_owned(&"a"), Some(1));
+    }
+
+    #[test]
+    fn write_keys_does_not_deadlock() {
+        let map = Arc::new(Map::new());
+        let keys = (0..16).collect::<Vec<u32>>();
+        for key in &keys {
+            map.insert(*key, *key);
+        }
+
+        let handles = (0..8)
+            .map(|t| {
+                let map = map.clone();
+                let keys = keys.clone();
+                thread::spawn(move || {
+                    for i in 0..2000 {
+                        // Half of the threads lock pairs in reverse order.
+                        let (mut a, mut b) = (keys[i % 16], keys[(i * 7 + 3) % 16]);
+                        if t % 2 == 0 {
+                            std::mem::swap(&mut a, &mut b);
+                        }
+                        let mut guard = map.write_keys([&a, &b]);
+                        let value_a = guard.shard(&a).remove(&a).unwrap();
+                        let value_b = guard.shard(&b).remove(&b).unwrap_or(value_a);
+                        guard.shard(&a).insert(a, value_b);
+                        guard.shard(&b).insert(b, value_a);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut values = keys
+            .iter()
+            .map(|k| map.get_owned(k).unwrap())
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(values, keys);
+    }
+}
@@ -0,0 +1,238 @@
+pub mod config;
+pub mod errors;
+pub mod glob;
+pub mod hasher;
+pub mod latency;
+pub mod map;
+pub mod persist;
+pub mod recent;
+pub mod slowlog;
+pub mod storage;
+pub mod tokens;
+pub mod uploads;
+pub mod waiters;
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+
+use crate::{
+    db::{
+        config::Limits, errors::ErrorStats, hasher::KeyHasher, latency::Latencies, map::Map,
+        recent::RecentKeys, slowlog::SlowLogBuffer, tokens::IdempotencyTokens, uploads::Uploads,
+        waiters::KeyWaiters,
+    },
+    error::CommandError,
+    protocol::Value,
+};
+
+/// State shared by every connection of the server.
+///
+/// Commands receive it behind an [`std::sync::Arc`] and use [`Db::map`] as the keyspace.
+#[derive(Default)]
+pub struct Db {
+    pub map: Map<BytesMut, Value<'static>, KeyHasher>,
+    pub slowlog: SlowLogBuffer,
+    /// Execution times of every command, see [`Latencies`].
+    pub latencies: Latencies,
+    /// Keys changed by mutating commands, see [`RecentKeys`].
+    pub recent: RecentKeys,
+    /// Clients blocked popping from list keys, see [`KeyWaiters`].
+    pub waiters: KeyWaiters,
+    /// Clients waiting for any write to a key, see [`crate::command::wait::WaitKey`].
+    pub watchers: KeyWaiters,
+    /// Error replies by kind, see [`ErrorStats`].
+    pub errors: ErrorStats,
+    /// Replies of writes made with an idempotency token, see [`IdempotencyTokens`].
+    pub tokens: IdempotencyTokens,
+    /// Values being uploaded in chunks, see [`Uploads`].
+    pub uploads: Uploads,
+    pub limits: Limits,
+    /// Directory the files clients name are read from and written to, see
+    /// [`persist::file_name`]. Empty for the working directory.
+    pub data_dir: PathBuf,
+    /// When the database was created.
+    pub start: StartTime,
+}
+
+/// Instant the server started at, defaulting to the time it is created.
+pub struct StartTime(pub Instant);
+
+impl StartTime {
+    pub fn uptime(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+impl Default for StartTime {
+    fn default() -> Self {
+        Self(Instant::now())
+    }
+}
+
+impl Db {
+    /// Creates an empty database whose keyspace hashes keys with `hash_builder`, see
+    /// [`KeyHasher`].
+    pub fn with_hasher(hash_builder: KeyHasher) -> Self {
+        Self {
+            map: Map::with_hasher(hash_builder),
+            ..Default::default()
+        }
+    }
+
+    /// Path of the file `name` in [`Db::data_dir`]. `name` has to be checked with
+    /// [`persist::file_name`] first.
+    pub fn data_path(&self, name: &Path) -> PathBuf {
+        self.data_dir.join(name)
+    }
+
+    /// Counts `error` and returns the reply for it.
+    pub fn error_reply(&self, error: CommandError) -> Value<'static> {
+        self.errors.record(error);
+        error.into()
+    }
+
+    /// Reports that a command changed `key`: records it as recently modified and wakes the
+    /// clients watching it or blocked on it. Any write may store a list, so every one wakes the
+    /// blocked clients, not only pushes. Call it once the shard lock is released.
+    pub fn modified(&self, key: &BytesMut) {
+        self.recent.record(key);
+        self.watchers.notify(key);
+        self.waiters.notify(key);
+    }
+
+    /// `value` as `SET` stores it: bytes and strings of at least
+    /// [`config::Parameter::CompressThreshold`] bytes are compressed, if that makes them smaller.
+    pub fn stored(&self, value: Value<'static>) -> Value<'static> {
+        #[cfg(feature = "compression")]
+        {
+            let threshold = self.limits.compress_threshold();
+            if threshold != 0 && value.len() as u64 >= threshold {
+                if let Some(compressed) = crate::protocol::compressed::Compressed::compress(&value)
+                {
+                    return Value::Compressed(compressed);
+                }
+            }
+        }
+        value
+    }
+
+    /// Inserts `entries` directly into the keyspace, bypassing the command path.
+    ///
+    /// Meant for restoring large datasets: entries are grouped by shard, so each shard is locked
+    /// once, and no replies are produced. Loaded keys are not reported as recently modified.
+    pub fn bulk_load(&self, entries: impl IntoIterator<Item = (BytesMut, Value<'static>)>) {
+        self.map.insert_all(entries);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        borrow::Cow,
+        hash::{BuildHasherDefault, DefaultHasher},
+        sync::Arc,
+    };
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{entry::CommandEntry, get::Get, incr::Incr, set::Set},
+        db::{hasher::KeyHasher, Db},
+        protocol::{Value, EMPTY},
+        testing::execute,
+    };
+
+    #[tokio::test]
+    async fn custom_hasher() {
+        let db = Arc::new(Db::with_hasher(KeyHasher::new(BuildHasherDefault::<
+            DefaultHasher,
+        >::default())));
+        let key = BytesMut::from("key");
+        execute(
+            &db,
+            CommandEntry::Set(Set {
+                key: key.clone(),
+                value: Value::Positive(41),
+            }),
+        )
+        .await;
+        execute(&db, CommandEntry::Incr(Incr { key: key.clone() })).await;
+        let reply = execute(&db, CommandEntry::Get(Get { key: key.clone() })).await;
+        assert_eq!(reply, vec![Value::Positive(42)]);
+        assert_eq!(db.map.len(), 1);
+        assert_eq!(db.map.remove(&key), Some(Value::Positive(42)));
+        let reply = execute(&db, CommandEntry::Get(Get { key })).await;
+        assert_eq!(reply, vec![Value::Error(Cow::Borrowed(EMPTY))]);
+    }
+
+    #[test]
+    fn bulk_load() {
+        let db = Db::default();
+        db.bulk_load(
+            (0..100_000u64).map(|i| (BytesMut::from(i.to_string().as_str()), Value::Positive(i))),
+        );
+        assert_eq!(db.map.len(), 100_000);
+        for i in 0..100_000u64 {
+            let key = BytesMut::from(i.to_string().as_str());
+            assert_eq!(db.map.get_owned(&key), Some(Value::Positive(i)));
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn compressed_round_trip() {
+        use crate::db::config::Parameter;
+
+        let db = Arc::new(Db::default());
+        db.config_set(Parameter::CompressThreshold, 64).unwrap();
+        let key = BytesMut::from("large");
+        let value = Value::Bytes(Cow::Owned(b"abcd".repeat(1000)));
+        execute(
+            &db,
+            CommandEntry::Set(Set {
+                key: key.clone(),
+                value: value.clone(),
+            }),
+        )
+        .await;
+
+        let Some(Value::Compressed(stored)) = db.map.get_owned(&key) else {
+            panic!("the value is not compressed");
+        };
+        assert!(Value::Compressed(stored.clone()).deep_size_of() < value.deep_size_of() / 10);
+        assert_eq!(stored.decompress(), value);
+        let reply = execute(&db, CommandEntry::Get(Get { key: key.clone() })).await;
+        assert_eq!(reply, vec![value.clone()]);
+
+        // Commands inspecting the value see through the compression.
+        let number = Value::String(Cow::Owned(format!("{:0>100}", 41)));
+        execute(
+            &db,
+            CommandEntry::Set(Set {
+                key: key.clone(),
+                value: number,
+            }),
+        )
+        .await;
+        assert!(matches!(db.map.get_owned(&key), Some(Value::Compressed(_))));
+        let reply = execute(&db, CommandEntry::Incr(Incr { key: key.clone() })).await;
+        assert_eq!(reply, vec![Value::Positive(42)]);
+
+        execute(
+            &db,
+            CommandEntry::Set(Set {
+                key: key.clone(),
+                value: Value::Bytes(Cow::Borrowed(b"small")),
+            }),
+        )
+        .await;
+        assert_eq!(
+            db.map.get_owned(&key),
+            Some(Value::Bytes(Cow::Borrowed(b"small")))
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use bytes::BytesMut;
+use nom::AsBytes;
+
+use crate::{
+    db::{glob, Db},
+    error::ProtocolError,
+    protocol::{parse, parse_all, Value},
+};
+
+/// Frame a single key is saved as: `[key, value]`.
+pub fn key_frame(key: &[u8], value: Value<'_>) -> BytesMut {
+    Value::Array(vec![Value::Bytes(Cow::Borrowed(key)), value]).encode()
+}
+
+/// `name` as the name of a file in [`Db::data_dir`], `None` unless it is a bare file name.
+///
+/// Clients only ever name files inside the data directory, so names with a directory part,
+/// `.`, `..`, or an absolute path are rejected rather than letting a client write anywhere the
+/// server can.
+pub fn file_name(name: &str) -> Option<PathBuf> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(file)), None) if file == name => Some(PathBuf::from(name)),
+        _ => None,
+    }
+}
+
+/// Replaces the file at `path` with `contents`, so readers see either the old or the new file,
+/// never a partial one.
+///
+/// The contents are written to a temporary file next to `path`, synced, and renamed over it.
+/// Every write uses its own temporary file, so concurrent writes to the same path never mix.
+/// The directory is synced after the rename, so the new file survives a crash.
+pub async fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let file = async {
+        tokio::fs::write(&temp, contents).await?;
+        tokio::fs::File::open(&temp).await?.sync_all().await?;
+        tokio::fs::rename(&temp, path).await
+    };
+    let result = file.await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp).await;
+        return result;
+    }
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    tokio::fs::File::open(dir).await?.sync_all().await
+}
+
+impl Db {
+    /// Saves the key to `path` as a [`key_frame`], replacing the file atomically. Returns
+    /// `false` without touching the file if the key does not exist.
+    pub async fn save_key(&self, key: &[u8], path: &Path) -> std::io::Result<bool> {
+        let Some(value) = self.map.get_owned(key) else {
+            return Ok(false);
+        };
+        write_atomically(path, &key_frame(key, value)).await?;
+        Ok(true)
+    }
+
+    /// Loads a key saved with [`Db::save_key`], overwriting its current value.
+    pub async fn load_key(&self, path: &Path) -> crate::error::Result<()> {
+        let contents = tokio::fs::read(path).await?;
+        let (_, frame) = parse(&contents)?;
+        let Value::Array(mut frame) = frame else {
+            return Err(ProtocolError::Command);
+        };
+        let (Some(value), Some(Value::Bytes(key)), None) = (frame.pop(), frame.pop(), frame.pop())
+        else {
+            return Err(ProtocolError::Command);
+        };
+        self.bulk_load([(BytesMut::from(key.as_bytes()), value.to_owned())]);
+        Ok(())
+    }
+
+    /// Saves every key matching the glob `pattern`, see [`glob::matches`], to `path` as
+    /// consecutive [`key_frame`]s, replacing the file atomically. Returns the number of keys
+    /// saved.
+    ///
+    /// Shards are walked one at a time, so writers are only held up while their shard is
+    /// searched, but keys written meanwhile may or may not be saved.
+    pub async fn save_matching(&self, pattern: &[u8], path: &Path) -> std::io::Result<u64> {
+        let mut contents = BytesMut::new();
+        let mut saved = 0;
+        self.map.for_each(|key, value| {
+            if glob::matches(pattern, key) {
+                contents.extend_from_slice(&key_frame(key, value.clone()));
+                saved += 1;
+            }
+        });
+        write_atomically(path, &contents).await?;
+        Ok(saved)
+    }
+
+    /// Loads every key saved with [`Db::save_matching`], overwriting their current values.
+    pub async fn load_keys(&self, path: &Path) -> crate::error::Result<()> {
+        self.bulk_load(load_snapshot(path).await?);
+        Ok(())
+    }
+}
+
+/// Reads the keys of a file written by [`Db::save_matching`] without loading them.
+pub async fn load_snapshot(path: &Path) -> crate::error::Result<Vec<(BytesMut, Value<'static>)>> {
+    let contents = tokio::fs::read(path).await?;
+    parse_all(&contents)?
+        .into_iter()
+        .map(|frame| match frame {
+            Value::Array(frame) => match <[Value<'_>; 2]>::try_from(frame) {
+                Ok([Value::Bytes(key), value]) => {
+                    Ok((BytesMut::from(key.as_bytes()), value.to_owned()))
+                }
+                _ => Err(ProtocolError::Command),
+            },
+            _ => Err(ProtocolError::Command),
+        })
+        .collect()
+}
+
+/// Keys which differ between the snapshots at `old` and `new`, sorted: added and changed keys
+/// with their new value, removed keys with `None`. Applying the result to a database holding
+/// `old` makes it hold `new`, so only the difference has to be shipped for incremental backups.
+pub async fn diff_snapshots(
+    old: &Path,
+    new: &Path,
+) -> crate::error::Result<Vec<(BytesMut, Option<Value<'static>>)>> {
+    let mut old: HashMap<_, _> = load_snapshot(old).await?.into_iter().collect();
+    let mut diff = Vec::new();
+    for (key, value) in load_snapshot(new).await? {
+        if old.remove(&key).as_ref() != Some(&value) {
+            diff.push((key, Some(value)));
+        }
+    }
+    diff.extend(old.into_keys().map(|key| (key, None)));
+    diff.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use bytes::BytesMut;
+
+    use crate::{
+        db::{
+            persist::{diff_snapshots, write_atomically},
+            Db,
+        },
+        protocol::Value,
+    };
+
+    #[tokio::test]
+    async fn diff() {
+        let dir = std::env::temp_dir();
+        let old = dir.join(format!("kvs-diff-old-{}", std::process::id()));
+        let new = dir.join(format!("kvs-diff-new-{}", std::process::id()));
+        let db = Db::default();
+        db.bulk_load([
+            (BytesMut::from("kept"), Value::Positive(1)),
+            (BytesMut::from("changed"), Value::Positive(2)),
+            (BytesMut::from("removed"), Value::Positive(3)),
+        ]);
+        db.save_matching(b"*", &old).await.unwrap();
+        db.map.remove(b"removed".as_slice());
+        db.bulk_load([
+            (
+                BytesMut::from("changed"),
+                Value::String(Cow::Borrowed("two")),
+            ),
+            (BytesMut::from("added"), Value::Positive(4)),
+        ]);
+        db.save_matching(b"*", &new).await.unwrap();
+
+        let diff = diff_snapshots(&old, &new).await.unwrap();
+        assert_eq!(
+            diff,
+            vec![
+                (BytesMut::from("added"), Some(Value::Positive(4))),
+                (
+                    BytesMut::from("changed"),
+                    Some(Value::String(Cow::Borrowed("two")))
+                ),
+                (BytesMut::from("removed"), None),
+            ]
+        );
+        assert_eq!(diff_snapshots(&new, &new).await.unwrap(), vec![]);
+
+        let _ = std::fs::remove_file(old);
+        let _ = std::fs::remove_file(new);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_writes() {
+        let path = std::env::temp_dir().join(format!("kvs-atomic-{}", std::process::id()));
+        let contents: Vec<_> = (0..8u8).map(|n| vec![n; 64 * 1024]).collect();
+        let writes: Vec<_> = contents
+            .iter()
+            .cloned()
+            .map(|contents| {
+                let path = path.clone();
+                tokio::spawn(async move { write_atomically(&path, &contents).await })
+            })
+            .collect();
+        for write in writes {
+            write.await.unwrap().unwrap();
+        }
+        let written = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(contents.contains(&written));
+    }
+}
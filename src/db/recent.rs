@@ -0,0 +1,154 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use bytes::BytesMut;
+
+pub const DEFAULT_MAX_LEN: usize = 128;
+
+/// Bounded list of the most recently modified keys, newest first.
+///
+/// Every mutating command records the keys it changed, so clients can poll for changes to
+/// invalidate their caches. A key modified again moves to the front instead of being listed
+/// twice.
+///
+/// Recording is on the path of every write, so it takes constant time: a key modified again is
+/// not searched for, its older entry is only skipped as stale from then on.
+pub struct RecentKeys {
+    max_len: AtomicUsize,
+    log: Mutex<Log>,
+}
+
+#[derive(Default)]
+struct Log {
+    /// Sequence number of the next record.
+    next: u64,
+    /// Sequence number of the latest record of every listed key.
+    latest: HashMap<BytesMut, u64>,
+    /// Records, newest first. Those of keys recorded again later are stale.
+    records: VecDeque<(u64, BytesMut)>,
+}
+
+impl Log {
+    fn is_live(&self, (seq, key): &(u64, BytesMut)) -> bool {
+        self.latest.get(key) == Some(seq)
+    }
+
+    /// Drops the oldest keys past `max_len`, and the stale records once they outnumber the
+    /// live ones, so the records stay within twice the limit.
+    fn trim(&mut self, max_len: usize) {
+        while self.latest.len() > max_len {
+            let Some(record) = self.records.pop_back() else {
+                break;
+            };
+            if self.is_live(&record) {
+                self.latest.remove(&record.1);
+            }
+        }
+        if self.records.len() > 2 * max_len {
+            let records = std::mem::take(&mut self.records);
+            self.records = records
+                .into_iter()
+                .filter(|record| self.is_live(record))
+                .collect();
+        }
+    }
+}
+
+impl RecentKeys {
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            max_len: AtomicUsize::new(max_len),
+            log: Mutex::default(),
+        }
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len.load(Ordering::Relaxed)
+    }
+
+    /// Changes the length limit, dropping the oldest keys which no longer fit.
+    pub fn set_max_len(&self, max_len: usize) {
+        let mut log = self.log.lock().unwrap();
+        self.max_len.store(max_len, Ordering::Relaxed);
+        log.trim(max_len);
+    }
+
+    pub fn record(&self, key: &BytesMut) {
+        let mut log = self.log.lock().unwrap();
+        let max_len = self.max_len();
+        if max_len == 0 {
+            return;
+        }
+        let seq = log.next;
+        log.next += 1;
+        log.latest.insert(key.clone(), seq);
+        log.records.push_front((seq, key.clone()));
+        log.trim(max_len);
+    }
+
+    /// Returns up to `count` most recently modified keys, newest first.
+    pub fn latest(&self, count: usize) -> Vec<BytesMut> {
+        let log = self.log.lock().unwrap();
+        log.records
+            .iter()
+            .filter(|record| log.is_live(record))
+            .take(count)
+            .map(|(_, key)| key.clone())
+            .collect()
+    }
+}
+
+impl Default for RecentKeys {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::RecentKeys;
+
+    #[test]
+    fn bounded_and_deduplicated() {
+        let recent = RecentKeys::new(2);
+        for key in ["a", "b", "a", "c"] {
+            recent.record(&BytesMut::from(key));
+        }
+        assert_eq!(
+            recent.latest(10),
+            vec![BytesMut::from("c"), BytesMut::from("a")]
+        );
+        assert_eq!(recent.latest(1), vec![BytesMut::from("c")]);
+    }
+
+    #[test]
+    fn stale_records_are_dropped() {
+        let recent = RecentKeys::new(3);
+        for _ in 0..1000 {
+            for key in ["a", "b"] {
+                recent.record(&BytesMut::from(key));
+            }
+        }
+        assert!(recent.log.lock().unwrap().records.len() <= 6);
+        recent.record(&BytesMut::from("c"));
+        recent.record(&BytesMut::from("a"));
+        assert_eq!(
+            recent.latest(10),
+            vec![
+                BytesMut::from("a"),
+                BytesMut::from("c"),
+                BytesMut::from("b")
+            ]
+        );
+
+        recent.set_max_len(1);
+        assert_eq!(recent.latest(10), vec![BytesMut::from("a")]);
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub const DEFAULT_THRESHOLD: Duration = Duration::from_millis(10);
+pub const DEFAULT_MAX_LEN: usize = 128;
+
+/// A command which took longer than the slowlog threshold to execute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowLogEntry {
+    /// Unique, increasing id of the entry.
+    pub id: u64,
+    /// Unix time in seconds at which the command finished.
+    pub timestamp: u64,
+    /// Name the command is registered under.
+    pub command: &'static str,
+    pub duration: Duration,
+}
+
+#[derive(Default)]
+struct Entries {
+    next_id: u64,
+    entries: VecDeque<SlowLogEntry>,
+}
+
+/// Bounded ring buffer of commands exceeding a latency threshold.
+///
+/// Once `max_len` entries are recorded, the oldest entry is dropped for every new one.
+///
+/// The threshold and the length limit can be changed at runtime, see [`crate::db::config`].
+pub struct SlowLogBuffer {
+    threshold_micros: AtomicU64,
+    max_len: AtomicUsize,
+    entries: Mutex<Entries>,
+}
+
+impl SlowLogBuffer {
+    pub fn new(threshold: Duration, max_len: usize) -> Self {
+        Self {
+            threshold_micros: AtomicU64::new(threshold.as_micros() as u64),
+            max_len: AtomicUsize::new(max_len),
+            entries: Mutex::default(),
+        }
+    }
+
+    pub fn threshold(&self) -> Duration {
+        Duration::from_micros(self.threshold_micros.load(Ordering::Relaxed))
+    }
+
+    pub fn set_threshold(&self, threshold: Duration) {
+        self.threshold_micros
+            .store(threshold.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len.load(Ordering::Relaxed)
+    }
+
+    /// Changes the length limit, dropping the oldest entries which no longer fit.
+    pub fn set_max_len(&self, max_len: usize) {
+        let mut entries = self.entries.lock().unwrap();
+        self.max_len.store(max_len, Ordering::Relaxed);
+        entries.entries.truncate(max_len);
+    }
+
+    /// Records `command` if `duration` exceeds the threshold.
+    pub fn record(&self, command: &'static str, duration: Duration) {
+        if duration < self.threshold() {
+            return;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_secs())
+            .unwrap_or_default();
+        let mut entries = self.entries.lock().unwrap();
+        let max_len = self.max_len();
+        if max_len == 0 {
+            return;
+        }
+        let id = entries.next_id;
+        entries.next_id += 1;
+        entries.entries.truncate(max_len - 1);
+        entries.entries.push_front(SlowLogEntry {
+            id,
+            timestamp,
+            command,
+            duration,
+        });
+    }
+
+    /// Returns recorded entries, newest first.
+    pub fn entries(&self) -> Vec<SlowLogEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn reset(&self) {
+        self.entries.lock().unwrap().entries.clear();
+    }
+}
+
+impl Default for SlowLogBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_THRESHOLD, DEFAULT_MAX_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::SlowLogBuffer;
+
+    #[test]
+    fn below_threshold() {
+        let slowlog = SlowLogBuffer::new(Duration::from_millis(10), 2);
+        slowlog.record("GET", Duration::from_millis(1));
+        assert!(slowlog.is_empty());
+    }
+
+    #[test]
+    fn bounded() {
+        let slowlog = SlowLogBuffer::new(Duration::ZERO, 2);
+        slowlog.record("GET", Duration::from_millis(1));
+        slowlog.record("SET", Duration::from_millis(2));
+        slowlog.record("INCR", Duration::from_millis(3));
+        let entries = slowlog.entries();
+        assert_eq!(
+            entries.iter().map(|e| e.command).collect::<Vec<_>>(),
+            vec!["INCR", "SET"]
+        );
+        assert_eq!(entries[0].id, 2);
+        slowlog.reset();
+        assert!(slowlog.is_empty());
+    }
+
+    #[test]
+    fn shrink() {
+        let slowlog = SlowLogBuffer::new(Duration::ZERO, 3);
+        slowlog.record("GET", Duration::from_millis(1));
+        slowlog.record("SET", Duration::from_millis(2));
+        slowlog.set_max_len(1);
+        assert_eq!(
+            slowlog
+                .entries()
+                .iter()
+                .map(|e| e.command)
+                .collect::<Vec<_>>(),
+            vec!["SET"]
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::hash::BuildHasher;
+
+use bytes::BytesMut;
+
+use crate::{db::map::Map, error::Result, protocol::Value};
+
+/// Key-value storage commands can run against.
+///
+/// [`Map`] is the storage of [`crate::db::Db`]. Commands only needing whole-value reads and
+/// writes, like `GET` and `SET`, implement [`crate::command::StorageCommand`], so they can run on
+/// other backends, e.g. a mock in tests. Commands modifying values in place or locking several
+/// keys together still need the [`Map`] and its locks.
+///
+/// Operations may fail with [`crate::error::ProtocolError::Retryable`] on transient failures,
+/// after which they must have had no effect, so they can be retried. The [`Map`] never fails.
+pub trait Storage: Send + Sync {
+    /// Returns a copy of the value at `key`.
+    fn get(&self, key: &[u8]) -> Result<Option<Value<'static>>>;
+    /// Stores `value` at `key`, returning the previous value.
+    fn set(&self, key: BytesMut, value: Value<'static>) -> Result<Option<Value<'static>>>;
+    fn remove(&self, key: &[u8]) -> Result<Option<Value<'static>>>;
+    fn len(&self) -> Result<usize>;
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+    /// Removes every key.
+    fn clear(&self) -> Result<()>;
+    /// Returns up to `count` keys from `cursor` on and the cursor to continue from, 0 once every
+    /// key was returned.
+    fn scan(&self, cursor: u64, count: usize) -> Result<(u64, Vec<BytesMut>)>;
+}
+
+impl<S> Storage for Map<BytesMut, Value<'static>, S>
+where
+    S: BuildHasher + Send + Sync,
+{
+    fn get(&self, key: &[u8]) -> Result<Option<Value<'static>>> {
+        Ok(self.get_owned(key))
+    }
+
+    fn set(&self, key: BytesMut, value: Value<'static>) -> Result<Option<Value<'static>>> {
+        Ok(self.insert(key, value))
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<Option<Value<'static>>> {
+        Ok(Map::remove(self, key))
+    }
+
+    fn len(&self) -> Result<usize> {
+        Ok(Map::len(self))
+    }
+
+    fn clear(&self) -> Result<()> {
+        Map::clear(self);
+        Ok(())
+    }
+
+    fn scan(&self, cursor: u64, count: usize) -> Result<(u64, Vec<BytesMut>)> {
+        Ok(Map::scan(self, cursor, count, |_, _| true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use bytes::BytesMut;
+
+    use crate::{
+        command::{get::Get, set::Set},
+        db::{storage::Storage, Db},
+        protocol::{Value, EMPTY},
+        testing::{execute_on, MockStorage},
+    };
+
+    #[tokio::test]
+    async fn get_and_set_on_mock() {
+        let db = Db::default();
+        let storage = MockStorage::default();
+        let set = |key: &str, value| {
+            execute_on(
+                &db,
+                &storage,
+                Set {
+                    key: BytesMut::from(key),
+                    value,
+                },
+            )
+        };
+        let get = |key: &str| {
+            execute_on(
+                &db,
+                &storage,
+                Get {
+                    key: BytesMut::from(key),
+                },
+            )
+        };
+        assert_eq!(set("a", Value::Positive(1)).await, vec![]);
+        set("b", Value::String(Cow::Borrowed("two"))).await;
+        set("a", Value::Positive(3)).await;
+
+        assert_eq!(get("a").await, vec![Value::Positive(3)]);
+        assert_eq!(get("b").await, vec![Value::String(Cow::Borrowed("two"))]);
+        assert_eq!(get("c").await, vec![Value::Error(Cow::Borrowed(EMPTY))]);
+        assert!(db.map.is_empty());
+        assert_eq!(storage.len().unwrap(), 2);
+        assert_eq!(storage.scan(0, 1).unwrap(), (1, vec![BytesMut::from("a")]));
+        assert_eq!(storage.scan(1, 1).unwrap(), (0, vec![BytesMut::from("b")]));
+        storage.clear().unwrap();
+        assert!(storage.is_empty().unwrap());
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use bytes::BytesMut;
+
+use crate::protocol::Value;
+
+pub const DEFAULT_MAX_LEN: usize = 1024;
+
+/// Bounded cache of the replies of writes made with an idempotency token, oldest dropped first.
+///
+/// A client retrying a write it did not get the reply for sends the same token again and gets
+/// the recorded reply, instead of having the write applied twice.
+pub struct IdempotencyTokens {
+    max_len: usize,
+    tokens: Mutex<Tokens>,
+}
+
+#[derive(Default)]
+struct Tokens {
+    replies: HashMap<BytesMut, Value<'static>>,
+    /// Tokens in the order they were recorded, oldest first.
+    order: VecDeque<BytesMut>,
+}
+
+impl IdempotencyTokens {
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            tokens: Mutex::default(),
+        }
+    }
+
+    /// Returns the reply recorded for `token`, or runs `write` and records its reply if the token
+    /// is new.
+    ///
+    /// `write` runs with the cache locked, so a token replayed while its write is still running
+    /// waits for it and gets the same reply.
+    pub fn apply_once(
+        &self,
+        token: &BytesMut,
+        write: impl FnOnce() -> Value<'static>,
+    ) -> Value<'static> {
+        let mut tokens = self.tokens.lock().unwrap();
+        if let Some(reply) = tokens.replies.get(token) {
+            return reply.clone();
+        }
+        let reply = write();
+        if self.max_len == 0 {
+            return reply;
+        }
+        if tokens.order.len() == self.max_len {
+            if let Some(oldest) = tokens.order.pop_front() {
+                tokens.replies.remove(&oldest);
+            }
+        }
+        tokens.order.push_back(token.clone());
+        tokens.replies.insert(token.clone(), reply.clone());
+        reply
+    }
+}
+
+impl Default for IdempotencyTokens {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use crate::protocol::Value;
+
+    use super::IdempotencyTokens;
+
+    #[test]
+    fn oldest_token_dropped() {
+        let tokens = IdempotencyTokens::new(2);
+        for (i, token) in ["a", "b", "c"].into_iter().enumerate() {
+            tokens.apply_once(&BytesMut::from(token), || Value::Positive(i as u64));
+        }
+        let replay = |token| tokens.apply_once(&BytesMut::from(token), || Value::Positive(42));
+        assert_eq!(replay("c"), Value::Positive(2));
+        assert_eq!(replay("a"), Value::Positive(42));
+    }
+}
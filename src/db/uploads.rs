@@ -0,0 +1,147 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::error::CommandError;
+
+/// Most uploads pending at once.
+pub const MAX_PENDING: usize = 64;
+/// Uploads which are not written to for this long are dropped.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Values being uploaded in chunks, so large values do not have to fit in a single frame.
+///
+/// `RESERVE` opens an upload of a fixed size and returns its handle, `WRITECHUNK` copies bytes
+/// into it and `COMMIT` takes the assembled value out to store it. Handles are shared by every
+/// connection. Uploads which are never committed are dropped once idle for [`IDLE_TIMEOUT`].
+#[derive(Default)]
+pub struct Uploads {
+    next: AtomicU64,
+    pending: Mutex<HashMap<u64, Upload>>,
+}
+
+struct Upload {
+    size: usize,
+    /// Bytes up to the end of the furthest chunk written, the rest is filled in on commit.
+    data: Vec<u8>,
+    last_write: Instant,
+}
+
+impl Uploads {
+    /// Opens an upload of `size` bytes as of `now` and returns its handle.
+    ///
+    /// Chunks may be written anywhere within the reservation, so up to `size` bytes are
+    /// allocated. Fails with [`CommandError::LimitExceeded`] if the sizes of the pending uploads
+    /// would add up to more than `max_size`, or [`MAX_PENDING`] uploads are already open.
+    pub fn reserve(&self, size: usize, max_size: usize, now: Instant) -> Result<u64, CommandError> {
+        let mut pending = self.pending.lock().unwrap();
+        Self::expire(&mut pending, now);
+        let reserved = pending.values().map(|upload| upload.size).sum::<usize>();
+        if pending.len() >= MAX_PENDING || size > max_size.saturating_sub(reserved) {
+            return Err(CommandError::LimitExceeded);
+        }
+        let handle = self.next.fetch_add(1, Ordering::Relaxed) + 1;
+        let upload = Upload {
+            size,
+            data: vec![],
+            last_write: now,
+        };
+        pending.insert(handle, upload);
+        Ok(handle)
+    }
+
+    /// Copies `bytes` into the upload at `offset`. `false` if there is no such upload or the
+    /// chunk does not fit into its size.
+    pub fn write(&self, handle: u64, offset: usize, bytes: &[u8], now: Instant) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        Self::expire(&mut pending, now);
+        let Some(upload) = pending.get_mut(&handle) else {
+            return false;
+        };
+        let Some(end) = offset
+            .checked_add(bytes.len())
+            .filter(|&end| end <= upload.size)
+        else {
+            return false;
+        };
+        if upload.data.len() < end {
+            upload.data.resize(end, 0);
+        }
+        upload.data[offset..end].copy_from_slice(bytes);
+        upload.last_write = now;
+        true
+    }
+
+    /// Closes the upload and returns its bytes, zeroes where no chunk was written.
+    pub fn take(&self, handle: u64, now: Instant) -> Option<Vec<u8>> {
+        let mut pending = self.pending.lock().unwrap();
+        Self::expire(&mut pending, now);
+        let Upload { size, mut data, .. } = pending.remove(&handle)?;
+        data.resize(size, 0);
+        Some(data)
+    }
+
+    /// Drops the uploads idle for longer than [`IDLE_TIMEOUT`] as of `now`.
+    fn expire(pending: &mut HashMap<u64, Upload>, now: Instant) {
+        pending
+            .retain(|_, upload| now.saturating_duration_since(upload.last_write) <= IDLE_TIMEOUT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::error::CommandError;
+
+    use super::{Uploads, IDLE_TIMEOUT, MAX_PENDING};
+
+    #[test]
+    fn limits() {
+        let uploads = Uploads::default();
+        let now = Instant::now();
+        assert_eq!(
+            uploads.reserve(101, 100, now),
+            Err(CommandError::LimitExceeded)
+        );
+        let handle = uploads.reserve(60, 100, now).unwrap();
+        assert_eq!(
+            uploads.reserve(41, 100, now),
+            Err(CommandError::LimitExceeded)
+        );
+
+        // Committing frees the reservation.
+        assert_eq!(uploads.take(handle, now), Some(vec![0; 60]));
+        for _ in 0..MAX_PENDING {
+            uploads.reserve(0, 100, now).unwrap();
+        }
+        assert_eq!(
+            uploads.reserve(0, 100, now),
+            Err(CommandError::LimitExceeded)
+        );
+    }
+
+    #[test]
+    fn idle_uploads_expire() {
+        let uploads = Uploads::default();
+        let start = Instant::now();
+        let idle = uploads.reserve(100, 100, start).unwrap();
+        let active = uploads.reserve(0, 100, start + IDLE_TIMEOUT / 2).unwrap();
+
+        // Writing keeps an upload alive.
+        assert!(uploads.write(active, 0, b"", start + IDLE_TIMEOUT));
+        let later = start + IDLE_TIMEOUT + IDLE_TIMEOUT / 4;
+        assert!(uploads.write(active, 0, b"", later));
+        assert!(!uploads.write(idle, 0, b"a", later));
+        assert_eq!(uploads.take(idle, later), None);
+
+        // Its reservation is free again.
+        assert!(uploads.reserve(100, 100, later).is_ok());
+        assert_eq!(uploads.take(active, later), Some(vec![]));
+    }
+}
@@ -0,0 +1,76 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use bytes::BytesMut;
+use tokio::sync::Notify;
+
+/// Clients blocked until one of their keys is written to.
+///
+/// A blocking command registers a single [`Notify`] under every key it waits on, every write to
+/// a key wakes everyone registered under it, see [`crate::db::Db::modified`]. Woken clients race
+/// for what was written, and the write may not be what they wait for, so a waiter has to check
+/// its keys again after every wake-up.
+#[derive(Default)]
+pub struct KeyWaiters {
+    waiters: Mutex<HashMap<BytesMut, Vec<Arc<Notify>>>>,
+}
+
+impl KeyWaiters {
+    /// Registers a waiter for `keys`; it stays registered until the returned guard is dropped.
+    ///
+    /// Register before checking the keys: a push in between stores a permit in the [`Notify`],
+    /// so the wake-up is not lost.
+    pub fn register<'a>(&'a self, keys: &'a [BytesMut]) -> Registration<'a> {
+        let notify = Arc::new(Notify::new());
+        let mut waiters = self.waiters.lock().unwrap();
+        for key in keys {
+            waiters.entry(key.clone()).or_default().push(notify.clone());
+        }
+        Registration {
+            waiters: self,
+            keys,
+            notify,
+        }
+    }
+
+    /// Wakes every client waiting on `key`.
+    pub fn notify(&self, key: &[u8]) {
+        if let Some(waiters) = self.waiters.lock().unwrap().get(key) {
+            waiters.iter().for_each(|notify| notify.notify_one());
+        }
+    }
+
+    fn unregister(&self, keys: &[BytesMut], notify: &Arc<Notify>) {
+        let mut waiters = self.waiters.lock().unwrap();
+        for key in keys {
+            if let Some(registered) = waiters.get_mut(key) {
+                registered.retain(|n| !Arc::ptr_eq(n, notify));
+                if registered.is_empty() {
+                    waiters.remove(key);
+                }
+            }
+        }
+    }
+}
+
+/// Waiter registered by [`KeyWaiters::register`].
+pub struct Registration<'a> {
+    waiters: &'a KeyWaiters,
+    keys: &'a [BytesMut],
+    notify: Arc<Notify>,
+}
+
+impl Registration<'_> {
+    /// Waits until one of the keys is pushed to.
+    pub async fn notified(&self) {
+        self.notify.notified().await
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.waiters.unregister(self.keys, &self.notify);
+    }
+}
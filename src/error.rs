@@ -1,37 +1,153 @@
 use thiserror::Error;
 
+use crate::protocol::Value;
+
 pub type Result<T> = std::result::Result<T, ProtocolError>;
 
 #[derive(Debug, Error)]
 pub enum ProtocolError {
     #[error("")]
     Read(#[from] tokio::io::Error),
+    /// The stream ended in the middle of a frame.
     #[error("")]
     ZeroRead,
+    /// The peer closed the stream cleanly, between frames.
+    #[error("")]
+    ConnectionClosed,
     #[error("")]
     Parse(#[from] nom::Err<ParseError>),
     #[error("")]
     Command,
+    /// The command has more arguments than [`crate::db::config::Parameter::MaxArgs`].
+    #[error("")]
+    TooManyArguments,
+    /// A transient failure of a [`crate::db::storage::Storage`] operation, e.g. contention or
+    /// an I/O error of a disk backend. Commands retry the operation before replying, see
+    /// [`crate::command::retry`].
+    #[error("")]
+    Retryable,
 }
 
-#[derive(Debug, Error)]
+/// Errors commands reply with, each counted separately, see [`crate::db::errors`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    /// The request is not a valid command.
+    Parse,
+    /// The value at the key has a type the command can not operate on.
+    WrongType,
+    /// The value at the key is not a number.
+    NotANumber,
+    /// The result does not fit into an integer value.
+    OutOfRange,
+    /// `HELLO` asked for a protocol version the server does not speak.
+    UnsupportedProtocol,
+    /// The reply would exceed a configured limit, see [`crate::db::config::Limits`].
+    TooLarge,
+    /// Reading or writing a file on the server failed.
+    Io,
+    /// The command has more arguments than allowed, see [`ProtocolError::TooManyArguments`].
+    TooManyArguments,
+    /// The command kept failing with [`ProtocolError::Retryable`].
+    Unavailable,
+    /// The result would exceed the limit the command was given.
+    LimitExceeded,
+}
+
+impl CommandError {
+    pub const ALL: &'static [Self] = &[
+        Self::Parse,
+        Self::WrongType,
+        Self::NotANumber,
+        Self::OutOfRange,
+        Self::UnsupportedProtocol,
+        Self::TooLarge,
+        Self::Io,
+        Self::TooManyArguments,
+        Self::Unavailable,
+        Self::LimitExceeded,
+    ];
+
+    /// Name the error is reported under in error statistics.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Parse => "PARSE",
+            Self::WrongType => "WRONGTYPE",
+            Self::NotANumber => "NOTANUMBER",
+            Self::OutOfRange => "OUTOFRANGE",
+            Self::UnsupportedProtocol => "NOPROTO",
+            Self::TooLarge => "TOOLARGE",
+            Self::Io => "IO",
+            Self::TooManyArguments => "TOOMANYARGS",
+            Self::Unavailable => "UNAVAILABLE",
+            Self::LimitExceeded => "LIMIT",
+        }
+    }
+
+    /// Message of the error reply, which is prefixed with [`CommandError::name`] as its code.
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Parse => "Can not parse the command",
+            Self::WrongType => "Wrong kind of value",
+            Self::NotANumber => "Not a number",
+            Self::OutOfRange => "Out of range",
+            Self::UnsupportedProtocol => "Unsupported protocol",
+            Self::TooLarge => "Reply too large",
+            Self::Io => "Can not access the file",
+            Self::TooManyArguments => "Too many arguments",
+            Self::Unavailable => "Try again later",
+            Self::LimitExceeded => "Limit exceeded",
+        }
+    }
+}
+
+impl From<CommandError> for Value<'static> {
+    fn from(error: CommandError) -> Self {
+        Value::error_with_code(error.name(), error.message())
+    }
+}
+
+#[derive(Debug, Default, Error)]
 #[error("")]
 #[from(tokio::io::Error)]
-pub struct ParseError;
+pub struct ParseError {
+    /// Length of the input left where parsing failed, if known, so the failure is at the input
+    /// length minus `remaining`.
+    pub remaining: Option<usize>,
+}
+
+impl ParseError {
+    /// Failure at the start of `input`.
+    pub fn at(input: &[u8]) -> Self {
+        Self {
+            remaining: Some(input.len()),
+        }
+    }
+}
 
 impl nom::error::ParseError<&[u8]> for ParseError {
-    fn from_error_kind(_: &[u8], _: nom::error::ErrorKind) -> Self {
-        Self
+    fn from_error_kind(input: &[u8], _: nom::error::ErrorKind) -> Self {
+        Self::at(input)
     }
 
-    fn append(_: &[u8], _: nom::error::ErrorKind, _: Self) -> Self {
-        Self
+    /// Keeps the innermost failure, which is where parsing actually stopped.
+    fn append(_: &[u8], _: nom::error::ErrorKind, other: Self) -> Self {
+        other
     }
 }
 
 impl nom::error::FromExternalError<&[u8], std::str::Utf8Error> for ParseError {
-    fn from_external_error(_: &[u8], _: nom::error::ErrorKind, _: std::str::Utf8Error) -> Self {
-        Self
+    fn from_external_error(input: &[u8], _: nom::error::ErrorKind, _: std::str::Utf8Error) -> Self {
+        Self::at(input)
+    }
+}
+
+impl nom::error::FromExternalError<&[u8], std::num::TryFromIntError> for ParseError {
+    fn from_external_error(
+        input: &[u8],
+        _: nom::error::ErrorKind,
+        _: std::num::TryFromIntError,
+    ) -> Self {
+        Self::at(input)
     }
 }
 
@@ -1,4 +1,8 @@
 pub mod codec;
 pub mod command;
+pub mod db;
 pub mod error;
 pub mod protocol;
+
+#[cfg(test)]
+mod testing;
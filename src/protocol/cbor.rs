@@ -0,0 +1,452 @@
+//! Conversion between [`Value`] and standard CBOR (RFC 8949).
+//!
+//! The framing of this crate borrows the CBOR layout, but differs from it where it matters for
+//! other tools: integers and lengths of 24 or more follow the header in 1 to 8 bytes rather than
+//! 1, 2, 4 or 8, arrays and maps longer than 30 are always indefinite, major type 5 holds errors
+//! rather than maps, maps are stored under major type 6, and comments under a float header.
+//! Values converted here use the standard layout instead, so any CBOR library can read them:
+//!
+//! - integers, bytes, strings, arrays and maps use their CBOR major types, with lengths in the
+//!   following 1, 2, 4 or 8 bytes where needed,
+//! - floats are written as double precision floats, half and single precision ones are read as
+//!   well,
+//! - nil, i.e. an error with the [`EMPTY`] message, is `null`,
+//! - other errors and comments are text strings wrapped in [`ERROR_TAG`] and [`COMMENT_TAG`].
+//!
+//! Map keys are kept encoded in a [`Value::Map`], so they are parsed and converted as well.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use bytes::BytesMut;
+
+use crate::{
+    error::{ParseError, ProtocolError},
+    protocol::{parse, Value, EMPTY},
+};
+
+/// Tag marking a text string as an error, from the first come first served range of the IANA
+/// CBOR tag registry, reading `kvse` in ASCII.
+pub const ERROR_TAG: u64 = 0x6B76_7365;
+/// Tag marking a text string as a comment, reading `kvsc` in ASCII.
+pub const COMMENT_TAG: u64 = 0x6B76_7363;
+
+const UNSIGNED: u8 = 0;
+const NEGATIVE: u8 = 1;
+const BYTES: u8 = 2;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+const TAG: u8 = 6;
+const SIMPLE: u8 = 7;
+
+/// Simple value `null`.
+const NULL: u64 = 22;
+/// Heads of half, single and double precision floats, followed by their 2, 4 or 8 bytes.
+const HALF: u8 = SIMPLE << 5 | 25;
+const SINGLE: u8 = SIMPLE << 5 | 26;
+const DOUBLE: u8 = SIMPLE << 5 | 27;
+const BREAK: u8 = 0xFF;
+
+impl Value<'_> {
+    /// Encodes the value as standard CBOR, see [`crate::protocol::cbor`].
+    ///
+    /// Map entries are emitted sorted by their keys as encoded by this crate, so equal maps
+    /// always produce the same bytes.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        write_value(self, &mut buf);
+        buf
+    }
+
+    /// Decodes a single standard CBOR item which has to span the whole `input`, see
+    /// [`crate::protocol::cbor`].
+    ///
+    /// Indefinite lengths are accepted. Half and single precision floats are widened to a
+    /// [`Value::Float`]. Items without a counterpart here, like booleans, integers beyond 64 bits
+    /// or unknown tags, fail with [`ProtocolError::Parse`].
+    pub fn from_cbor(input: &[u8]) -> crate::error::Result<Value<'static>> {
+        let mut decoder = Decoder { input };
+        match decoder.value() {
+            Some(value) if decoder.input.is_empty() => Ok(value),
+            _ => Err(ProtocolError::Parse(nom::Err::Failure(
+                ParseError::default(),
+            ))),
+        }
+    }
+}
+
+fn write_head(major: u8, argument: u64, buf: &mut Vec<u8>) {
+    let major = major << 5;
+    match argument {
+        0..=23 => buf.push(major | argument as u8),
+        24..=0xFF => buf.extend([major | 24, argument as u8]),
+        0x100..=0xFFFF => {
+            buf.push(major | 25);
+            buf.extend((argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xFFFF_FFFF => {
+            buf.push(major | 26);
+            buf.extend((argument as u32).to_be_bytes());
+        }
+        _ => {
+            buf.push(major | 27);
+            buf.extend(argument.to_be_bytes());
+        }
+    }
+}
+
+fn write_text(text: &str, buf: &mut Vec<u8>) {
+    write_head(TEXT, text.len() as u64, buf);
+    buf.extend_from_slice(text.as_bytes());
+}
+
+fn write_value(value: &Value<'_>, buf: &mut Vec<u8>) {
+    match value {
+        Value::Positive(n) => write_head(UNSIGNED, *n, buf),
+        Value::Negative(n) if *n < 0 => write_head(NEGATIVE, !*n as u64, buf),
+        Value::Negative(n) => write_head(UNSIGNED, *n as u64, buf),
+        Value::Bytes(bytes) => {
+            write_head(BYTES, bytes.len() as u64, buf);
+            buf.extend_from_slice(bytes);
+        }
+        Value::String(string) => write_text(string, buf),
+        Value::Array(array) => {
+            write_head(ARRAY, array.len() as u64, buf);
+            for value in array {
+                write_value(value, buf);
+            }
+        }
+        Value::Map(map) => {
+            write_head(MAP, map.len() as u64, buf);
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            for (key, value) in entries {
+                match parse(key) {
+                    Ok((_, key)) => write_value(&key, buf),
+                    // Keys are only ever created by encoding a value, keep them as they are
+                    // should that not hold.
+                    Err(_) => write_value(&Value::Bytes(Cow::Borrowed(key)), buf),
+                }
+                write_value(value, buf);
+            }
+        }
+        Value::Error(error) if error == EMPTY => write_head(SIMPLE, NULL, buf),
+        Value::Error(error) => {
+            write_head(TAG, ERROR_TAG, buf);
+            write_text(error, buf);
+        }
+        Value::Float(n) => {
+            buf.push(DOUBLE);
+            buf.extend(n.to_be_bytes());
+        }
+        Value::Comment(comment) => {
+            write_head(TAG, COMMENT_TAG, buf);
+            write_text(comment, buf);
+        }
+        #[cfg(feature = "compression")]
+        Value::Compressed(compressed) => write_value(&compressed.decompress(), buf),
+    }
+}
+
+/// Head of a CBOR item, the argument is `None` for indefinite lengths.
+struct Head {
+    major: u8,
+    argument: Option<u64>,
+}
+
+struct Decoder<'i> {
+    input: &'i [u8],
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, n: usize) -> Option<&[u8]> {
+        if self.input.len() < n {
+            return None;
+        }
+        let (taken, rest) = self.input.split_at(n);
+        self.input = rest;
+        Some(taken)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn head(&mut self) -> Option<Head> {
+        let [first] = self.take_array()?;
+        let (major, additional) = (first >> 5, first & 0x1F);
+        let argument = match additional {
+            0..=23 => Some(additional as u64),
+            24 => Some(u8::from_be_bytes(self.take_array()?) as u64),
+            25 => Some(u16::from_be_bytes(self.take_array()?) as u64),
+            26 => Some(u32::from_be_bytes(self.take_array()?) as u64),
+            27 => Some(u64::from_be_bytes(self.take_array()?)),
+            31 if (BYTES..=MAP).contains(&major) => None,
+            _ => return None,
+        };
+        Some(Head { major, argument })
+    }
+
+    /// Consumes the break ending an indefinite length item if it comes next.
+    fn at_break(&mut self) -> bool {
+        let at_break = self.input.first() == Some(&BREAK);
+        if at_break {
+            self.input = &self.input[1..];
+        }
+        at_break
+    }
+
+    /// Contents of a byte or text string, joining the chunks of an indefinite length one.
+    fn chunks(&mut self, major: u8, argument: Option<u64>) -> Option<Vec<u8>> {
+        if let Some(len) = argument {
+            return Some(self.take(usize::try_from(len).ok()?)?.to_vec());
+        }
+        let mut joined = vec![];
+        while !self.at_break() {
+            match self.head()? {
+                Head {
+                    major: chunk,
+                    argument: Some(len),
+                } if chunk == major => {
+                    joined.extend_from_slice(self.take(usize::try_from(len).ok()?)?);
+                }
+                _ => return None,
+            }
+        }
+        Some(joined)
+    }
+
+    fn text(&mut self, argument: Option<u64>) -> Option<String> {
+        String::from_utf8(self.chunks(TEXT, argument)?).ok()
+    }
+
+    /// Decodes items until `len` are read or, for indefinite lengths, the break.
+    fn items(
+        &mut self,
+        len: Option<u64>,
+        mut item: impl FnMut(&mut Self) -> Option<()>,
+    ) -> Option<()> {
+        match len {
+            Some(len) => (0..len).try_for_each(|_| item(self)),
+            None => {
+                while !self.at_break() {
+                    item(self)?;
+                }
+                Some(())
+            }
+        }
+    }
+
+    fn value(&mut self) -> Option<Value<'static>> {
+        match self.input.first() {
+            Some(&HALF) => {
+                let [_, bits @ ..] = self.take_array::<3>()?;
+                return Some(Value::Float(half_to_f64(u16::from_be_bytes(bits))));
+            }
+            Some(&SINGLE) => {
+                let [_, bits @ ..] = self.take_array::<5>()?;
+                return Some(Value::Float(f32::from_be_bytes(bits).into()));
+            }
+            Some(&DOUBLE) => {
+                let [_, bits @ ..] = self.take_array::<9>()?;
+                return Some(Value::Float(f64::from_be_bytes(bits)));
+            }
+            _ => {}
+        }
+        let Head { major, argument } = self.head()?;
+        let value = match major {
+            UNSIGNED => Value::Positive(argument?),
+            NEGATIVE => Value::Negative(!i64::try_from(argument?).ok()?),
+            BYTES => Value::Bytes(Cow::Owned(self.chunks(BYTES, argument)?)),
+            TEXT => Value::String(Cow::Owned(self.text(argument)?)),
+            ARRAY => {
+                let mut array = vec![];
+                self.items(argument, |decoder| {
+                    array.push(decoder.value()?);
+                    Some(())
+                })?;
+                Value::Array(array)
+            }
+            MAP => {
+                let mut map = HashMap::<BytesMut, _>::new();
+                self.items(argument, |decoder| {
+                    let key = decoder.value()?.encode();
+                    map.insert(key, decoder.value()?);
+                    Some(())
+                })?;
+                Value::Map(map)
+            }
+            TAG => {
+                let tag = argument?;
+                let Head {
+                    major: TEXT,
+                    argument,
+                } = self.head()?
+                else {
+                    return None;
+                };
+                let text = Cow::Owned(self.text(argument)?);
+                match tag {
+                    ERROR_TAG => Value::Error(text),
+                    COMMENT_TAG => Value::Comment(text),
+                    _ => return None,
+                }
+            }
+            SIMPLE if argument == Some(NULL) => Value::Error(Cow::Borrowed(EMPTY)),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// Widens an IEEE 754 half precision float, which every `f64` can hold exactly.
+fn half_to_f64(bits: u16) -> f64 {
+    let exponent = (bits >> 10) & 0x1F;
+    let mantissa = f64::from(bits & 0x3FF);
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        0x1F if mantissa == 0.0 => f64::INFINITY,
+        0x1F => f64::NAN,
+        _ => (mantissa + 1024.0) * 2f64.powi(i32::from(exponent) - 25),
+    };
+    if bits >> 15 == 1 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap};
+
+    use ciborium::Value as Cbor;
+    use test_case::test_case;
+
+    use crate::protocol::{
+        cbor::{COMMENT_TAG, ERROR_TAG},
+        Value, EMPTY,
+    };
+
+    fn ciborium_bytes(value: &Cbor) -> Vec<u8> {
+        let mut buf = vec![];
+        ciborium::into_writer(value, &mut buf).unwrap();
+        buf
+    }
+
+    fn long_text() -> String {
+        "long string ".repeat(30)
+    }
+
+    #[test_case(Value::Positive(7), Cbor::Integer(7.into()); "small positive")]
+    #[test_case(Value::Positive(u64::MAX), Cbor::Integer(u64::MAX.into()); "max positive")]
+    #[test_case(Value::Negative(-1000), Cbor::Integer((-1000).into()); "negative")]
+    #[test_case(Value::Negative(i64::MIN), Cbor::Integer(i64::MIN.into()); "min negative")]
+    #[test_case(
+        Value::Bytes(Cow::Owned(vec![7; 300])),
+        Cbor::Bytes(vec![7; 300]);
+        "long bytes"
+    )]
+    #[test_case(
+        Value::String(Cow::Owned(long_text())),
+        Cbor::Text(long_text());
+        "long string"
+    )]
+    #[test_case(Value::Error(Cow::Borrowed(EMPTY)), Cbor::Null; "nil")]
+    #[test_case(
+        Value::Error(Cow::Borrowed("WRONGTYPE")),
+        Cbor::Tag(ERROR_TAG, Box::new(Cbor::Text("WRONGTYPE".into())));
+        "error"
+    )]
+    #[test_case(
+        Value::Comment(Cow::Borrowed("at 1700000000")),
+        Cbor::Tag(COMMENT_TAG, Box::new(Cbor::Text("at 1700000000".into())));
+        "comment"
+    )]
+    #[test_case(
+        Value::Array((0..40).map(Value::Positive).collect()),
+        Cbor::Array((0..40).map(|n| Cbor::Integer(n.into())).collect());
+        "long array"
+    )]
+    fn round_trip(value: Value<'static>, cbor: Cbor) {
+        let bytes = value.to_cbor();
+        assert_eq!(bytes, ciborium_bytes(&cbor));
+        assert_eq!(ciborium::from_reader::<Cbor, _>(&bytes[..]).unwrap(), cbor);
+        let decoded = Value::from_cbor(&bytes).unwrap();
+        assert_eq!(decoded, value);
+        // The decoded value has to be usable like any other, starting with replying with it.
+        let encoded = decoded.encode();
+        assert_eq!(crate::protocol::parse::parse(&encoded).unwrap().1, value);
+    }
+
+    #[test]
+    fn float_round_trip() {
+        for float in [0.0, -1.5, f64::INFINITY] {
+            let bytes = Value::Float(float).to_cbor();
+            assert_eq!(
+                ciborium::from_reader::<Cbor, _>(&bytes[..]).unwrap(),
+                Cbor::Float(float)
+            );
+            assert_eq!(Value::from_cbor(&bytes).unwrap(), Value::Float(float));
+        }
+    }
+
+    /// Other CBOR encoders pick the shortest float which holds the value exactly.
+    #[test_case(1.5, &[0xF9, 0x3E, 0x00]; "half")]
+    #[test_case(-65504.0, &[0xF9, 0xFB, 0xFF]; "largest half")]
+    #[test_case(5.960464477539063e-8, &[0xF9, 0x00, 0x01]; "subnormal half")]
+    #[test_case(f64::NEG_INFINITY, &[0xF9, 0xFC, 0x00]; "infinite half")]
+    #[test_case(100000.0, &[0xFA, 0x47, 0xC3, 0x50, 0x00]; "single")]
+    fn short_float(float: f64, cbor: &[u8]) {
+        assert_eq!(ciborium_bytes(&Cbor::Float(float)), cbor);
+        assert_eq!(Value::from_cbor(cbor).unwrap(), Value::Float(float));
+    }
+
+    #[test]
+    fn map_round_trip() {
+        let map = Value::Map(HashMap::from([
+            (
+                Value::String(Cow::Borrowed("name")).encode(),
+                Value::String(Cow::Borrowed("kvs")),
+            ),
+            (
+                Value::Positive(1).encode(),
+                Value::Array(vec![Value::Negative(-1000)]),
+            ),
+        ]));
+        let bytes = map.to_cbor();
+        let Cbor::Map(entries) = ciborium::from_reader::<Cbor, _>(&bytes[..]).unwrap() else {
+            panic!("not a map");
+        };
+        assert_eq!(entries.len(), 2);
+        assert!(entries.contains(&(Cbor::Text("name".into()), Cbor::Text("kvs".into()))));
+        assert!(entries.contains(&(
+            Cbor::Integer(1.into()),
+            Cbor::Array(vec![Cbor::Integer((-1000).into())])
+        )));
+        assert_eq!(Value::from_cbor(&bytes).unwrap(), map);
+    }
+
+    #[test]
+    fn indefinite_lengths() {
+        // ["ab" "c"] as an indefinite text string inside an indefinite array.
+        let bytes = [0x9F, 0x7F, 0x62, b'a', b'b', 0x61, b'c', 0xFF, 0xFF];
+        assert_eq!(
+            Value::from_cbor(&bytes).unwrap(),
+            Value::Array(vec![Value::String(Cow::Borrowed("abc"))])
+        );
+    }
+
+    #[test_case(Cbor::Bool(true); "bool")]
+    #[test_case(Cbor::Integer((-1i128 << 64).try_into().unwrap()); "wide negative")]
+    #[test_case(Cbor::Tag(1, Box::new(Cbor::Integer(0.into()))); "unknown tag")]
+    fn unsupported(cbor: Cbor) {
+        assert!(Value::from_cbor(&ciborium_bytes(&cbor)).is_err());
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        assert!(Value::from_cbor(&[0x01, 0x02]).is_err());
+        assert!(Value::from_cbor(&[0x82, 0x01]).is_err());
+    }
+}
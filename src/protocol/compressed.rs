@@ -0,0 +1,51 @@
+use std::borrow::Cow;
+
+use crate::protocol::{Major, Value};
+
+/// Bytes or string kept compressed in memory, see [`Value::Compressed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compressed {
+    /// Major of the original value, either [`Major::Bytes`] or [`Major::String`].
+    major: Major,
+    data: Vec<u8>,
+}
+
+impl Compressed {
+    /// Compresses bytes and strings, `None` for other values or if compression does not make the
+    /// value smaller.
+    pub fn compress(value: &Value<'_>) -> Option<Self> {
+        let (major, bytes) = match value {
+            Value::Bytes(bytes) => (Major::Bytes, bytes.as_ref()),
+            Value::String(string) => (Major::String, string.as_bytes()),
+            _ => return None,
+        };
+        let data = lz4_flex::compress_prepend_size(bytes);
+        (data.len() < bytes.len()).then_some(Self { major, data })
+    }
+
+    /// The value as it was before compression.
+    pub fn decompress(&self) -> Value<'static> {
+        let bytes = lz4_flex::decompress_size_prepended(&self.data)
+            .expect("compressed values are only created by `compress`");
+        match self.major {
+            Major::String => Value::String(Cow::Owned(
+                String::from_utf8(bytes).expect("only valid strings are compressed as strings"),
+            )),
+            _ => Value::Bytes(Cow::Owned(bytes)),
+        }
+    }
+
+    pub fn major(&self) -> Major {
+        self.major
+    }
+
+    /// Size of the compressed data.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
@@ -1,123 +1,185 @@
-use super::{Major, Value, INDEFINITE_LENGTH};
+use super::{Major, Value, COMMENT, FLOAT, INDEFINITE_LENGTH};
 use std::{borrow::Cow, collections::HashMap};
 
 use bytes::{BufMut, BytesMut};
 
-pub fn encode_map(map: HashMap<BytesMut, Value<'_>>, buf: &mut BytesMut) {
-    let major = (Major::Map as u8) << 5;
-    let len = map.len();
-    let major = if len < 31 {
-        major | len as u8
+/// Collections with fewer than [`INDEFINITE_LENGTH`] elements store their length in the first
+/// byte. Longer ones are encoded as indefinite-length and terminated by a `0xFF` byte, since
+/// additional information of 31 is reserved for the indefinite-length marker.
+fn is_indefinite(len: usize) -> bool {
+    len >= INDEFINITE_LENGTH as usize
+}
+
+/// Options changing how [`Value::encode_with`] lays out values.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct EncodeOptions {
+    /// Emit map entries sorted by their encoded key bytes, as in the CBOR canonical form, so
+    /// equal maps always encode to the same bytes. Otherwise entries follow the iteration order
+    /// of the [`HashMap`], which differs between maps holding the same entries.
+    pub canonical: bool,
+}
+
+/// Leading byte of a value: its major type in the top 3 bits and `additional` information in
+/// the low 5 bits.
+pub fn header(major: Major, additional: u8) -> u8 {
+    ((major as u8) << 5) | additional
+}
+
+/// Number of bytes an integer of at least 24 is stored in after its header.
+fn integer_len(n: u64) -> usize {
+    let mut len = (64 - n.leading_zeros() as usize) / 8;
+    if len == 0 || !n.leading_zeros().is_multiple_of(8) {
+        len += 1;
+    }
+    len
+}
+
+pub fn positive_header(n: u64) -> u8 {
+    if n < 24 {
+        header(Major::Positive, n as u8)
     } else {
-        INDEFINITE_LENGTH
-    };
-    buf.put_u8(major);
-    buf.extend(map.into_iter().flat_map(|(k, v)| {
-        let mut k = k;
-        k.extend(v.encode());
-        k
-    }));
-    if len >= 31 {
-        buf.put_u8(0xFF);
+        header(Major::Positive, (integer_len(n) + 23) as u8)
     }
 }
 
-pub fn encode_error(error: Cow<'_, str>, buf: &mut BytesMut) {
-    let bytes = error.as_bytes();
-    if let Some(first) = bytes.first().copied() {
-        if bytes.len() == 1 && first < 24 {
-            write_single_byte(first, buf, Major::Error as u8);
-            return;
-        }
+pub fn negative_header(n: i64) -> u8 {
+    if n.unsigned_abs() < 24 {
+        header(Major::Negative, -n as u8)
+    } else {
+        header(
+            Major::Negative,
+            (integer_len(n.wrapping_neg() as u64) + 23) as u8,
+        )
     }
-    let major = (Major::Error as u8) << 5;
-    let major = major | bytes.len() as u8;
-    buf.put_u8(major);
-    buf.extend_from_slice(bytes);
 }
 
-pub fn encode_negative(n: i64, buf: &mut BytesMut) {
-    if n.abs() < 24 {
-        dbg!(-n);
-        let major = (Major::Negative as u8) << 5;
-        let major = major | -n as u8;
-        buf.put_u8(major);
-        return;
+/// Header of bytes, a string or an error holding `len` bytes. Lengths below 24 are stored
+/// directly, longer ones follow the header in `additional - 23` bytes, as integers do.
+pub fn length_header(major: Major, len: usize) -> u8 {
+    if len < 24 {
+        header(major, len as u8)
+    } else {
+        header(major, (integer_len(len as u64) + 23) as u8)
     }
+}
 
-    let mut len = (64 - (-n).leading_zeros() as usize) / 8;
-    if len == 0 || (-n).leading_zeros() % 8 != 0 {
-        len += 1;
+/// Writes the header of a `len` bytes long payload and the length following it, if any.
+fn encode_length(major: Major, len: usize, buf: &mut BytesMut) {
+    buf.put_u8(length_header(major, len));
+    if len >= 24 {
+        buf.put_uint(len as u64, integer_len(len as u64));
     }
+}
 
-    let major = (Major::Negative as u8) << 5;
-    let major = major | (len + 23) as u8;
-    buf.put_u8(major);
-    buf.put_int(-(n + 1), len);
+/// Header of an array or map with `len` elements, see [`is_indefinite`].
+pub fn collection_header(major: Major, len: usize) -> u8 {
+    if is_indefinite(len) {
+        header(major, INDEFINITE_LENGTH)
+    } else {
+        header(major, len as u8)
+    }
 }
 
-pub fn encode_positive(n: u64, buf: &mut BytesMut) {
-    if n < 24 {
-        let major = (Major::Positive as u8) << 5;
-        let major = major | n as u8;
-        buf.put_u8(major);
-        return;
+/// Header of an error. A single byte below 24 is stored in the header itself, which is not
+/// followed by a payload then.
+pub fn error_header(error: &str) -> u8 {
+    match error.as_bytes() {
+        [byte] if *byte < 24 => header(Major::Error, *byte),
+        bytes => length_header(Major::Error, bytes.len()),
     }
-    let mut len = (64 - n.leading_zeros() as usize) / 8;
-    if len == 0 || n.leading_zeros() % 8 != 0 {
-        len += 1;
+}
+
+pub fn encode_map(map: HashMap<BytesMut, Value<'_>>, buf: &mut BytesMut, options: &EncodeOptions) {
+    let len = map.len();
+    buf.put_u8(collection_header(Major::Map, len));
+    if options.canonical {
+        let mut entries = map.into_iter().collect::<Vec<_>>();
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        encode_entries(entries, buf, options);
+    } else {
+        encode_entries(map, buf, options);
+    }
+    if is_indefinite(len) {
+        buf.put_u8(0xFF);
+    }
+}
+
+fn encode_entries<'v>(
+    entries: impl IntoIterator<Item = (BytesMut, Value<'v>)>,
+    buf: &mut BytesMut,
+    options: &EncodeOptions,
+) {
+    for (k, v) in entries {
+        buf.extend_from_slice(&k);
+        v.encode_into_with(buf, options);
+    }
+}
+
+pub fn encode_error(error: Cow<'_, str>, buf: &mut BytesMut) {
+    if let [byte] = error.as_bytes() {
+        if *byte < 24 {
+            buf.put_u8(error_header(&error));
+            return;
+        }
     }
+    encode_length(Major::Error, error.len(), buf);
+    buf.extend_from_slice(error.as_bytes());
+}
 
-    let major = (Major::Positive as u8) << 5;
-    let major = major | (len + 23) as u8;
-    buf.put_u8(major);
-    buf.put_int(n as i64, len);
+pub fn encode_comment(comment: Cow<'_, str>, buf: &mut BytesMut) {
+    buf.put_u8(header(Major::Float, COMMENT));
+    encode_string(comment, buf);
 }
 
-fn write_single_byte(byte: u8, buf: &mut BytesMut, major: u8) {
-    let major = major << 5;
-    let major = major | byte;
-    buf.put_u8(major);
+pub fn encode_float(n: f64, buf: &mut BytesMut) {
+    buf.put_u8(header(Major::Float, FLOAT));
+    buf.put_f64(n);
+}
+
+pub fn encode_negative(n: i64, buf: &mut BytesMut) {
+    buf.put_u8(negative_header(n));
+    if n.unsigned_abs() >= 24 {
+        buf.put_int(!n, integer_len(n.wrapping_neg() as u64));
+    }
+}
+
+pub fn encode_positive(n: u64, buf: &mut BytesMut) {
+    buf.put_u8(positive_header(n));
+    if n >= 24 {
+        buf.put_int(n as i64, integer_len(n));
+    }
 }
 
 pub fn encode_bytes(bytes: Cow<'_, [u8]>, buf: &mut BytesMut) {
-    let major = (Major::Bytes as u8) << 5;
-    let major = major | bytes.len() as u8;
-    buf.put_u8(major);
+    encode_length(Major::Bytes, bytes.len(), buf);
     buf.extend_from_slice(&bytes[..]);
 }
 
 pub fn encode_string(string: Cow<'_, str>, buf: &mut BytesMut) {
-    let bytes = string.as_bytes();
-    let major = (Major::String as u8) << 5;
-    let major = major | bytes.len() as u8;
-    buf.put_u8(major);
-    buf.extend_from_slice(bytes);
+    encode_length(Major::String, string.len(), buf);
+    buf.extend_from_slice(string.as_bytes());
 }
 
-pub fn encode_array(array: Vec<Value<'_>>, buf: &mut BytesMut) {
-    let major = (Major::Array as u8) << 5;
+pub fn encode_array(array: Vec<Value<'_>>, buf: &mut BytesMut, options: &EncodeOptions) {
     let len = array.len();
-    let major = if len < 31 {
-        major | len as u8
-    } else {
-        major | INDEFINITE_LENGTH
-    };
-
-    buf.put_u8(major);
-    buf.extend(array.into_iter().flat_map(|i| i.encode().into_iter()));
-    if len >= 31 {
+    buf.put_u8(collection_header(Major::Array, len));
+    for item in array {
+        item.encode_into_with(buf, options);
+    }
+    if is_indefinite(len) {
         buf.put_u8(0xFF);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::borrow::Cow;
+    use std::{borrow::Cow, collections::HashMap};
 
-    use crate::protocol::{ARRAY_MAJOR, INDEFINITE_LENGTH};
+    use bytes::BytesMut;
 
-    use super::Value;
+    use crate::protocol::{ARRAY_MAJOR, INDEFINITE_LENGTH, MAP_MAJOR};
+
+    use super::{EncodeOptions, Value};
     use test_case::test_case;
 
     #[test_case(0, b"\x00")]
@@ -155,6 +217,14 @@ mod tests {
         assert_eq!(&encoded_number[..], b"\x39\x01\xf3");
     }
 
+    #[test]
+    fn min_negative() {
+        let encoded_number = Value::Negative(i64::MIN).encode();
+        assert_eq!(&encoded_number[..], b"\x3f\x7f\xff\xff\xff\xff\xff\xff\xff");
+        let (_, parsed) = crate::protocol::parse::parse(&encoded_number).unwrap();
+        assert_eq!(parsed, Value::Negative(i64::MIN));
+    }
+
     #[test]
     fn bytes() {
         let bytes = Value::<'_, u8, str>::Bytes(Cow::from(&b"hi"[..]));
@@ -169,6 +239,13 @@ mod tests {
         assert_eq!(&encoded_bytes[..], [0b011_00010, b'h', b'i']);
     }
 
+    #[test]
+    fn long_bytes() {
+        let encoded_bytes = Value::<'_, u8, str>::Bytes(Cow::Owned(vec![0; 300])).encode();
+        assert_eq!(&encoded_bytes[..3], [0b010_11001, 0x01, 0x2C]);
+        assert_eq!(encoded_bytes.len(), 303);
+    }
+
     #[test]
     fn sized_array() {
         let array = Value::Array(vec![Value::Positive(5), Value::Negative(-500)]);
@@ -182,9 +259,7 @@ mod tests {
     #[test]
     fn unsized_array() {
         let array = Value::Array(
-            std::iter::repeat(Value::Positive(500))
-                .take(32)
-                .collect::<Vec<Value<'_, u8, str>>>(),
+            std::iter::repeat_n(Value::Positive(500), 32).collect::<Vec<Value<'_, u8, str>>>(),
         );
         let encoded_array = array.encode();
         let mut encoded = vec![(ARRAY_MAJOR << 5) | INDEFINITE_LENGTH];
@@ -195,4 +270,52 @@ mod tests {
 
         assert_eq!(&encoded_array[..], encoded);
     }
+
+    #[test_case(30, (ARRAY_MAJOR << 5) | 30, false)]
+    #[test_case(31, (ARRAY_MAJOR << 5) | INDEFINITE_LENGTH, true)]
+    #[test_case(32, (ARRAY_MAJOR << 5) | INDEFINITE_LENGTH, true)]
+    fn array_length_boundary(len: usize, header: u8, terminated: bool) {
+        let array = Value::Array(vec![Value::Positive(1); len]);
+        let encoded_array = array.encode();
+        assert_eq!(encoded_array[0], header);
+        assert_eq!(encoded_array.len(), 1 + len + terminated as usize);
+        assert_eq!(encoded_array[encoded_array.len() - 1] == 0xFF, terminated);
+    }
+
+    #[test_case(30, (MAP_MAJOR << 5) | 30, false)]
+    #[test_case(31, (MAP_MAJOR << 5) | INDEFINITE_LENGTH, true)]
+    #[test_case(32, (MAP_MAJOR << 5) | INDEFINITE_LENGTH, true)]
+    fn map_length_boundary(len: usize, header: u8, terminated: bool) {
+        let map = Value::Map(
+            (0..len as u64)
+                .map(|i| (Value::Positive(i + 100).encode(), Value::Positive(1)))
+                .collect::<HashMap<BytesMut, Value<'_>>>(),
+        );
+        let encoded_map = map.encode();
+        assert_eq!(encoded_map[0], header);
+        // every key is a two byte positive number followed by a one byte value
+        assert_eq!(encoded_map.len(), 1 + len * 3 + terminated as usize);
+        assert_eq!(encoded_map[encoded_map.len() - 1] == 0xFF, terminated);
+    }
+
+    #[test]
+    fn canonical_map_order() {
+        let entries = (0..20u64)
+            .map(|i| (Value::Positive(i * 7 + 100).encode(), Value::Positive(i)))
+            .collect::<Vec<_>>();
+        let forward = entries.iter().cloned().collect::<HashMap<_, _>>();
+        let backward = entries.iter().rev().cloned().collect::<HashMap<_, _>>();
+        let nested = |map| Value::Array(vec![Value::Map(map), Value::Positive(1)]);
+
+        let canonical = EncodeOptions { canonical: true };
+        let encoded = nested(forward).encode_with(&canonical);
+        assert_eq!(encoded, nested(backward).encode_with(&canonical));
+
+        // Keys are ascending two byte numbers, each followed by its one byte value.
+        let keys = encoded[2..encoded.len() - 1]
+            .chunks(3)
+            .map(|entry| &entry[..entry.len() - 1])
+            .collect::<Vec<_>>();
+        assert!(keys.is_sorted());
+    }
 }
@@ -1,12 +1,18 @@
+pub mod cbor;
+#[cfg(feature = "compression")]
+pub mod compressed;
 pub mod encode;
 pub mod parse;
+pub mod stream;
 
-pub use parse::parse;
+pub use encode::EncodeOptions;
+pub use parse::{parse, parse_all, parse_lenient, parse_with, peek_major, ParseOptions};
+pub use stream::Parser;
 
 use std::str::Utf8Error;
 use std::{borrow::Cow, collections::HashMap};
 
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 
 pub const POSITIVE_MAJOR: u8 = 0b000;
 pub const NEGATIVE_MAJOR: u8 = 0b001;
@@ -17,6 +23,10 @@ pub const ERROR_MAJOR: u8 = 0b101;
 pub const MAP_MAJOR: u8 = 0b110;
 pub const FLOAT_MAJOR: u8 = 0b111;
 
+/// Bytes of the length preceding length-delimited frames, see [`Value::encode_delimited_into`].
+pub const LENGTH_PREFIX: usize = 4;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Major {
     Positive = 0b000,
     Negative = 0b001,
@@ -48,6 +58,16 @@ impl TryFrom<u8> for Major {
 
 pub const INDEFINITE_LENGTH: u8 = 31;
 
+/// Additional information of the float major marking a comment, see [`Value::Comment`].
+pub const COMMENT: u8 = 30;
+
+/// Additional information of the float major marking a double precision float, see
+/// [`Value::Float`]. As in CBOR, the 8 bytes of the IEEE 754 value follow in big-endian order.
+pub const FLOAT: u8 = 27;
+
+/// Message of the error value replied in place of a missing value.
+pub const EMPTY: &str = "Can not find the key";
+
 /// [CBOR](https://www.rfc-editor.org/rfc/rfc8949.html)-like binary format.
 ///
 /// In general, type representation in this format consists of the first byte and (possibly) data
@@ -59,7 +79,6 @@ pub const INDEFINITE_LENGTH: u8 = 31;
 ///
 /// By default no allocation required for parsing, to get owned value use
 /// [`Value::to_owned`] or [`Value::clone`]
-#[derive(Eq, PartialEq)]
 pub enum Value<'input, B = u8, S = str>
 where
     [B]: ToOwned<Owned = Vec<B>>,
@@ -72,6 +91,17 @@ where
     Array(Vec<Value<'input, B, S>>),
     Map(HashMap<BytesMut, Value<'input, B, S>>),
     Error(Cow<'input, S>),
+    /// Double precision float. Floats compare equal if their bits are equal, so a NaN equals
+    /// itself, but `0.0` and `-0.0` are not equal.
+    Float(f64),
+    /// Metadata frame, e.g. a timestamp in a log of commands, which carries no data and is skipped
+    /// by [`parse_all`] and by the server. Encoded as a float header with additional
+    /// information of [`COMMENT`], followed by an encoded string.
+    Comment(Cow<'input, S>),
+    /// Bytes or string kept compressed in memory by the server. It is never parsed and encodes
+    /// as the original value, so clients never see it.
+    #[cfg(feature = "compression")]
+    Compressed(compressed::Compressed),
 }
 
 impl<'input, B, S> Value<'input, B, S>
@@ -80,6 +110,10 @@ where
     [B]: ToOwned<Owned = Vec<B>>,
     S: ToOwned<Owned = String> + ?Sized + 'input,
 {
+    /// Converts the value into one which does not borrow the input.
+    ///
+    /// Borrowed bytes and strings are copied, owned ones are moved. Arrays are converted in
+    /// place, maps have to be rebuilt since their value type changes, but their keys are moved.
     pub fn to_owned(self) -> Value<'static, B, S> {
         match self {
             Value::Positive(p) => Value::Positive(p),
@@ -94,22 +128,31 @@ where
             ),
             Value::Map(map) => Value::Map(
                 map.into_iter()
-                    .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                    .map(|(k, v)| (k, v.to_owned()))
                     .collect::<HashMap<BytesMut, Value<'static, B, S>>>(),
             ),
             Value::Error(e) => Value::Error(Cow::Owned(e.into_owned())),
+            Value::Float(n) => Value::Float(n),
+            Value::Comment(c) => Value::Comment(Cow::Owned(c.into_owned())),
+            #[cfg(feature = "compression")]
+            Value::Compressed(c) => Value::Compressed(c),
         }
     }
 
-    pub fn first_byte(&self) -> u8 {
+    /// Major type the value is encoded with.
+    pub fn major(&self) -> Major {
         match self {
-            Value::Positive(_) => todo!(),
-            Value::Negative(_) => todo!(),
-            Value::Bytes(_) => todo!(),
-            Value::String(_) => todo!(),
-            Value::Array(_) => todo!(),
-            Value::Map(_) => todo!(),
-            Value::Error(_) => todo!(),
+            Value::Positive(_) => Major::Positive,
+            Value::Negative(_) => Major::Negative,
+            Value::Bytes(_) => Major::Bytes,
+            Value::String(_) => Major::String,
+            Value::Array(_) => Major::Array,
+            Value::Map(_) => Major::Map,
+            Value::Error(_) => Major::Error,
+            Value::Float(_) => Major::Float,
+            Value::Comment(_) => Major::Float,
+            #[cfg(feature = "compression")]
+            Value::Compressed(c) => c.major(),
         }
     }
 
@@ -118,10 +161,14 @@ where
             Value::Positive(_) => 8,
             Value::Negative(_) => 8,
             Value::Bytes(b) => b.len(),
-            Value::String(s) => s.clone().into_owned().as_bytes().len(),
+            Value::String(s) => s.clone().into_owned().len(),
             Value::Array(array) => array.iter().map(|i| i.len()).sum(),
             Value::Map(map) => map.iter().map(|(k, v)| k.len() + v.len()).sum(),
-            Value::Error(e) => e.clone().into_owned().as_bytes().len(),
+            Value::Error(e) => e.clone().into_owned().len(),
+            Value::Float(_) => 8,
+            Value::Comment(c) => c.clone().into_owned().len(),
+            #[cfg(feature = "compression")]
+            Value::Compressed(c) => c.len(),
         }
     }
 
@@ -156,6 +203,16 @@ where
             Value::Error(error) => {
                 write!(f, "e:{:?}", error)
             }
+            Value::Float(n) => {
+                write!(f, "f:{:?}", n)
+            }
+            Value::Comment(comment) => {
+                write!(f, "c:{:?}", comment)
+            }
+            #[cfg(feature = "compression")]
+            Value::Compressed(compressed) => {
+                write!(f, "z:{:?}", compressed.decompress())
+            }
         }
     }
 }
@@ -181,23 +238,106 @@ where
 }
 
 impl Value<'_> {
+    /// Leading byte [`Value::encode`] emits for the value, computed without encoding it.
+    pub fn first_byte(&self) -> u8 {
+        match self {
+            Value::Positive(n) => encode::positive_header(*n),
+            Value::Negative(n) => encode::negative_header(*n),
+            Value::Bytes(b) => encode::length_header(Major::Bytes, b.len()),
+            Value::String(s) => encode::length_header(Major::String, s.len()),
+            Value::Array(array) => encode::collection_header(Major::Array, array.len()),
+            Value::Map(map) => encode::collection_header(Major::Map, map.len()),
+            Value::Error(e) => encode::error_header(e),
+            Value::Float(_) => encode::header(Major::Float, FLOAT),
+            Value::Comment(_) => encode::header(Major::Float, COMMENT),
+            #[cfg(feature = "compression")]
+            Value::Compressed(c) => c.decompress().first_byte(),
+        }
+    }
+
     pub fn encode(self) -> BytesMut {
         let mut buf = BytesMut::with_capacity(self.len());
+        self.encode_into(&mut buf);
+        buf
+    }
+
+    /// Encodes the value and returns it together with its encoded length.
+    pub fn encode_with_len(self) -> (BytesMut, usize) {
+        let buf = self.encode();
+        let len = buf.len();
+        (buf, len)
+    }
 
+    /// Appends the value to `buf` as a length-delimited frame: the encoded length as a big-endian
+    /// `u32`, followed by the encoded value. The length is filled in once the value is encoded,
+    /// so the value is only walked once. Returns the encoded length.
+    ///
+    /// **Panics** if the encoded value is longer than `u32::MAX` bytes.
+    pub fn encode_delimited_into(self, buf: &mut BytesMut) -> usize {
+        let start = buf.len();
+        buf.put_u32(0);
+        self.encode_into(buf);
+        let len = buf.len() - start - LENGTH_PREFIX;
+        let prefix = u32::try_from(len).expect("frame too long for its length prefix");
+        buf[start..start + LENGTH_PREFIX].copy_from_slice(&prefix.to_be_bytes());
+        len
+    }
+
+    /// Encodes the value as laid out by `options`, see [`Value::encode`].
+    pub fn encode_with(self, options: &EncodeOptions) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(self.len());
+        self.encode_into_with(&mut buf, options);
+        buf
+    }
+
+    /// Appends the encoded value to `buf`, reusing its capacity.
+    pub fn encode_into(self, buf: &mut BytesMut) {
+        self.encode_into_with(buf, &EncodeOptions::default());
+    }
+
+    /// Appends the encoded value to `buf` as laid out by `options`.
+    pub fn encode_into_with(self, buf: &mut BytesMut, options: &EncodeOptions) {
         match self {
-            Value::Positive(n) => encode::encode_positive(n, &mut buf),
-            Value::Negative(n) => encode::encode_negative(n, &mut buf),
-            Value::Bytes(b) => encode::encode_bytes(b, &mut buf),
-            Value::String(s) => encode::encode_string(s, &mut buf),
-            Value::Array(array) => encode::encode_array(array, &mut buf),
-            Value::Map(map) => encode::encode_map(map, &mut buf),
-            Value::Error(err) => encode::encode_error(err, &mut buf),
+            Value::Positive(n) => encode::encode_positive(n, buf),
+            Value::Negative(n) => encode::encode_negative(n, buf),
+            Value::Bytes(b) => encode::encode_bytes(b, buf),
+            Value::String(s) => encode::encode_string(s, buf),
+            Value::Array(array) => encode::encode_array(array, buf, options),
+            Value::Map(map) => encode::encode_map(map, buf, options),
+            Value::Error(err) => encode::encode_error(err, buf),
+            Value::Float(n) => encode::encode_float(n, buf),
+            Value::Comment(comment) => encode::encode_comment(comment, buf),
+            #[cfg(feature = "compression")]
+            Value::Compressed(compressed) => compressed.decompress().encode_into_with(buf, options),
         }
+    }
+}
 
-        buf
+/// Equality by value, except for [`Value::Float`], which compares bit patterns so equality stays
+/// reflexive. A [`Value::Compressed`] equals the value it was compressed from.
+impl PartialEq for Value<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Positive(a), Self::Positive(b)) => a == b,
+            (Self::Negative(a), Self::Negative(b)) => a == b,
+            (Self::Bytes(a), Self::Bytes(b)) => a == b,
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Array(a), Self::Array(b)) => a == b,
+            (Self::Map(a), Self::Map(b)) => a == b,
+            (Self::Error(a), Self::Error(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+            (Self::Comment(a), Self::Comment(b)) => a == b,
+            #[cfg(feature = "compression")]
+            (Self::Compressed(a), Self::Compressed(b)) => a == b,
+            #[cfg(feature = "compression")]
+            (Self::Compressed(a), b) | (b, Self::Compressed(a)) => a.decompress() == *b,
+            _ => false,
+        }
     }
 }
 
+impl Eq for Value<'_> {}
+
 impl<'input, B, S> Clone for Value<'input, B, S>
 where
     B: 'input,
@@ -213,6 +353,344 @@ where
             Self::Array(arg0) => Self::Array(arg0.clone()),
             Self::Map(arg0) => Self::Map(arg0.clone()),
             Self::Error(arg0) => Self::Error(arg0.clone()),
+            Self::Float(arg0) => Self::Float(*arg0),
+            Self::Comment(arg0) => Self::Comment(arg0.clone()),
+            #[cfg(feature = "compression")]
+            Self::Compressed(arg0) => Self::Compressed(arg0.clone()),
+        }
+    }
+}
+
+impl Value<'_> {
+    /// Approximate number of bytes the value occupies in memory, including nested values and
+    /// the contents of bytes and strings.
+    pub fn deep_size_of(&self) -> usize {
+        let heap = match self {
+            Value::Positive(_) | Value::Negative(_) | Value::Float(_) => 0,
+            Value::Bytes(b) => b.len(),
+            Value::String(s) | Value::Error(s) | Value::Comment(s) => s.len(),
+            #[cfg(feature = "compression")]
+            Value::Compressed(c) => c.len(),
+            Value::Array(array) => array.iter().map(Value::deep_size_of).sum(),
+            Value::Map(map) => map
+                .iter()
+                .map(|(k, v)| std::mem::size_of::<BytesMut>() + k.len() + v.deep_size_of())
+                .sum(),
+        };
+        std::mem::size_of::<Self>() + heap
+    }
+
+    /// Drops the elements of arrays and maps past the first `max_elements`, in nested containers
+    /// too, and returns whether anything was dropped. Which entries of a map are kept is
+    /// unspecified.
+    pub fn truncate(&mut self, max_elements: usize) -> bool {
+        let truncated = match self {
+            Value::Array(items) if items.len() > max_elements => {
+                items.truncate(max_elements);
+                true
+            }
+            Value::Map(map) if map.len() > max_elements => {
+                let excess = map.keys().skip(max_elements).cloned().collect::<Vec<_>>();
+                for key in excess {
+                    map.remove(&key);
+                }
+                true
+            }
+            _ => false,
+        };
+        match self {
+            Value::Array(items) => items
+                .iter_mut()
+                .fold(truncated, |any, item| item.truncate(max_elements) | any),
+            Value::Map(map) => map
+                .values_mut()
+                .fold(truncated, |any, value| value.truncate(max_elements) | any),
+            _ => truncated,
         }
     }
+
+    /// Merges `patch` into a map: its entries are added or overwrite existing ones, entries whose
+    /// patch value is nil are removed. Returns whether any entry changed, or `None` and leaves the
+    /// value as is if it is not a map.
+    pub fn merge(&mut self, patch: HashMap<BytesMut, Self>) -> Option<bool> {
+        let Value::Map(map) = self else {
+            return None;
+        };
+        Some(patch.into_iter().fold(false, |changed, (key, value)| {
+            if value.is_nil() {
+                map.remove(&key).is_some() | changed
+            } else if map.get(&key) == Some(&value) {
+                changed
+            } else {
+                map.insert(key, value);
+                true
+            }
+        }))
+    }
+
+    /// Numeric value of the value: integers as is, bytes and strings holding a decimal number
+    /// parsed, compressed ones included. `None` for anything else.
+    pub fn coerce_number(&self) -> Option<i128> {
+        match self {
+            Value::Positive(p) => Some(*p as i128),
+            Value::Negative(n) => Some(*n as i128),
+            Value::Bytes(b) => std::str::from_utf8(b).ok()?.parse().ok(),
+            Value::String(s) => s.parse().ok(),
+            #[cfg(feature = "compression")]
+            Value::Compressed(c) => c.decompress().coerce_number(),
+            _ => None,
+        }
+    }
+
+    /// The value with a [`Value::Compressed`] expanded into the bytes or string it holds, so it
+    /// can be inspected like any other value. Borrows the value unless it is compressed.
+    pub fn decompressed(&self) -> Cow<'_, Self> {
+        match self {
+            #[cfg(feature = "compression")]
+            Value::Compressed(c) => Cow::Owned(c.decompress()),
+            value => Cow::Borrowed(value),
+        }
+    }
+
+    /// Integer value holding `n`: [`Value::Positive`] unless it is negative. `None` if `n` does not
+    /// fit either variant.
+    pub fn from_number(n: i128) -> Option<Self> {
+        if n >= 0 {
+            u64::try_from(n).ok().map(Value::Positive)
+        } else {
+            i64::try_from(n).ok().map(Value::Negative)
+        }
+    }
+
+    /// Name of the kind of the value, both signs of integers are `integer`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Positive(_) | Value::Negative(_) => "integer",
+            Value::Bytes(_) => "bytes",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Error(_) => "error",
+            Value::Float(_) => "float",
+            Value::Comment(_) => "comment",
+            #[cfg(feature = "compression")]
+            Value::Compressed(c) => match c.major() {
+                Major::String => "string",
+                _ => "bytes",
+            },
+        }
+    }
+
+    /// Number of elements of an array or entries of a map, `None` for scalar values.
+    pub fn container_len(&self) -> Option<usize> {
+        match self {
+            Value::Array(array) => Some(array.len()),
+            Value::Map(map) => Some(map.len()),
+            _ => None,
+        }
+    }
+
+    /// Whether the value is the nil reply, see [`EMPTY`].
+    pub fn is_nil(&self) -> bool {
+        matches!(self, Value::Error(e) if e == EMPTY)
+    }
+
+    /// Whether the value is an error reply. Nil is not considered an error.
+    pub fn is_error(&self) -> bool {
+        self.as_error().is_some()
+    }
+
+    /// Message of the error reply, `None` for any other value including nil.
+    pub fn as_error(&self) -> Option<&str> {
+        match self {
+            Value::Error(e) if e != EMPTY => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Error reply formatted as `<code> <message>`, so clients can tell errors apart by the
+    /// leading code, e.g. `WRONGTYPE Wrong kind of value`.
+    pub fn error_with_code(code: &str, message: &str) -> Value<'static> {
+        Value::Error(Cow::Owned(format!("{code} {message}")))
+    }
+
+    /// Leading code of an error reply, see [`Value::error_with_code`]. `None` for other values,
+    /// nil, and errors which do not start with an uppercase code followed by a space.
+    pub fn error_code(&self) -> Option<&str> {
+        let (code, _) = self.as_error()?.split_once(' ')?;
+        let is_code = !code.is_empty()
+            && code
+                .bytes()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit());
+        is_code.then_some(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap};
+
+    use bytes::BytesMut;
+    use test_case::test_case;
+
+    use super::{Major, Value, EMPTY, LENGTH_PREFIX};
+    use crate::testing::allocations;
+
+    #[test_case(Value::Positive(1), Major::Positive)]
+    #[test_case(Value::Negative(-1), Major::Negative)]
+    #[test_case(Value::Bytes(Cow::Borrowed(b"hi")), Major::Bytes)]
+    #[test_case(Value::String(Cow::Borrowed("hi")), Major::String)]
+    #[test_case(Value::Array(vec![]), Major::Array)]
+    #[test_case(Value::Map(HashMap::new()), Major::Map)]
+    #[test_case(Value::Error(Cow::Borrowed("oops")), Major::Error)]
+    #[test_case(Value::Float(1.5), Major::Float)]
+    #[test_case(Value::Comment(Cow::Borrowed("note")), Major::Float)]
+    fn major(value: Value<'static>, major: Major) {
+        assert_eq!(value.major(), major);
+        assert_eq!(value.encode()[0] >> 5, major as u8);
+    }
+
+    #[test]
+    fn first_byte() {
+        let values = [
+            Value::Positive(5),
+            Value::Positive(500),
+            Value::Positive(u64::MAX),
+            Value::Negative(-5),
+            Value::Negative(-1000),
+            Value::Bytes(Cow::Borrowed(b"hi")),
+            Value::String(Cow::Borrowed("hello")),
+            Value::Array(vec![Value::Positive(1); 3]),
+            Value::Array(vec![Value::Positive(1); 40]),
+            Value::Map(HashMap::from([(
+                Value::Positive(1).encode(),
+                Value::Positive(2),
+            )])),
+            Value::Error(Cow::Borrowed("oops")),
+            Value::Error(Cow::Borrowed("\u{5}")),
+            Value::Float(-1.5),
+            Value::Comment(Cow::Borrowed("note")),
+        ];
+        for value in values {
+            assert_eq!(value.first_byte(), value.clone().encode()[0], "{value:?}");
+        }
+    }
+
+    #[test_case(Value::Positive(0), false, None)]
+    #[test_case(Value::Bytes(Cow::Borrowed(b"")), false, None)]
+    #[test_case(Value::Array(vec![]), false, None)]
+    #[test_case(Value::Error(Cow::Borrowed(EMPTY)), true, None)]
+    #[test_case(Value::Error(Cow::Owned(EMPTY.to_string())), true, None)]
+    #[test_case(
+        Value::Error(Cow::Borrowed("Not a number")),
+        false,
+        Some("Not a number")
+    )]
+    fn nil_and_error(value: Value<'static>, nil: bool, error: Option<&str>) {
+        assert_eq!(value.is_nil(), nil);
+        assert_eq!(value.is_error(), error.is_some());
+        assert_eq!(value.as_error(), error);
+    }
+
+    #[test_case("WRONGTYPE", "Wrong kind of value")]
+    #[test_case("ERR", "something went wrong")]
+    #[test_case("NOAUTH2", "")]
+    fn error_code_round_trip(code: &str, message: &str) {
+        let error = Value::error_with_code(code, message);
+        assert_eq!(error.as_error(), Some(format!("{code} {message}").as_str()));
+        assert_eq!(error.error_code(), Some(code));
+        let encoded = error.clone().encode();
+        let parsed = crate::protocol::parse(&encoded).unwrap().1;
+        assert_eq!(parsed.error_code(), Some(code));
+    }
+
+    #[test_case(Value::Error(Cow::Borrowed("Not a number")))]
+    #[test_case(Value::Error(Cow::Borrowed("ERR")))]
+    #[test_case(Value::Error(Cow::Borrowed(EMPTY)))]
+    #[test_case(Value::String(Cow::Borrowed("ERR not an error")))]
+    fn no_error_code(value: Value<'static>) {
+        assert_eq!(value.error_code(), None);
+    }
+
+    #[test]
+    fn command_errors_carry_codes() {
+        for &error in crate::error::CommandError::ALL {
+            let reply = Value::from(error);
+            assert_eq!(reply.error_code(), Some(error.name()));
+        }
+    }
+
+    #[test_case(Value::Positive(42), Some(42))]
+    #[test_case(Value::Negative(-42), Some(-42))]
+    #[test_case(Value::Bytes(Cow::Borrowed(b"42")), Some(42))]
+    #[test_case(Value::String(Cow::Borrowed("-42")), Some(-42))]
+    #[test_case(Value::String(Cow::Borrowed("4.2")), None)]
+    #[test_case(Value::Bytes(Cow::Borrowed(b"\xff")), None)]
+    #[test_case(Value::Array(vec![]), None)]
+    fn coerce_number(value: Value<'static>, number: Option<i128>) {
+        assert_eq!(value.coerce_number(), number);
+    }
+
+    #[test_case(0, Some(Value::Positive(0)))]
+    #[test_case(-1, Some(Value::Negative(-1)))]
+    #[test_case(u64::MAX as i128, Some(Value::Positive(u64::MAX)))]
+    #[test_case(u64::MAX as i128 + 1, None)]
+    #[test_case(i64::MIN as i128 - 1, None)]
+    fn from_number(n: i128, value: Option<Value<'static>>) {
+        assert_eq!(Value::from_number(n), value);
+    }
+
+    #[test]
+    fn to_owned_moves_owned_data() {
+        let value: Value<'static> = Value::Array(vec![
+            Value::Bytes(Cow::Owned(b"bytes".to_vec())),
+            Value::String(Cow::Owned("string".to_string())),
+            Value::Array(vec![Value::Error(Cow::Owned("error".to_string()))]),
+        ]);
+        let cloned = value.clone();
+        let (count, owned) = allocations(|| cloned.to_owned());
+        assert_eq!(count, 0);
+        assert_eq!(owned, value);
+
+        let map: Value<'static> = Value::Map(HashMap::from([(
+            Value::Positive(1).encode(),
+            Value::String(Cow::Owned("string".to_string())),
+        )]));
+        // Only the new table is allocated.
+        let cloned = map.clone();
+        let (count, owned) = allocations(|| cloned.to_owned());
+        assert_eq!(count, 1);
+        assert_eq!(owned, map);
+    }
+
+    #[test]
+    fn deep_size_of() {
+        let small = Value::Bytes(Cow::Borrowed(b"hi"));
+        let array = Value::Array(vec![small.clone(), small.clone()]);
+        assert_eq!(small.deep_size_of(), std::mem::size_of::<Value<'_>>() + 2);
+        assert_eq!(
+            array.deep_size_of(),
+            std::mem::size_of::<Value<'_>>() + 2 * small.deep_size_of()
+        );
+    }
+
+    #[test_case(Value::Positive(500))]
+    #[test_case(Value::String(Cow::Borrowed("hello")))]
+    #[test_case(Value::Array((0..40).map(Value::Positive).collect()))]
+    #[test_case(Value::Map(HashMap::from([(Value::Positive(1).encode(), Value::Positive(2))])))]
+    fn delimited_length(value: Value<'static>) {
+        let body = value.clone().encode();
+        let (encoded, len) = value.clone().encode_with_len();
+        assert_eq!((encoded.len(), len), (body.len(), body.len()));
+
+        let mut buf = BytesMut::from("previous frame");
+        let start = buf.len();
+        assert_eq!(value.encode_delimited_into(&mut buf), body.len());
+        let (prefix, rest) = buf[start..].split_at(LENGTH_PREFIX);
+        assert_eq!(
+            u32::from_be_bytes(prefix.try_into().unwrap()) as usize,
+            rest.len()
+        );
+        assert_eq!(rest.len(), body.len());
+    }
 }
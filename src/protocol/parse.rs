@@ -1,87 +1,217 @@
-use crate::protocol::{Major, Value, INDEFINITE_LENGTH};
+use crate::protocol::{Major, Value, COMMENT, FLOAT, INDEFINITE_LENGTH};
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::collections::HashMap;
 
-use crate::error::IResult;
-use bytes::BytesMut;
+use crate::error::{IResult, ParseError};
 use nom::{
-    bytes::complete::{tag, take},
+    bytes::streaming::{tag, take},
     combinator::{map, map_res},
     multi::{count, many_till},
-    number::complete::be_u8,
+    number::streaming::{be_f64, be_u8},
     sequence::tuple,
 };
 
 /// Parse first byte and split it into `major` and `additional` information.
+///
+/// Unlike canonical CBOR, where additional values 28 to 30 are reserved, every additional value
+/// has a meaning here, so none of them are rejected:
+/// - integers store values below 24 directly, 24 to 31 mean the value follows in
+///   `additional - 23` bytes, i.e. 28 to 30 are 5 to 7 byte integers,
+/// - bytes, strings, and errors store their length the same way,
+/// - arrays and maps store their length directly up to 30, 31 marks indefinite length.
+///
+/// Output of a standard CBOR encoder using those values is therefore not misread as an error,
+/// but as the value this scheme assigns to them.
 pub fn parse_first_byte(input: &[u8]) -> IResult<&[u8], (Major, u8)> {
     map_res(map(be_u8, |b: u8| (b >> 5, b & 0x1F)), |(major, size)| {
         Major::try_from(major).map(|m| (m, size))
     })(input)
 }
 
+/// Major type of the value at the beginning of `input`, read from the first byte only.
+///
+/// Cheaper than [`parse`] when only the kind of the value is needed, the rest of `input` is not
+/// looked at, so it may be incomplete or even invalid.
+pub fn peek_major(input: &[u8]) -> crate::error::Result<Major> {
+    Ok(parse_first_byte(input)?.1 .0)
+}
+
+/// Options changing how [`parse_with`] treats its input.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ParseOptions {
+    /// Decode strings and errors with invalid UTF-8 lossily, replacing invalid sequences with
+    /// `U+FFFD`, instead of failing the whole value.
+    pub lossy_strings: bool,
+    /// Estimated heap a single frame may allocate once parsed, `None` for no limit.
+    ///
+    /// Every parsed value counts as the size of a [`Value`], plus whatever it allocates on its
+    /// own, so frames which are small on the wire but expand into many values, like an array of
+    /// empty strings, can be rejected. Frames exceeding the budget fail with
+    /// [`nom::Err::Failure`].
+    pub max_allocation: Option<usize>,
+}
+
+/// State of a single [`parse_with`] call shared by all nested values.
+struct Context<'o> {
+    options: &'o ParseOptions,
+    /// Allocation budget left, see [`ParseOptions::max_allocation`].
+    remaining: Cell<usize>,
+}
+
+impl Context<'_> {
+    /// Takes `bytes` from the allocation budget, failing once it is exhausted.
+    fn allocate(&self, bytes: usize) -> Result<(), nom::Err<ParseError>> {
+        let remaining = self
+            .remaining
+            .get()
+            .checked_sub(bytes)
+            .ok_or(nom::Err::Failure(ParseError::default()))?;
+        self.remaining.set(remaining);
+        Ok(())
+    }
+}
+
+/// Parses a single value from the beginning of `input` with default [`ParseOptions`].
+///
+/// If `input` ends in the middle of a value, [`nom::Err::Incomplete`] is returned, so the caller
+/// can read more bytes and try again.
 pub fn parse(input: &[u8]) -> IResult<&[u8], Value<'_>> {
+    parse_with(input, &ParseOptions::default())
+}
+
+/// Parses back-to-back encoded frames, e.g. the contents of a dump, until `input` is exhausted.
+///
+/// Unlike an array, the frames have no header; a trailing partial frame is an error. Top-level
+/// [`Value::Comment`] frames are skipped.
+pub fn parse_all(mut input: &[u8]) -> crate::error::Result<Vec<Value<'_>>> {
+    let mut values = vec![];
+    while !input.is_empty() {
+        let (rest, value) = parse(input)?;
+        if !matches!(value, Value::Comment(_)) {
+            values.push(value);
+        }
+        input = rest;
+    }
+    Ok(values)
+}
+
+/// Parses a single value from the beginning of `input`, see [`parse`].
+pub fn parse_with<'i>(input: &'i [u8], options: &ParseOptions) -> IResult<&'i [u8], Value<'i>> {
+    let context = Context {
+        options,
+        remaining: Cell::new(options.max_allocation.unwrap_or(usize::MAX)),
+    };
+    parse_value(input, &context)
+}
+
+/// Parses like [`parse_with`], but arrays and maps with more than `max_elements` elements are
+/// truncated instead of rejected, see [`Value::truncate`], for best-effort tooling over untrusted
+/// input. Also returns whether anything was truncated.
+///
+/// The dropped elements are still parsed, so they count against
+/// [`ParseOptions::max_allocation`] and have to be valid.
+pub fn parse_lenient<'i>(
+    input: &'i [u8],
+    options: &ParseOptions,
+    max_elements: usize,
+) -> IResult<&'i [u8], (Value<'i>, bool)> {
+    let (rest, mut value) = parse_with(input, options)?;
+    let truncated = value.truncate(max_elements);
+    Ok((rest, (value, truncated)))
+}
+
+fn parse_value<'i>(input: &'i [u8], context: &Context<'_>) -> IResult<&'i [u8], Value<'i>> {
     let (rest, (major, size)) = parse_first_byte(input)?;
+    context.allocate(std::mem::size_of::<Value<'_>>())?;
     match major {
         Major::Positive => parse_number(rest, size).map(|(rest, n)| (rest, Value::Positive(n))),
         Major::Negative => {
             parse_number(rest, size).map(|(rest, n)| (rest, Value::Negative(-1 - n as i64)))
         }
         Major::Bytes => parse_bytes(rest, size),
-        Major::String => parse_string(rest, size),
-        Major::Array => parse_array(rest, size),
-        Major::Error => parse_error(rest, size),
-        Major::Map => parse_map(rest, size),
-        _ => todo!(),
+        Major::String => map(|i| parse_str(i, size, context), Value::String)(rest),
+        Major::Array => parse_array(rest, size, context),
+        Major::Error => map(|i| parse_str(i, size, context), Value::Error)(rest),
+        Major::Map => parse_map(rest, size, context),
+        Major::Float if size == COMMENT => parse_comment(rest, context),
+        Major::Float if size == FLOAT => map(be_f64, Value::Float)(rest),
+        // Half and single precision floats and simple values are not supported.
+        Major::Float => Err(nom::Err::Error(ParseError::at(input))),
     }
 }
 
-fn parse_array(input: &[u8], size: u8) -> IResult<&[u8], Value<'_>> {
+fn parse_array<'i>(
+    input: &'i [u8],
+    size: u8,
+    context: &Context<'_>,
+) -> IResult<&'i [u8], Value<'i>> {
+    let item = |i| parse_value(i, context);
     if size == INDEFINITE_LENGTH {
-        return map(many_till(parse, tag(&[0xFF][..])), |items| {
+        return map(many_till(item, tag(&[0xFF][..])), |items| {
             Value::Array(items.0)
         })(input);
     }
     map(
-        count(parse, size as usize),
+        count(item, size as usize),
         |array: Vec<Value<'_, u8, str>>| Value::Array(array),
     )(input)
 }
 
-fn parse_map(input: &[u8], size: u8) -> IResult<&[u8], Value<'_>> {
+fn parse_map<'i>(input: &'i [u8], size: u8, context: &Context<'_>) -> IResult<&'i [u8], Value<'i>> {
+    // Keys are stored encoded, which copies them.
+    let item = |i| parse_value(i, context);
+    let key = |i: &'i [u8]| {
+        let (rest, key) = parse_value(i, context)?;
+        context.allocate(i.len() - rest.len())?;
+        Ok((rest, key))
+    };
     if size == INDEFINITE_LENGTH {
-        return map(
-            many_till(tuple((parse, parse)), tag(&[0xFF][..])),
-            |items| {
-                Value::Map(HashMap::<_, _, std::hash::RandomState>::from_iter(
-                    items.0.into_iter().map(|(k, v)| (k.encode(), v)),
-                ))
-            },
-        )(input);
-    }
-    map(count(tuple((parse, parse)), size as usize), |map| {
+        return map(many_till(tuple((key, item)), tag(&[0xFF][..])), |items| {
+            Value::Map(HashMap::<_, _, std::hash::RandomState>::from_iter(
+                items.0.into_iter().map(|(k, v)| (k.encode(), v)),
+            ))
+        })(input);
+    }
+    map(count(tuple((key, item)), size as usize), |map| {
         Value::Map(HashMap::<_, _, std::hash::RandomState>::from_iter(
-            map.into_iter().map(|(_, v)| (BytesMut::new(), v)),
+            map.into_iter().map(|(k, v)| (k.encode(), v)),
         ))
     })(input)
 }
 
-fn parse_bytes(input: &[u8], additional: u8) -> IResult<&[u8], Value<'_>> {
-    map(take(additional), |bytes: &[u8]| {
-        Value::Bytes(Cow::from(bytes))
-    })(input)
+/// Parses the string following a comment header.
+fn parse_comment<'i>(input: &'i [u8], context: &Context<'_>) -> IResult<&'i [u8], Value<'i>> {
+    let (rest, (major, size)) = parse_first_byte(input)?;
+    if major != Major::String {
+        return Err(nom::Err::Error(ParseError::at(input)));
+    }
+    map(move |i| parse_str(i, size, context), Value::Comment)(rest)
 }
 
-fn parse_string(input: &[u8], additional: u8) -> IResult<&[u8], Value<'_>> {
-    map(
-        map_res(take(additional), |bytes: &[u8]| std::str::from_utf8(bytes)),
-        |s: &str| Value::String(Cow::from(s)),
-    )(input)
+/// Length of the payload of bytes, a string or an error, see [`parse_first_byte`].
+fn parse_length(input: &[u8], additional: u8) -> IResult<&[u8], usize> {
+    map_res(|i| parse_number(i, additional), usize::try_from)(input)
+}
+
+fn parse_bytes(input: &[u8], additional: u8) -> IResult<&[u8], Value<'_>> {
+    let (input, len) = parse_length(input, additional)?;
+    map(take(len), |bytes: &[u8]| Value::Bytes(Cow::from(bytes)))(input)
 }
 
-fn parse_error(input: &[u8], additional: u8) -> IResult<&[u8], Value<'_>> {
+/// Parses the UTF-8 payload shared by strings and errors.
+fn parse_str<'i>(
+    input: &'i [u8],
+    additional: u8,
+    context: &Context<'_>,
+) -> IResult<&'i [u8], Cow<'i, str>> {
+    let (input, len) = parse_length(input, additional)?;
+    if context.options.lossy_strings {
+        return map(take(len), String::from_utf8_lossy)(input);
+    }
     map(
-        map_res(take(additional), |bytes: &[u8]| std::str::from_utf8(bytes)),
-        |s: &str| Value::Error(Cow::from(s)),
+        map_res(take(len), |bytes: &[u8]| std::str::from_utf8(bytes)),
+        Cow::from,
     )(input)
 }
 
@@ -226,4 +356,212 @@ mod tests {
         );
         assert!(rest.is_empty());
     }
+
+    #[test]
+    fn incomplete() {
+        let payload = [((Major::Array as u8) << 5) | 2, 0b011_00101, 104, 101];
+        for end in 0..payload.len() {
+            assert!(matches!(
+                parse(&payload[..end]),
+                Err(nom::Err::Incomplete(_))
+            ));
+        }
+    }
+
+    #[test_case::test_case(30)]
+    #[test_case::test_case(31)]
+    #[test_case::test_case(32)]
+    fn array_length_boundary(len: u64) {
+        let array = Value::Array((0..len).map(Value::Positive).collect());
+        let payload = array.clone().encode();
+        let parsed = parse(&payload[..]);
+        assert!(parsed.is_ok());
+        let (rest, parsed) = parsed.unwrap();
+        assert_eq!(parsed, array);
+        assert!(rest.is_empty());
+    }
+
+    #[test_case::test_case(30)]
+    #[test_case::test_case(31)]
+    #[test_case::test_case(32)]
+    fn map_length_boundary(len: u64) {
+        let map = Value::Map(
+            (0..len)
+                .map(|i| (Value::Positive(i).encode(), Value::Positive(i * 1000)))
+                .collect(),
+        );
+        let payload = map.clone().encode();
+        let parsed = parse(&payload[..]);
+        assert!(parsed.is_ok());
+        let (rest, parsed) = parsed.unwrap();
+        assert_eq!(parsed, map);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn float_round_trip() {
+        for float in [0.0, -1.5, f64::INFINITY, f64::NAN] {
+            let payload = Value::Float(float).encode();
+            assert_eq!(payload.len(), 9);
+            assert_eq!(payload[1..], float.to_be_bytes());
+            let (rest, parsed) = parse(&payload[..]).unwrap();
+            let Value::Float(parsed) = parsed else {
+                panic!("not a float: {parsed:?}");
+            };
+            assert_eq!(parsed.to_bits(), float.to_bits());
+            assert!(rest.is_empty());
+        }
+        assert!(matches!(
+            parse(&Value::Float(1.0).encode()[..5]),
+            Err(nom::Err::Incomplete(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_utf8_string() {
+        let payload = [0b011_00011, b'h', 0xFF, b'i'];
+        assert!(parse(&payload[..]).is_err());
+
+        let options = ParseOptions {
+            lossy_strings: true,
+            ..Default::default()
+        };
+        let parsed = parse_with(&payload[..], &options);
+        assert!(parsed.is_ok());
+        let (rest, parsed) = parsed.unwrap();
+        assert_eq!(parsed, Value::String(Cow::Borrowed("h\u{FFFD}i")));
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn concatenated_frames() {
+        let values = vec![
+            Value::Positive(42),
+            Value::String(Cow::Borrowed("hi")),
+            Value::Array(vec![Value::Bytes(Cow::Borrowed(b"key"))]),
+        ];
+        let mut payload = vec![];
+        for value in values.clone() {
+            payload.extend_from_slice(&value.encode());
+        }
+        assert_eq!(parse_all(&payload).unwrap(), values);
+        assert!(parse_all(&payload[..payload.len() - 1]).is_err());
+        assert!(parse_all(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn comments_skipped() {
+        let values = vec![
+            Value::Array(vec![Value::String(Cow::Borrowed("PING"))]),
+            Value::Array(vec![
+                Value::String(Cow::Borrowed("GET")),
+                Value::Bytes(Cow::Borrowed(b"key")),
+            ]),
+        ];
+        let comment = Value::Comment(Cow::Borrowed("at 1700000000"));
+        let mut plain = vec![];
+        let mut commented = comment.clone().encode().to_vec();
+        for value in values.clone() {
+            plain.extend_from_slice(&value.clone().encode());
+            commented.extend_from_slice(&value.encode());
+            commented.extend_from_slice(&comment.clone().encode());
+        }
+
+        assert_eq!(parse(&commented).unwrap().1, comment);
+        assert_eq!(parse_all(&commented).unwrap(), values);
+        assert_eq!(parse_all(&commented).unwrap(), parse_all(&plain).unwrap());
+    }
+
+    #[test]
+    fn peek_major_only_reads_first_byte() {
+        let payload = [0b011_00101, b'h', 0xFF];
+        assert_eq!(peek_major(&payload).unwrap(), Major::String);
+        assert!(parse(&payload).is_err());
+        assert!(peek_major(&[]).is_err());
+    }
+
+    #[test]
+    fn allocation_budget() {
+        let payload = Value::Array(vec![Value::Bytes(Cow::Borrowed(b"")); 1000]).encode();
+        let options = ParseOptions {
+            max_allocation: Some(10 * payload.len()),
+            ..Default::default()
+        };
+        assert!(parse(&payload).is_ok());
+        assert!(matches!(
+            parse_with(&payload, &options),
+            Err(nom::Err::Failure(_))
+        ));
+
+        let small = Value::Array(vec![Value::Bytes(Cow::Borrowed(b"")); 10]).encode();
+        assert!(parse_with(&small, &options).is_ok());
+    }
+
+    #[test]
+    fn lenient_truncates_collections() {
+        let nested = Value::Array((0..5).map(Value::Positive).collect());
+        let mut items = vec![nested; 3];
+        items.extend((0..37).map(Value::Positive));
+        let payload = Value::Array(items).encode();
+
+        let (rest, (value, truncated)) =
+            parse_lenient(&payload, &ParseOptions::default(), 4).unwrap();
+        assert!(rest.is_empty());
+        assert!(truncated);
+        let nested = Value::Array((0..4).map(Value::Positive).collect());
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                nested.clone(),
+                nested.clone(),
+                nested,
+                Value::Positive(0)
+            ])
+        );
+
+        let (_, (value, truncated)) =
+            parse_lenient(&payload, &ParseOptions::default(), 40).unwrap();
+        assert!(!truncated);
+        assert_eq!(value, parse(&payload).unwrap().1);
+    }
+
+    #[test_case::test_case(Value::Bytes(Cow::Owned(vec![7; 24])))]
+    #[test_case::test_case(Value::Bytes(Cow::Owned(vec![7; 300])))]
+    #[test_case::test_case(Value::String(Cow::Owned("a".repeat(70_000))))]
+    #[test_case::test_case(Value::Error(Cow::Owned("e".repeat(40))))]
+    fn long_payload(value: Value<'static>) {
+        let payload = value.clone().encode();
+        assert_eq!(payload[0], value.first_byte());
+        let (rest, parsed) = parse(&payload).unwrap();
+        assert_eq!(parsed, value);
+        assert!(rest.is_empty());
+    }
+
+    #[test_case::test_case(28)]
+    #[test_case::test_case(29)]
+    #[test_case::test_case(30)]
+    fn reserved_additional(additional: u8) {
+        let len = additional as usize;
+
+        let mut payload = vec![((Major::Positive as u8) << 5) | additional];
+        payload.extend(std::iter::repeat_n(0, len - 24));
+        payload.push(1);
+        let (rest, value) = parse(&payload).unwrap();
+        assert_eq!(value, Value::Positive(1));
+        assert!(rest.is_empty());
+
+        let mut payload = vec![((Major::String as u8) << 5) | additional];
+        payload.extend(std::iter::repeat_n(0, len - 24));
+        payload.push(3);
+        payload.extend(b"abc");
+        let (rest, value) = parse(&payload).unwrap();
+        assert_eq!(value, Value::String(Cow::Borrowed("abc")));
+        assert!(rest.is_empty());
+
+        let mut payload = vec![((Major::Array as u8) << 5) | additional];
+        payload.extend(std::iter::repeat_n(0, len));
+        let (rest, value) = parse(&payload).unwrap();
+        assert_eq!(value.container_len(), Some(len));
+        assert!(rest.is_empty());
+    }
 }
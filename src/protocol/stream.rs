@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use bytes::BytesMut;
+
+use crate::protocol::{parse, parse::parse_first_byte, Major, Value, INDEFINITE_LENGTH};
+
+/// Container whose elements are still being parsed.
+enum Frame {
+    Array {
+        items: Vec<Value<'static>>,
+        /// Elements left to read, `None` for indefinite length.
+        remaining: Option<usize>,
+    },
+    Map {
+        entries: HashMap<BytesMut, Value<'static>>,
+        /// Encoded key whose value is being parsed.
+        key: Option<BytesMut>,
+        /// Entries left to read, `None` for indefinite length.
+        remaining: Option<usize>,
+    },
+}
+
+impl Frame {
+    fn new(major: Major, size: u8) -> Self {
+        let remaining = (size != INDEFINITE_LENGTH).then_some(size as usize);
+        match major {
+            Major::Map => Frame::Map {
+                entries: HashMap::new(),
+                key: None,
+                remaining,
+            },
+            _ => Frame::Array {
+                items: vec![],
+                remaining,
+            },
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        match self {
+            Frame::Array { remaining, .. } | Frame::Map { remaining, .. } => *remaining == Some(0),
+        }
+    }
+
+    fn is_indefinite(&self) -> bool {
+        match self {
+            Frame::Array { remaining, .. } | Frame::Map { remaining, .. } => remaining.is_none(),
+        }
+    }
+
+    /// Adds an element, which for maps alternates between a key and its value.
+    fn push(&mut self, value: Value<'static>) {
+        match self {
+            Frame::Array { items, remaining } => {
+                items.push(value);
+                if let Some(remaining) = remaining {
+                    *remaining -= 1;
+                }
+            }
+            Frame::Map {
+                entries,
+                key,
+                remaining,
+            } => match key.take() {
+                Some(key) => {
+                    entries.insert(key, value);
+                    if let Some(remaining) = remaining {
+                        *remaining -= 1;
+                    }
+                }
+                None => *key = Some(value.encode()),
+            },
+        }
+    }
+
+    fn finish(self) -> Value<'static> {
+        match self {
+            Frame::Array { items, .. } => Value::Array(items),
+            Frame::Map { entries, .. } => Value::Map(entries),
+        }
+    }
+}
+
+/// Incremental parser for values arriving in arbitrary pieces.
+///
+/// Unlike [`parse`], which needs the whole value in one buffer and starts over once more bytes
+/// arrive, the parser keeps the containers it is in the middle of across [`Parser::feed`] calls,
+/// so only the bytes of the scalar currently being read are buffered.
+#[derive(Default)]
+pub struct Parser {
+    /// Bytes not consumed yet: a partial scalar, or whatever followed the last complete value.
+    pending: Vec<u8>,
+    /// Containers being filled, innermost last.
+    stack: Vec<Frame>,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `bytes` to the input and returns the next value once it is complete.
+    ///
+    /// Bytes following a complete value are kept for the next value, feed an empty slice to get
+    /// it. On invalid input the parser is reset and the error is returned.
+    pub fn feed(&mut self, bytes: &[u8]) -> crate::error::Result<Option<Value<'static>>> {
+        self.pending.extend_from_slice(bytes);
+        let next = self.next_value();
+        if next.is_err() {
+            *self = Self::default();
+        }
+        next
+    }
+
+    /// Whether the parser is in the middle of a value.
+    pub fn is_partial(&self) -> bool {
+        !self.stack.is_empty() || !self.pending.is_empty()
+    }
+
+    fn next_value(&mut self) -> crate::error::Result<Option<Value<'static>>> {
+        while let Some(&first) = self.pending.first() {
+            let (_, (major, size)) = parse_first_byte(&self.pending)?;
+            let value = match self.stack.last() {
+                Some(frame) if frame.is_indefinite() && first == 0xFF => {
+                    self.pending.drain(..1);
+                    self.stack.pop().map(Frame::finish)
+                }
+                _ if matches!(major, Major::Array | Major::Map) => {
+                    self.pending.drain(..1);
+                    self.open(Frame::new(major, size))
+                }
+                _ => match parse(&self.pending) {
+                    Ok((rest, value)) => {
+                        let consumed = self.pending.len() - rest.len();
+                        let value = value.to_owned();
+                        self.pending.drain(..consumed);
+                        Some(value)
+                    }
+                    Err(nom::Err::Incomplete(_)) => return Ok(None),
+                    Err(error) => return Err(error.into()),
+                },
+            };
+            if let Some(value) = value.and_then(|value| self.complete(value)) {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Starts filling `frame`, or returns it right away if it is empty.
+    fn open(&mut self, frame: Frame) -> Option<Value<'static>> {
+        if frame.is_full() {
+            return Some(frame.finish());
+        }
+        self.stack.push(frame);
+        None
+    }
+
+    /// Hands a finished value to its container, returning it if it is a top-level value.
+    fn complete(&mut self, mut value: Value<'static>) -> Option<Value<'static>> {
+        loop {
+            let Some(frame) = self.stack.last_mut() else {
+                return Some(value);
+            };
+            frame.push(value);
+            if !frame.is_full() {
+                return None;
+            }
+            value = self.stack.pop()?.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap};
+
+    use crate::protocol::{stream::Parser, Value};
+
+    #[test]
+    fn nested_array_byte_by_byte() {
+        let value = Value::Array(vec![
+            Value::Positive(500),
+            Value::Array(vec![
+                Value::String(Cow::Borrowed("hello")),
+                Value::Array(vec![]),
+                Value::Map(HashMap::from([(
+                    Value::Positive(1).encode(),
+                    Value::Bytes(Cow::Borrowed(b"one")),
+                )])),
+            ]),
+            Value::Array((0..40).map(Value::Positive).collect()),
+        ]);
+        let payload = value.clone().encode();
+
+        let mut parser = Parser::new();
+        let (last, bytes) = payload.split_last().unwrap();
+        for byte in bytes {
+            assert_eq!(parser.feed(&[*byte]).unwrap(), None);
+            assert!(parser.is_partial());
+        }
+        assert_eq!(parser.feed(&[*last]).unwrap(), Some(value));
+        assert!(!parser.is_partial());
+    }
+
+    #[test]
+    fn several_values_in_one_feed() {
+        let mut payload = Value::Positive(1).encode();
+        payload.extend_from_slice(&Value::Array(vec![Value::Positive(2)]).encode());
+        payload.extend_from_slice(&Value::String(Cow::Borrowed("thr")).encode()[..2]);
+
+        let mut parser = Parser::new();
+        assert_eq!(parser.feed(&payload).unwrap(), Some(Value::Positive(1)));
+        assert_eq!(
+            parser.feed(&[]).unwrap(),
+            Some(Value::Array(vec![Value::Positive(2)]))
+        );
+        assert_eq!(parser.feed(&[]).unwrap(), None);
+        assert_eq!(
+            parser.feed(b"hr").unwrap(),
+            Some(Value::String(Cow::Borrowed("thr")))
+        );
+    }
+
+    #[test]
+    fn invalid_input_resets() {
+        let mut parser = Parser::new();
+        assert_eq!(parser.feed(&[0b100_00010, 0b011_00001]).unwrap(), None);
+        assert!(parser.feed(&[0xFF]).is_err());
+        assert!(!parser.is_partial());
+        assert_eq!(
+            parser.feed(&Value::Positive(7).encode()).unwrap(),
+            Some(Value::Positive(7))
+        );
+    }
+}
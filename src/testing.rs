@@ -0,0 +1,185 @@
+//! Helpers shared by unit tests.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use bytes::BytesMut;
+use tokio::io::AsyncWrite;
+
+use crate::{
+    codec::Connection,
+    command::{entry::CommandEntry, StorageCommand},
+    db::{storage::Storage, Db},
+    error::{self, ProtocolError},
+    protocol::{parse, Value},
+};
+
+/// Collects every frame written to it.
+pub struct TestWriter {
+    pub values: Vec<Value<'static>>,
+}
+
+impl TestWriter {
+    pub fn new() -> Self {
+        Self { values: vec![] }
+    }
+}
+
+impl AsyncWrite for TestWriter {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<Result<usize, std::io::Error>> {
+        let mut rest = buf;
+        while !rest.is_empty() {
+            match parse(rest) {
+                Ok((tail, value)) => {
+                    self.values.push(value.to_owned());
+                    rest = tail;
+                }
+                Err(_) => {
+                    return std::task::Poll::Ready(Err(std::io::Error::other("parse error")));
+                }
+            }
+        }
+        std::task::Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+/// Executes `command` on a fresh connection and returns every frame it replied with.
+pub async fn execute(db: &Arc<Db>, command: CommandEntry) -> Vec<Value<'static>> {
+    let mut connection = Connection::new(tokio::io::empty(), TestWriter::new());
+    command.execute(&mut connection, db.clone()).await;
+    connection.write_half.into_inner().values
+}
+
+/// Executes `command` on `storage` in place of the map of `db` and returns every frame it
+/// replied with.
+pub async fn execute_on(
+    db: &Db,
+    storage: &impl Storage,
+    command: impl StorageCommand,
+) -> Vec<Value<'static>> {
+    let mut connection = Connection::new(tokio::io::empty(), TestWriter::new());
+    let _ = command.execute_on(&mut connection, db, storage).await;
+    let _ = connection.flush_writer().await;
+    connection.write_half.into_inner().values
+}
+
+/// [`Storage`] keeping keys in order in a single map, failing the next `failures` operations
+/// with [`ProtocolError::Retryable`].
+#[derive(Default)]
+pub struct MockStorage {
+    entries: Mutex<BTreeMap<BytesMut, Value<'static>>>,
+    pub failures: AtomicU32,
+    /// Operations attempted, failed ones included.
+    pub calls: AtomicU32,
+}
+
+impl MockStorage {
+    fn call(&self) -> error::Result<MutexGuard<'_, BTreeMap<BytesMut, Value<'static>>>> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let failing = self
+            .failures
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        match failing {
+            Ok(_) => Err(ProtocolError::Retryable),
+            Err(_) => Ok(self.entries.lock().unwrap()),
+        }
+    }
+}
+
+impl Storage for MockStorage {
+    fn get(&self, key: &[u8]) -> error::Result<Option<Value<'static>>> {
+        Ok(self.call()?.get(key).cloned())
+    }
+
+    fn set(&self, key: BytesMut, value: Value<'static>) -> error::Result<Option<Value<'static>>> {
+        Ok(self.call()?.insert(key, value))
+    }
+
+    fn remove(&self, key: &[u8]) -> error::Result<Option<Value<'static>>> {
+        Ok(self.call()?.remove(key))
+    }
+
+    fn len(&self) -> error::Result<usize> {
+        Ok(self.call()?.len())
+    }
+
+    fn clear(&self) -> error::Result<()> {
+        self.call()?.clear();
+        Ok(())
+    }
+
+    fn scan(&self, cursor: u64, count: usize) -> error::Result<(u64, Vec<BytesMut>)> {
+        let entries = self.call()?;
+        let keys = entries
+            .keys()
+            .skip(cursor as usize)
+            .take(count)
+            .cloned()
+            .collect::<Vec<_>>();
+        let next = cursor as usize + keys.len();
+        let next = if next >= entries.len() {
+            0
+        } else {
+            next as u64
+        };
+        Ok((next, keys))
+    }
+}
+
+/// Counts allocations made by the current thread, see [`allocations`].
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Number of allocations the current thread made while running `f`.
+pub fn allocations<T>(f: impl FnOnce() -> T) -> (usize, T) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (ALLOCATIONS.with(Cell::get) - before, result)
+}